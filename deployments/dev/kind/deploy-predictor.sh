@@ -29,10 +29,56 @@ echo "Creating predictions table..."
 psql -h localhost -p 4567 -d dev -U root \
   -f "$SCRIPT_DIR/manifests/risingwave/schemas/005_predictions.sql" || true
 
+echo "Adding prediction interval columns to existing predictions tables..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/014_predictions_intervals.sql" || true
+
+echo "Creating prediction_events outbox..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/012_prediction_events.sql" || true
+
+echo "Creating prediction_event_offsets table..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/015_prediction_event_offsets.sql" || true
+
+echo "Creating model registry..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/013_models.sql" || true
+
+echo "Creating webhooks tables..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/008_webhooks.sql" || true
+
+echo "Creating idempotency_keys table..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/009_idempotency_keys.sql" || true
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/016_idempotency_claims.sql" || true
+
+echo "Creating pair_aliases table..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/010_pair_aliases.sql" || true
+
+echo "Creating rejected_predictions table..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/011_rejected_predictions.sql" || true
+
+echo "Creating orderbook_snapshots table..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/017_orderbook_snapshots.sql" || true
+
+echo "Creating funding_rates and open_interest tables..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/018_derivatives.sql" || true
+
 echo "Creating lunarcrush_metrics table..."
 psql -h localhost -p 4567 -d dev -U root \
   -f "$SCRIPT_DIR/manifests/risingwave/schemas/006_lunarcrush.sql" || true
 
+echo "Creating prices view..."
+psql -h localhost -p 4567 -d dev -U root \
+  -f "$SCRIPT_DIR/manifests/risingwave/schemas/007_prices.sql" || true
+
 kill $PF_PID 2>/dev/null || true
 
 echo "Deploying predictor manifests..."
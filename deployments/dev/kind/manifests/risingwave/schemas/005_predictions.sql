@@ -10,7 +10,12 @@ CREATE TABLE IF NOT EXISTS predictions (
     -- Prediction details
     predicted_price DOUBLE PRECISION,
     model_version VARCHAR,
-    predicted_ts_ms BIGINT,        -- Timestamp of predicted price (ms)
+    predicted_ts_ms BIGINT,        -- Timestamp of predicted price (ms), NULL = current fair value
+
+    -- Probabilistic models only; NULL for point estimates
+    lower_bound DOUBLE PRECISION,  -- Lower end of the prediction interval
+    upper_bound DOUBLE PRECISION,  -- Upper end of the prediction interval
+    quantile DOUBLE PRECISION,     -- Quantile level predicted_price represents (e.g., 0.5)
 
     PRIMARY KEY (pair, ts_ms, model_name)
 );
@@ -0,0 +1,10 @@
+-- Prices: realized market price per pair, one row per minute
+-- Actual prices the prediction API evaluates predictions against
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS prices AS
+SELECT
+    pair,
+    window_end_ms AS ts_ms,        -- Close of the 60s candle ending at this time (ms)
+    close AS price
+FROM candles
+WHERE candle_seconds = 60;
@@ -0,0 +1,29 @@
+-- Webhooks: URLs the prediction API POSTs new predictions to
+-- Managed through the prediction API's /admin/webhooks endpoints
+
+CREATE TABLE IF NOT EXISTS webhooks (
+    id VARCHAR PRIMARY KEY,        -- Random hex id assigned by the API
+    url VARCHAR,                   -- https:// or http:// URL predictions are POSTed to
+    pairs VARCHAR[],               -- Pairs to deliver, NULL = all pairs
+    secret VARCHAR,                -- HMAC-SHA256 key for X-Signature
+    created_ts_ms BIGINT           -- When the webhook was registered (ms)
+);
+
+-- Deliveries that failed every attempt, kept for inspection for
+-- DEAD_LETTER_RETENTION_MS
+CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+    id VARCHAR PRIMARY KEY,        -- Random hex id assigned by the API
+    webhook_id VARCHAR,            -- Webhook the delivery was for
+    url VARCHAR,                   -- URL at the time of delivery
+    payload VARCHAR,               -- JSON body that was POSTed
+    attempts INT,                  -- Delivery attempts made
+    last_status INT,               -- HTTP status of the last attempt, NULL = no response
+    last_error VARCHAR,            -- Why the last attempt failed
+    failed_ts_ms BIGINT            -- When the delivery was given up (ms)
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_dead_letters_webhook
+ON webhook_dead_letters (webhook_id, failed_ts_ms DESC);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_dead_letters_failed
+ON webhook_dead_letters (failed_ts_ms);
@@ -0,0 +1,14 @@
+-- Idempotency keys: responses to prediction writes, replayed on retries
+-- Managed by the prediction API; rows expire after IDEMPOTENCY_KEY_TTL_MS,
+-- or after 60s while still in progress
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key VARCHAR PRIMARY KEY,       -- Idempotency-Key header sent by the client
+    request_hash VARCHAR,          -- SHA-256 of method, path, query and body
+    status INT,                    -- HTTP status of the response, NULL = in progress
+    content_type VARCHAR,          -- Content-Type of the response
+    location VARCHAR,              -- Location of the response, if any
+    body BYTEA,                    -- Body of the response
+    created_ts_ms BIGINT,          -- When the first request arrived (ms)
+    claim VARCHAR                  -- Random token identifying that request's claim
+);
@@ -0,0 +1,9 @@
+-- Pair aliases: exchange-native symbols mapped to canonical pairs
+-- Managed through the prediction API's /admin/pair-aliases endpoints;
+-- ingested predictions are stored under the canonical pair
+
+CREATE TABLE IF NOT EXISTS pair_aliases (
+    alias VARCHAR PRIMARY KEY,     -- Normalized symbol, e.g. XBTUSD for "XBT/USD"
+    pair VARCHAR,                  -- Canonical pair, e.g. BTCUSD
+    created_ts_ms BIGINT           -- When the alias was last set (ms)
+);
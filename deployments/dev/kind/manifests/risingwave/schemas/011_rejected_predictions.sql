@@ -0,0 +1,16 @@
+-- Rejected predictions: ingested payloads that failed validation
+-- Written by the prediction API's ingestion endpoints, inspected and
+-- replayed through /admin/rejected-predictions; rows expire after
+-- REJECTED_PREDICTION_RETENTION_MS
+
+CREATE TABLE IF NOT EXISTS rejected_predictions (
+    id VARCHAR PRIMARY KEY,        -- Random hex id assigned by the API
+    pair VARCHAR,                  -- Pair of the payload, after alias mapping
+    model_name VARCHAR,            -- Model of the payload
+    payload VARCHAR,               -- JSON prediction as submitted
+    reason VARCHAR,                -- Why validation failed
+    rejected_ts_ms BIGINT          -- When it was rejected (ms)
+);
+
+CREATE INDEX IF NOT EXISTS idx_rejected_predictions_time
+ON rejected_predictions (rejected_ts_ms DESC);
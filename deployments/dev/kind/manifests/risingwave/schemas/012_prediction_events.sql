@@ -0,0 +1,27 @@
+-- Prediction events: outbox of prediction writes
+-- Written just before each prediction by the predictor and the prediction
+-- API's ingestion endpoints; relayed by the prediction API to streams, long
+-- polls and webhooks once the prediction is stored. Rows expire after
+-- PREDICTION_EVENT_RETENTION_MS.
+-- Tables created with the earlier BIGSERIAL seq key must be dropped and
+-- recreated; they only hold events awaiting relay.
+
+CREATE TABLE IF NOT EXISTS prediction_events (
+    id VARCHAR PRIMARY KEY,        -- Random hex id assigned by the writer
+
+    -- The prediction as written
+    pair VARCHAR,
+    ts_ms BIGINT,
+    model_name VARCHAR,
+    predicted_price DOUBLE PRECISION,
+    model_version VARCHAR,
+    predicted_ts_ms BIGINT,
+    lower_bound DOUBLE PRECISION,
+    upper_bound DOUBLE PRECISION,
+    quantile DOUBLE PRECISION,
+
+    created_ts_ms BIGINT           -- When the event was written (ms), by the writer's clock
+);
+
+CREATE INDEX IF NOT EXISTS idx_prediction_events_created
+ON prediction_events (created_ts_ms);
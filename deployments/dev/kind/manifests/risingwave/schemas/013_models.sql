@@ -0,0 +1,21 @@
+-- Model registry: model versions with the metadata to reproduce them
+-- Managed through the prediction API's /admin/models endpoints
+
+CREATE TABLE IF NOT EXISTS models (
+    id VARCHAR PRIMARY KEY,        -- Random hex id assigned by the API
+    pair VARCHAR,                  -- Trading pair the model predicts
+    model_name VARCHAR,            -- As in predictions.model_name
+    model_version VARCHAR,         -- As in predictions.model_version
+    artifact_uri VARCHAR,          -- Where the trained artifact is stored, NULL = unknown
+    features VARCHAR[],            -- Input features, in model order
+    training_from_ts_ms BIGINT,    -- Start of the training data window (ms)
+    training_to_ts_ms BIGINT,      -- End of the training data window (ms)
+    status VARCHAR,                -- staging, production or retired
+    traffic_pct INT,               -- Share of default traffic served from a staging version (0-100)
+    promoted_ts_ms BIGINT,         -- When the version last became production (ms), NULL = never
+    created_ts_ms BIGINT,          -- When the version was registered (ms)
+    updated_ts_ms BIGINT           -- When the entry last changed (ms)
+);
+
+CREATE INDEX IF NOT EXISTS idx_models_pair
+ON models (pair, model_name, model_version);
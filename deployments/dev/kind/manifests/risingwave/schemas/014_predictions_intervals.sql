@@ -0,0 +1,9 @@
+-- Prediction interval columns for predictions tables created before they
+-- were added to 005_predictions.sql, which CREATE TABLE IF NOT EXISTS
+-- leaves as they are
+-- Each statement fails harmlessly with "column already exists" on tables
+-- that have the column; psql carries on with the next one
+
+ALTER TABLE predictions ADD COLUMN lower_bound DOUBLE PRECISION;
+ALTER TABLE predictions ADD COLUMN upper_bound DOUBLE PRECISION;
+ALTER TABLE predictions ADD COLUMN quantile DOUBLE PRECISION;
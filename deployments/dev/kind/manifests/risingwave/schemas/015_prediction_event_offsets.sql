@@ -0,0 +1,9 @@
+-- Prediction event offsets: how far each consumer of the prediction_events
+-- outbox has got, so it resumes there after a restart
+-- Written by the prediction API's webhook dispatcher
+
+CREATE TABLE IF NOT EXISTS prediction_event_offsets (
+    consumer VARCHAR PRIMARY KEY,  -- Consumer name, e.g. 'webhooks'
+    settled_ts_ms BIGINT,          -- Every event written at or before this was handled (ms)
+    updated_ts_ms BIGINT           -- When the offset was last saved (ms)
+);
@@ -0,0 +1,6 @@
+-- Claim column for idempotency_keys tables created before it was added to
+-- 009_idempotency_keys.sql, which CREATE TABLE IF NOT EXISTS leaves as it
+-- is
+-- Fails harmlessly with "column already exists" on tables that have it
+
+ALTER TABLE idempotency_keys ADD COLUMN claim VARCHAR;
@@ -0,0 +1,14 @@
+-- Order book snapshots: top levels of each side of a pair's L2 book
+-- Collected from the exchange by the prediction API (ORDERBOOK_PAIRS) and
+-- served by GET /orderbook, as the raw inputs of book features
+
+CREATE TABLE IF NOT EXISTS orderbook_snapshots (
+    pair VARCHAR,                     -- Trading pair (e.g., BTCUSDT)
+    ts_ms BIGINT,                     -- Exchange transaction time of the snapshot (ms)
+    last_update_id BIGINT,            -- Exchange's update id of the book
+    bid_prices DOUBLE PRECISION[],    -- Bids, best (highest) first
+    bid_quantities DOUBLE PRECISION[],
+    ask_prices DOUBLE PRECISION[],    -- Asks, best (lowest) first
+    ask_quantities DOUBLE PRECISION[],
+    PRIMARY KEY (pair, ts_ms)
+);
@@ -0,0 +1,19 @@
+-- Perpetual futures funding rates and open interest
+-- Collected from the exchange by the prediction API (DERIVATIVES_PAIRS) and
+-- served by GET /funding-rates and GET /open-interest, as inputs of the
+-- perp-pair models
+
+CREATE TABLE IF NOT EXISTS funding_rates (
+    pair VARCHAR,                     -- Trading pair (e.g., BTCUSDT)
+    ts_ms BIGINT,                     -- Funding time (ms)
+    funding_rate DOUBLE PRECISION,    -- Rate paid by longs to shorts, as a fraction
+    mark_price DOUBLE PRECISION,      -- Mark price at funding time; NULL where the exchange has none
+    PRIMARY KEY (pair, ts_ms)
+);
+
+CREATE TABLE IF NOT EXISTS open_interest (
+    pair VARCHAR,                     -- Trading pair (e.g., BTCUSDT)
+    ts_ms BIGINT,                     -- Exchange time of the reading (ms)
+    open_interest DOUBLE PRECISION,   -- Open contracts, in the base asset
+    PRIMARY KEY (pair, ts_ms)
+);
@@ -0,0 +1,152 @@
+"""Golden protobuf fixtures for the decoder in src/protobuf.rs.
+
+The fixtures are encoded by Google's protobuf runtime, not by this service,
+so the decoder is tested against what real producers send. The messages of
+../prediction.proto are declared here field for field (the pure-Python
+runtime cannot parse .proto files), alongside a `NewPrediction` from a
+newer schema with fields this service does not know.
+
+Regenerate with any protobuf >= 4 runtime:
+
+    pip install protobuf
+    python3 proto/testdata/generate.py
+
+The checked-in fixtures were written by protobuf 6.32.0.
+"""
+
+from pathlib import Path
+
+from google.protobuf import descriptor_pb2, descriptor_pool, message_factory
+import google.protobuf
+
+F = descriptor_pb2.FieldDescriptorProto
+OUT = Path(__file__).parent
+
+
+def field(message, name, number, type_, label=F.LABEL_OPTIONAL, type_name=None, oneof=None):
+    f = message.field.add(name=name, number=number, type=type_, label=label)
+    if type_name:
+        f.type_name = type_name
+    if oneof is not None:
+        # proto3 `optional`: a synthetic oneof holding just this field
+        f.proto3_optional = True
+        f.oneof_index = oneof
+    return f
+
+
+def new_prediction(file, name):
+    message = file.message_type.add(name=name)
+    for oneof in ("_predicted_ts_ms", "_lower_bound", "_upper_bound", "_quantile"):
+        message.oneof_decl.add(name=oneof)
+    field(message, "pair", 1, F.TYPE_STRING)
+    field(message, "model_name", 2, F.TYPE_STRING)
+    field(message, "model_version", 3, F.TYPE_STRING)
+    field(message, "ts_ms", 4, F.TYPE_INT64)
+    field(message, "predicted_ts_ms", 5, F.TYPE_INT64, oneof=0)
+    field(message, "predicted_price", 6, F.TYPE_DOUBLE)
+    field(message, "lower_bound", 7, F.TYPE_DOUBLE, oneof=1)
+    field(message, "upper_bound", 8, F.TYPE_DOUBLE, oneof=2)
+    field(message, "quantile", 9, F.TYPE_DOUBLE, oneof=3)
+    return message
+
+
+def schema():
+    file = descriptor_pb2.FileDescriptorProto(
+        name="prediction.proto", package="cryptopred.prediction.v1", syntax="proto3"
+    )
+    new_prediction(file, "NewPrediction")
+    bulk = file.message_type.add(name="BulkRequest")
+    field(bulk, "predictions", 1, F.TYPE_MESSAGE, F.LABEL_REPEATED,
+          ".cryptopred.prediction.v1.NewPrediction")
+
+    # What a producer built against a later version of the schema sends
+    source = file.message_type.add(name="Source")
+    field(source, "host", 1, F.TYPE_STRING)
+    field(source, "pid", 2, F.TYPE_UINT32)
+    newer = new_prediction(file, "NewerPrediction")
+    field(newer, "comment", 10, F.TYPE_STRING)
+    field(newer, "horizons_ms", 11, F.TYPE_INT64, F.LABEL_REPEATED)
+    field(newer, "features", 12, F.TYPE_FIXED32)
+    field(newer, "drift", 13, F.TYPE_SINT64)
+    field(newer, "source", 14, F.TYPE_MESSAGE, type_name=".cryptopred.prediction.v1.Source")
+    newer_bulk = file.message_type.add(name="NewerBulkRequest")
+    field(newer_bulk, "predictions", 1, F.TYPE_MESSAGE, F.LABEL_REPEATED,
+          ".cryptopred.prediction.v1.NewerPrediction")
+    field(newer_bulk, "batch_id", 2, F.TYPE_STRING)
+
+    pool = descriptor_pool.DescriptorPool()
+    pool.Add(file)
+    return {
+        name: message_factory.GetMessageClass(
+            pool.FindMessageTypeByName(f"cryptopred.prediction.v1.{name}")
+        )
+        for name in ("NewPrediction", "BulkRequest", "NewerPrediction", "NewerBulkRequest")
+    }
+
+
+def main():
+    m = schema()
+    NewPrediction, BulkRequest = m["NewPrediction"], m["BulkRequest"]
+
+    full = NewPrediction(
+        pair="BTCUSDT",
+        model_name="lgbm",
+        model_version="v1",
+        ts_ms=1_700_000_000_000,
+        predicted_ts_ms=1_700_000_060_000,
+        predicted_price=65000.5,
+        lower_bound=64000.25,
+        upper_bound=66000.75,
+        quantile=0.5,
+    )
+    # Optionals left unset, proto3 defaults (0, "") left out of the encoding
+    minimal = NewPrediction(pair="ETHUSDT", model_name="arima", predicted_price=3100.0)
+    # Negative int64 values take ten bytes; optionals set to zero are still
+    # sent, unlike plain fields holding the default
+    negative = NewPrediction(
+        pair="BTCUSDT",
+        model_name="lgbm",
+        model_version="v1",
+        ts_ms=-1,
+        predicted_ts_ms=-1_700_000_000_000,
+        predicted_price=-0.5,
+        lower_bound=0.0,
+        quantile=0.0,
+    )
+    unknown = m["NewerPrediction"](
+        pair="SOLUSDT",
+        model_name="tft",
+        model_version="v3",
+        ts_ms=1_700_000_000_000,
+        predicted_price=150.125,
+        upper_bound=155.0,
+        comment="added later",
+        horizons_ms=[60_000, -60_000, 3_600_000],
+        features=42,
+        drift=-7,
+    )
+    unknown.source.host = "runner-1"
+    unknown.source.pid = 4242
+    newer_bulk = m["NewerBulkRequest"](batch_id="batch-7")
+    newer_bulk.predictions.add().CopyFrom(unknown)
+
+    bulk = BulkRequest()
+    for prediction in (full, minimal, negative):
+        bulk.predictions.add().CopyFrom(prediction)
+
+    fixtures = {
+        "new_prediction.pb": full,
+        "new_prediction_minimal.pb": minimal,
+        "new_prediction_negative.pb": negative,
+        "new_prediction_unknown_fields.pb": unknown,
+        "bulk_request.pb": bulk,
+        "bulk_request_unknown_fields.pb": newer_bulk,
+    }
+    for name, message in fixtures.items():
+        (OUT / name).write_bytes(message.SerializeToString())
+        print(f"{name}: {message.ByteSize()} bytes")
+    print(f"protobuf {google.protobuf.__version__}")
+
+
+if __name__ == "__main__":
+    main()
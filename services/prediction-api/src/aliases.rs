@@ -0,0 +1,70 @@
+//! Cached pair alias map, read on every prediction write.
+//!
+//! The map is loaded on first use and swapped for a fresh one whenever it
+//! changes through this replica's admin API, and periodically to pick up
+//! changes made through other replicas.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::sync::watch;
+
+use crate::db;
+use crate::error::ApiError;
+
+/// How often the map is reloaded from the database.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Alias to canonical pair.
+pub type AliasMap = HashMap<String, String>;
+
+/// Handle to the cache; cheap to clone.
+#[derive(Clone)]
+pub struct Aliases {
+    pool: PgPool,
+    map: Arc<RwLock<Option<Arc<AliasMap>>>>,
+}
+
+impl Aliases {
+    /// Start reloading the map from `pool` until `shutdown` turns true.
+    pub fn start(pool: PgPool, mut shutdown: watch::Receiver<bool>) -> Self {
+        let aliases = Self {
+            pool,
+            map: Arc::default(),
+        };
+
+        let reloader = aliases.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(RELOAD_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown.wait_for(|&closed| closed) => break,
+                }
+                if let Err(e) = reloader.reload().await {
+                    tracing::warn!(error = %e, "Failed to load pair aliases");
+                }
+            }
+        });
+
+        aliases
+    }
+
+    /// The current map, loaded now if it never was.
+    pub async fn get(&self) -> Result<Arc<AliasMap>, ApiError> {
+        let cached = self.map.read().expect("alias lock poisoned").clone();
+        match cached {
+            Some(map) => Ok(map),
+            None => self.reload().await,
+        }
+    }
+
+    /// Load the map again, after it was changed.
+    pub async fn reload(&self) -> Result<Arc<AliasMap>, ApiError> {
+        let map = Arc::new(db::get_pair_alias_map(&self.pool).await?);
+        *self.map.write().expect("alias lock poisoned") = Some(map.clone());
+        Ok(map)
+    }
+}
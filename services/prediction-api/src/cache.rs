@@ -0,0 +1,82 @@
+//! Per-route `Cache-Control` policy, so CDNs and reverse proxies can absorb
+//! read traffic.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use axum::http::HeaderValue;
+
+/// Default `CACHE_MAX_AGE`.
+pub const DEFAULT_CACHE_MAX_AGE: &str = "/health=0;/ready=0;/predictions=1;/predictions/latest=1";
+
+/// Seconds responses may be cached for, by route.
+///
+/// Routes are matched by their template, e.g. `/models/{model_name}/predictions`.
+#[derive(Debug, Clone, Default)]
+pub struct CachePolicy(BTreeMap<String, u32>);
+
+impl CachePolicy {
+    /// `Cache-Control` for successful responses from `route`; `None` when the
+    /// route has no policy.
+    ///
+    /// Zero forbids storing the response at all. Otherwise responses may be
+    /// cached for that long; `public` is never set, so responses to
+    /// authenticated requests stay out of shared caches.
+    pub fn header(&self, route: &str) -> Option<HeaderValue> {
+        self.0.get(route).map(|&max_age| match max_age {
+            0 => HeaderValue::from_static("no-store"),
+            max_age => HeaderValue::from_str(&format!("max-age={max_age}"))
+                .expect("max-age is a valid header value"),
+        })
+    }
+}
+
+/// Parses `route=seconds;route=seconds;...`.
+impl FromStr for CachePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut routes = BTreeMap::new();
+
+        for entry in s.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (route, max_age) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("route without max-age: {}", entry))?;
+            let route = route.trim();
+            if !route.starts_with('/') {
+                return Err(format!("invalid route: {:?}", route));
+            }
+            let max_age = max_age
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid max-age for {}: {}", route, max_age.trim()))?;
+
+            routes.insert(route.to_string(), max_age);
+        }
+
+        Ok(Self(routes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_policies() {
+        let policy: CachePolicy = "/health=0; /predictions = 5".parse().unwrap();
+        assert_eq!(policy.header("/health").unwrap(), "no-store");
+        assert_eq!(policy.header("/predictions").unwrap(), "max-age=5");
+        assert_eq!(policy.header("/prices"), None);
+
+        assert!(DEFAULT_CACHE_MAX_AGE.parse::<CachePolicy>().is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_entries() {
+        assert!("/health".parse::<CachePolicy>().is_err());
+        assert!("health=0".parse::<CachePolicy>().is_err());
+        assert!("/health=-1".parse::<CachePolicy>().is_err());
+        assert!("/health=soon".parse::<CachePolicy>().is_err());
+    }
+}
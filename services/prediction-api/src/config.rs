@@ -1,11 +1,24 @@
 //! Configuration management for the prediction API.
 
 use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
+use crate::cache::{CachePolicy, DEFAULT_CACHE_MAX_AGE};
+use crate::db::NonFinitePrice;
 use crate::error::ApiError;
+use crate::experiments::StickyBy;
+use crate::orderbook::DEPTHS;
+use crate::projection::{ProjectionProfiles, DEFAULT_PROFILES};
+use crate::routes::predictions::validate_pair;
+use crate::routes::webhooks::validate_url;
+use crate::timestamp::TimestampFormat;
 
 /// Application configuration loaded from environment variables.
-#[derive(Debug, Clone)]
+///
+/// `Debug` is implemented by hand so secrets never end up in logs.
+#[derive(Clone)]
 pub struct Config {
     pub api_port: u16,
     pub pg_host: String,
@@ -13,12 +26,258 @@ pub struct Config {
     pub pg_database: String,
     pub pg_user: String,
     pub pg_password: String,
+    /// Database connections pooled
+    pub pg_max_connections: u32,
+    /// Fraction of pool connections in use at which requests are shed (0-1]
+    pub load_shed_threshold: f64,
+    /// Seconds suggested to clients in the `Retry-After` header when shedding
+    pub load_shed_retry_after_secs: u64,
+    /// Whether per-IP rate limiting is applied at all
+    pub rate_limit_enabled: bool,
+    /// Rate limit for cheap single-row reads
+    pub rate_limit_read: RateLimit,
+    /// Rate limit for heavy scans (history, exports)
+    pub rate_limit_heavy: RateLimit,
+    /// Rate limit for prediction ingestion
+    pub rate_limit_write: RateLimit,
+    /// Rate limit for admin endpoints
+    pub rate_limit_admin: RateLimit,
+    /// How timestamps are rendered in prediction responses
+    pub timestamp_format: TimestampFormat,
+    /// Bearer token for admin endpoints; admin endpoints are disabled when empty
+    pub admin_api_key: String,
+    /// Bearer token for prediction ingestion; ingestion is disabled when empty
+    pub ingest_api_key: String,
+    /// Widest time range a history request may cover (ms)
+    pub max_history_range_ms: i64,
+    /// Most predictions `/predictions/recent` may return
+    pub max_recent_predictions: u32,
+    /// Pair whose latest prediction `GET /` returns; an endpoint index when unset
+    pub default_pair: Option<String>,
+    /// Whether rows with a NaN/infinite price are dropped or served flagged
+    pub non_finite_price: NonFinitePrice,
+    /// Field subsets selectable with `?profile=`
+    pub prediction_profiles: ProjectionProfiles,
+    /// Abort startup when the query self-test fails instead of warning
+    pub startup_selftest_strict: bool,
+    /// Largest `|delta_pct|` still reported as a `flat` direction
+    pub direction_flat_threshold_pct: f64,
+    /// `Cache-Control` max-age per route
+    pub cache_max_age: CachePolicy,
+    /// When the unprefixed aliases of `/v1` routes may be removed (ms),
+    /// announced in their `Sunset` header
+    pub legacy_sunset_ms: Option<u64>,
+    /// Algorithms responses may be compressed with; none disables compression
+    pub compression: Vec<Compression>,
+    /// Smallest response body (bytes) worth compressing
+    pub compression_min_size: u16,
+    /// How often the prediction stream and the webhook dispatcher check for
+    /// new predictions (ms)
+    pub feed_poll_interval_ms: u64,
+    /// How long `GET /predictions?wait=true` holds a request (ms)
+    pub long_poll_timeout_ms: u64,
+    /// Whether this replica delivers webhooks
+    pub webhook_dispatcher_enabled: bool,
+    /// How long a webhook delivery may take (ms)
+    pub webhook_timeout_ms: u64,
+    /// Attempts before a webhook delivery becomes a dead letter
+    pub webhook_max_attempts: u32,
+    /// Delay before the first webhook retry (ms); doubles with each retry
+    pub webhook_retry_base_ms: u64,
+    /// Deliveries made to one webhook at a time
+    pub webhook_concurrency: usize,
+    /// Deliveries to one webhook that may wait for a turn; more become dead
+    /// letters
+    pub webhook_queue_size: usize,
+    /// How long an `Idempotency-Key` is remembered (ms)
+    pub idempotency_key_ttl_ms: u64,
+    /// Prediction writes processed at once
+    pub ingest_concurrency: usize,
+    /// Prediction writes that may wait for a turn before more are rejected
+    pub ingest_queue_size: usize,
+    /// How long a prediction write may wait for a turn (ms)
+    pub ingest_queue_timeout_ms: u64,
+    /// Exports streamed at once, each holding a database connection
+    pub export_concurrency: usize,
+    /// How long prediction events are kept in the outbox (ms)
+    pub prediction_event_retention_ms: u64,
+    /// How long rejected predictions are kept (ms)
+    pub rejected_prediction_retention_ms: u64,
+    /// How long webhook dead letters are kept (ms)
+    pub dead_letter_retention_ms: u64,
+    /// What keeps a client on one side of an A/B split
+    pub ab_sticky_by: StickyBy,
+    /// Base URL of the Binance USDⓈ-M futures REST API market data is
+    /// collected from
+    pub exchange_base_url: String,
+    /// Pairs whose order books this replica collects; none when empty
+    pub orderbook_pairs: Vec<String>,
+    /// Levels collected on each side of an order book
+    pub orderbook_depth: u32,
+    /// How often order books are collected (ms)
+    pub orderbook_interval_ms: u64,
+    /// Pairs whose funding rates and open interest this replica collects;
+    /// none when empty
+    pub derivatives_pairs: Vec<String>,
+    /// How often funding rates and open interest are collected (ms)
+    pub derivatives_interval_ms: u64,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("api_port", &self.api_port)
+            .field("pg_host", &self.pg_host)
+            .field("pg_port", &self.pg_port)
+            .field("pg_database", &self.pg_database)
+            .field("pg_user", &self.pg_user)
+            .field("pg_password", &redact(&self.pg_password))
+            .field("pg_max_connections", &self.pg_max_connections)
+            .field("load_shed_threshold", &self.load_shed_threshold)
+            .field(
+                "load_shed_retry_after_secs",
+                &self.load_shed_retry_after_secs,
+            )
+            .field("rate_limit_enabled", &self.rate_limit_enabled)
+            .field("rate_limit_read", &self.rate_limit_read)
+            .field("rate_limit_heavy", &self.rate_limit_heavy)
+            .field("rate_limit_write", &self.rate_limit_write)
+            .field("rate_limit_admin", &self.rate_limit_admin)
+            .field("timestamp_format", &self.timestamp_format)
+            .field("admin_api_key", &redact(&self.admin_api_key))
+            .field("ingest_api_key", &redact(&self.ingest_api_key))
+            .field("max_history_range_ms", &self.max_history_range_ms)
+            .field("max_recent_predictions", &self.max_recent_predictions)
+            .field("default_pair", &self.default_pair)
+            .field("non_finite_price", &self.non_finite_price)
+            .field("prediction_profiles", &self.prediction_profiles)
+            .field("startup_selftest_strict", &self.startup_selftest_strict)
+            .field(
+                "direction_flat_threshold_pct",
+                &self.direction_flat_threshold_pct,
+            )
+            .field("cache_max_age", &self.cache_max_age)
+            .field("legacy_sunset_ms", &self.legacy_sunset_ms)
+            .field("compression", &self.compression)
+            .field("compression_min_size", &self.compression_min_size)
+            .field("feed_poll_interval_ms", &self.feed_poll_interval_ms)
+            .field("long_poll_timeout_ms", &self.long_poll_timeout_ms)
+            .field(
+                "webhook_dispatcher_enabled",
+                &self.webhook_dispatcher_enabled,
+            )
+            .field("webhook_timeout_ms", &self.webhook_timeout_ms)
+            .field("webhook_max_attempts", &self.webhook_max_attempts)
+            .field("webhook_retry_base_ms", &self.webhook_retry_base_ms)
+            .field("webhook_concurrency", &self.webhook_concurrency)
+            .field("webhook_queue_size", &self.webhook_queue_size)
+            .field("idempotency_key_ttl_ms", &self.idempotency_key_ttl_ms)
+            .field("ingest_concurrency", &self.ingest_concurrency)
+            .field("ingest_queue_size", &self.ingest_queue_size)
+            .field("ingest_queue_timeout_ms", &self.ingest_queue_timeout_ms)
+            .field("export_concurrency", &self.export_concurrency)
+            .field(
+                "prediction_event_retention_ms",
+                &self.prediction_event_retention_ms,
+            )
+            .field(
+                "rejected_prediction_retention_ms",
+                &self.rejected_prediction_retention_ms,
+            )
+            .field("dead_letter_retention_ms", &self.dead_letter_retention_ms)
+            .field("ab_sticky_by", &self.ab_sticky_by)
+            .field("exchange_base_url", &self.exchange_base_url)
+            .field("orderbook_pairs", &self.orderbook_pairs)
+            .field("orderbook_depth", &self.orderbook_depth)
+            .field("orderbook_interval_ms", &self.orderbook_interval_ms)
+            .field("derivatives_pairs", &self.derivatives_pairs)
+            .field("derivatives_interval_ms", &self.derivatives_interval_ms)
+            .finish()
+    }
+}
+
+/// Hide a secret while still showing whether it is set.
+fn redact(secret: &str) -> &'static str {
+    if secret.is_empty() {
+        "<unset>"
+    } else {
+        "<redacted>"
+    }
+}
+
+/// Response compression algorithm, negotiated with `Accept-Encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Deflate,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(Self::Gzip),
+            "deflate" => Ok(Self::Deflate),
+            other => Err(format!("unsupported compression algorithm: {}", other)),
+        }
+    }
+}
+
+/// Parse a comma-separated list of compression algorithms.
+fn parse_compression(s: &str) -> Result<Vec<Compression>, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Per-IP rate limit applied to a group of routes.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Sustained requests per second
+    pub per_second: u64,
+    /// Requests allowed in a burst above the sustained rate
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Load a rate limit from `{prefix}_PER_SECOND` and `{prefix}_BURST`.
+    fn from_env(prefix: &str, per_second: u64, burst: u32) -> Result<Self, ApiError> {
+        let limit = Self {
+            per_second: env::var(format!("{prefix}_PER_SECOND"))
+                .map_or(Ok(per_second), |v| v.parse())
+                .map_err(|_| ApiError::Config(format!("Invalid {prefix}_PER_SECOND")))?,
+            burst: env::var(format!("{prefix}_BURST"))
+                .map_or(Ok(burst), |v| v.parse())
+                .map_err(|_| ApiError::Config(format!("Invalid {prefix}_BURST")))?,
+        };
+
+        if limit.burst == 0 {
+            return Err(ApiError::Config(format!("{prefix}_BURST must be positive")));
+        }
+
+        // The replenish period is counted in whole nanoseconds
+        if !(1..=1_000_000_000).contains(&limit.per_second) {
+            return Err(ApiError::Config(format!(
+                "{prefix}_PER_SECOND must be between 1 and 1000000000"
+            )));
+        }
+
+        Ok(limit)
+    }
+
+    /// Interval after which one request of quota is replenished.
+    pub fn period(&self) -> Duration {
+        Duration::from_nanos(1_000_000_000 / self.per_second)
+    }
 }
 
 impl Config {
     /// Load configuration from environment variables.
     pub fn from_env() -> Result<Self, ApiError> {
-        Ok(Self {
+        let config = Self {
             api_port: env::var("API_PORT")
                 .unwrap_or_else(|_| "3000".to_string())
                 .parse()
@@ -35,7 +294,337 @@ impl Config {
                 .unwrap_or_else(|_| "root".to_string()),
             pg_password: env::var("PG_PASSWORD")
                 .unwrap_or_default(),
-        })
+            pg_max_connections: env::var("PG_MAX_CONNECTIONS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid PG_MAX_CONNECTIONS".to_string()))?,
+            load_shed_threshold: env::var("LOAD_SHED_THRESHOLD")
+                .unwrap_or_else(|_| "0.9".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid LOAD_SHED_THRESHOLD".to_string()))?,
+            load_shed_retry_after_secs: env::var("LOAD_SHED_RETRY_AFTER_SECS")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid LOAD_SHED_RETRY_AFTER_SECS".to_string()))?,
+            rate_limit_enabled: env::var("RATE_LIMIT_ENABLED")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid RATE_LIMIT_ENABLED".to_string()))?,
+            rate_limit_read: RateLimit::from_env("RATE_LIMIT_READ", 100, 50)?,
+            rate_limit_heavy: RateLimit::from_env("RATE_LIMIT_HEAVY", 10, 5)?,
+            rate_limit_write: RateLimit::from_env("RATE_LIMIT_WRITE", 50, 50)?,
+            rate_limit_admin: RateLimit::from_env("RATE_LIMIT_ADMIN", 5, 5)?,
+            timestamp_format: env::var("TIMESTAMP_FORMAT")
+                .unwrap_or_else(|_| "ms".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid TIMESTAMP_FORMAT".to_string()))?,
+            admin_api_key: env::var("ADMIN_API_KEY").unwrap_or_default(),
+            ingest_api_key: env::var("INGEST_API_KEY").unwrap_or_default(),
+            max_history_range_ms: env::var("MAX_HISTORY_RANGE_MS")
+                .unwrap_or_else(|_| "7776000000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid MAX_HISTORY_RANGE_MS".to_string()))?,
+            max_recent_predictions: env::var("MAX_RECENT_PREDICTIONS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid MAX_RECENT_PREDICTIONS".to_string()))?,
+            default_pair: env::var("DEFAULT_PAIR").ok().filter(|p| !p.is_empty()),
+            non_finite_price: env::var("NON_FINITE_PRICE")
+                .unwrap_or_else(|_| "strict".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid NON_FINITE_PRICE".to_string()))?,
+            prediction_profiles: env::var("PREDICTION_PROFILES")
+                .unwrap_or_else(|_| DEFAULT_PROFILES.to_string())
+                .parse()
+                .map_err(|e| ApiError::Config(format!("Invalid PREDICTION_PROFILES: {}", e)))?,
+            startup_selftest_strict: env::var("STARTUP_SELFTEST_STRICT")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid STARTUP_SELFTEST_STRICT".to_string()))?,
+            direction_flat_threshold_pct: env::var("DIRECTION_FLAT_THRESHOLD_PCT")
+                .unwrap_or_else(|_| "0.1".to_string())
+                .parse()
+                .map_err(|_| {
+                    ApiError::Config("Invalid DIRECTION_FLAT_THRESHOLD_PCT".to_string())
+                })?,
+            cache_max_age: env::var("CACHE_MAX_AGE")
+                .unwrap_or_else(|_| DEFAULT_CACHE_MAX_AGE.to_string())
+                .parse()
+                .map_err(|e| ApiError::Config(format!("Invalid CACHE_MAX_AGE: {}", e)))?,
+            legacy_sunset_ms: env::var("LEGACY_SUNSET_MS")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse())
+                .transpose()
+                .map_err(|_| ApiError::Config("Invalid LEGACY_SUNSET_MS".to_string()))?,
+            compression: parse_compression(
+                &env::var("COMPRESSION").unwrap_or_else(|_| "gzip,deflate".to_string()),
+            )
+            .map_err(|e| ApiError::Config(format!("Invalid COMPRESSION: {}", e)))?,
+            compression_min_size: env::var("COMPRESSION_MIN_SIZE")
+                .unwrap_or_else(|_| "1024".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid COMPRESSION_MIN_SIZE".to_string()))?,
+            feed_poll_interval_ms: env::var("FEED_POLL_INTERVAL_MS")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid FEED_POLL_INTERVAL_MS".to_string()))?,
+            long_poll_timeout_ms: env::var("LONG_POLL_TIMEOUT_MS")
+                .unwrap_or_else(|_| "30000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid LONG_POLL_TIMEOUT_MS".to_string()))?,
+            webhook_dispatcher_enabled: env::var("WEBHOOK_DISPATCHER_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid WEBHOOK_DISPATCHER_ENABLED".to_string()))?,
+            webhook_timeout_ms: env::var("WEBHOOK_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid WEBHOOK_TIMEOUT_MS".to_string()))?,
+            webhook_max_attempts: env::var("WEBHOOK_MAX_ATTEMPTS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid WEBHOOK_MAX_ATTEMPTS".to_string()))?,
+            webhook_retry_base_ms: env::var("WEBHOOK_RETRY_BASE_MS")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid WEBHOOK_RETRY_BASE_MS".to_string()))?,
+            webhook_concurrency: env::var("WEBHOOK_CONCURRENCY")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid WEBHOOK_CONCURRENCY".to_string()))?,
+            webhook_queue_size: env::var("WEBHOOK_QUEUE_SIZE")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid WEBHOOK_QUEUE_SIZE".to_string()))?,
+            idempotency_key_ttl_ms: env::var("IDEMPOTENCY_KEY_TTL_MS")
+                .unwrap_or_else(|_| "86400000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid IDEMPOTENCY_KEY_TTL_MS".to_string()))?,
+            ingest_concurrency: env::var("INGEST_CONCURRENCY")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid INGEST_CONCURRENCY".to_string()))?,
+            ingest_queue_size: env::var("INGEST_QUEUE_SIZE")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid INGEST_QUEUE_SIZE".to_string()))?,
+            ingest_queue_timeout_ms: env::var("INGEST_QUEUE_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid INGEST_QUEUE_TIMEOUT_MS".to_string()))?,
+            export_concurrency: env::var("EXPORT_CONCURRENCY")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid EXPORT_CONCURRENCY".to_string()))?,
+            prediction_event_retention_ms: env::var("PREDICTION_EVENT_RETENTION_MS")
+                .unwrap_or_else(|_| "86400000".to_string())
+                .parse()
+                .map_err(|_| {
+                    ApiError::Config("Invalid PREDICTION_EVENT_RETENTION_MS".to_string())
+                })?,
+            rejected_prediction_retention_ms: env::var("REJECTED_PREDICTION_RETENTION_MS")
+                .unwrap_or_else(|_| "604800000".to_string())
+                .parse()
+                .map_err(|_| {
+                    ApiError::Config("Invalid REJECTED_PREDICTION_RETENTION_MS".to_string())
+                })?,
+            dead_letter_retention_ms: env::var("DEAD_LETTER_RETENTION_MS")
+                .unwrap_or_else(|_| "604800000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid DEAD_LETTER_RETENTION_MS".to_string()))?,
+            ab_sticky_by: env::var("AB_STICKY_BY")
+                .unwrap_or_else(|_| "api_key".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid AB_STICKY_BY".to_string()))?,
+            exchange_base_url: env::var("EXCHANGE_BASE_URL")
+                .unwrap_or_else(|_| "https://fapi.binance.com".to_string()),
+            orderbook_pairs: env::var("ORDERBOOK_PAIRS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|pair| !pair.is_empty())
+                .map(str::to_string)
+                .collect(),
+            orderbook_depth: env::var("ORDERBOOK_DEPTH")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid ORDERBOOK_DEPTH".to_string()))?,
+            orderbook_interval_ms: env::var("ORDERBOOK_INTERVAL_MS")
+                .unwrap_or_else(|_| "60000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid ORDERBOOK_INTERVAL_MS".to_string()))?,
+            derivatives_pairs: env::var("DERIVATIVES_PAIRS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|pair| !pair.is_empty())
+                .map(str::to_string)
+                .collect(),
+            derivatives_interval_ms: env::var("DERIVATIVES_INTERVAL_MS")
+                .unwrap_or_else(|_| "60000".to_string())
+                .parse()
+                .map_err(|_| ApiError::Config("Invalid DERIVATIVES_INTERVAL_MS".to_string()))?,
+        };
+
+        if !(config.load_shed_threshold > 0.0 && config.load_shed_threshold <= 1.0) {
+            return Err(ApiError::Config(
+                "LOAD_SHED_THRESHOLD must be in (0, 1]".to_string(),
+            ));
+        }
+
+        if config.max_history_range_ms <= 0 {
+            return Err(ApiError::Config(
+                "MAX_HISTORY_RANGE_MS must be positive".to_string(),
+            ));
+        }
+
+        if config.max_recent_predictions == 0 {
+            return Err(ApiError::Config(
+                "MAX_RECENT_PREDICTIONS must be positive".to_string(),
+            ));
+        }
+
+        if !(config.direction_flat_threshold_pct >= 0.0
+            && config.direction_flat_threshold_pct.is_finite())
+        {
+            return Err(ApiError::Config(
+                "DIRECTION_FLAT_THRESHOLD_PCT must be a non-negative number".to_string(),
+            ));
+        }
+
+        if config.feed_poll_interval_ms == 0 {
+            return Err(ApiError::Config(
+                "FEED_POLL_INTERVAL_MS must be positive".to_string(),
+            ));
+        }
+
+        if config.long_poll_timeout_ms == 0 {
+            return Err(ApiError::Config(
+                "LONG_POLL_TIMEOUT_MS must be positive".to_string(),
+            ));
+        }
+
+        if config.webhook_timeout_ms == 0 {
+            return Err(ApiError::Config(
+                "WEBHOOK_TIMEOUT_MS must be positive".to_string(),
+            ));
+        }
+
+        if config.webhook_max_attempts == 0 {
+            return Err(ApiError::Config(
+                "WEBHOOK_MAX_ATTEMPTS must be positive".to_string(),
+            ));
+        }
+
+        if config.webhook_concurrency == 0 {
+            return Err(ApiError::Config(
+                "WEBHOOK_CONCURRENCY must be positive".to_string(),
+            ));
+        }
+
+        if config.webhook_queue_size == 0 {
+            return Err(ApiError::Config(
+                "WEBHOOK_QUEUE_SIZE must be positive".to_string(),
+            ));
+        }
+
+        if config.idempotency_key_ttl_ms == 0 {
+            return Err(ApiError::Config(
+                "IDEMPOTENCY_KEY_TTL_MS must be positive".to_string(),
+            ));
+        }
+
+        if config.ingest_concurrency == 0 {
+            return Err(ApiError::Config(
+                "INGEST_CONCURRENCY must be positive".to_string(),
+            ));
+        }
+
+        if config.ingest_queue_size == 0 {
+            return Err(ApiError::Config(
+                "INGEST_QUEUE_SIZE must be positive".to_string(),
+            ));
+        }
+
+        if config.ingest_queue_timeout_ms == 0 {
+            return Err(ApiError::Config(
+                "INGEST_QUEUE_TIMEOUT_MS must be positive".to_string(),
+            ));
+        }
+
+        if config.pg_max_connections < 2 {
+            return Err(ApiError::Config(
+                "PG_MAX_CONNECTIONS must be at least 2".to_string(),
+            ));
+        }
+
+        // Exports hold their connection for the whole download, so they
+        // must leave some to everything else
+        if config.export_concurrency == 0
+            || config.export_concurrency >= config.pg_max_connections as usize
+        {
+            return Err(ApiError::Config(
+                "EXPORT_CONCURRENCY must be positive and below PG_MAX_CONNECTIONS".to_string(),
+            ));
+        }
+
+        // Events must outlive the feed's wait for writes committing late
+        if config.prediction_event_retention_ms < 600_000 {
+            return Err(ApiError::Config(
+                "PREDICTION_EVENT_RETENTION_MS must be at least 600000".to_string(),
+            ));
+        }
+
+        if config.rejected_prediction_retention_ms == 0 {
+            return Err(ApiError::Config(
+                "REJECTED_PREDICTION_RETENTION_MS must be positive".to_string(),
+            ));
+        }
+
+        if config.dead_letter_retention_ms == 0 {
+            return Err(ApiError::Config(
+                "DEAD_LETTER_RETENTION_MS must be positive".to_string(),
+            ));
+        }
+
+        validate_url(&config.exchange_base_url)
+            .map_err(|_| ApiError::Config("Invalid EXCHANGE_BASE_URL".to_string()))?;
+
+        for pair in &config.orderbook_pairs {
+            validate_pair(pair)
+                .map_err(|_| ApiError::Config("Invalid ORDERBOOK_PAIRS".to_string()))?;
+        }
+
+        if !DEPTHS.contains(&config.orderbook_depth) {
+            return Err(ApiError::Config(format!(
+                "ORDERBOOK_DEPTH must be one of {DEPTHS:?}"
+            )));
+        }
+
+        if config.orderbook_interval_ms == 0 {
+            return Err(ApiError::Config(
+                "ORDERBOOK_INTERVAL_MS must be positive".to_string(),
+            ));
+        }
+
+        for pair in &config.derivatives_pairs {
+            validate_pair(pair)
+                .map_err(|_| ApiError::Config("Invalid DERIVATIVES_PAIRS".to_string()))?;
+        }
+
+        if config.derivatives_interval_ms == 0 {
+            return Err(ApiError::Config(
+                "DERIVATIVES_INTERVAL_MS must be positive".to_string(),
+            ));
+        }
+
+        if let Some(pair) = &config.default_pair {
+            validate_pair(pair)
+                .map_err(|_| ApiError::Config("Invalid DEFAULT_PAIR".to_string()))?;
+        }
+
+        Ok(config)
     }
 
     /// Build PostgreSQL connection URL.
@@ -1,65 +1,2899 @@
 //! Database operations for predictions.
+//!
+//! Predictions and their `prediction_events` outbox rows are not written in
+//! one transaction: RisingWave has no read-write transactions, so the outbox
+//! does not give the guarantee of a transactional one. Each write is a
+//! statement of its own, event first, and an event is only relayed once a
+//! prediction matching it is stored. A write that stops halfway therefore
+//! publishes nothing, but a write that loses its key to another one must
+//! delete its event again, and stopping before it does can leave an event
+//! for a prediction stored by the other write with the same values. Bulk
+//! writes are not atomic either: when one chunk fails, the chunks before it
+//! stay stored and their events are relayed.
 
-use sqlx::{PgPool, Row};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use futures_util::{stream, Stream, TryStreamExt};
+use sqlx::query_builder::Separated;
+use sqlx::{postgres::PgRow, PgPool, Postgres, QueryBuilder, Row};
+use tokio::sync::{mpsc, OwnedSemaphorePermit};
 
 use crate::error::ApiError;
+use crate::idempotency::IdempotencyRecord;
+use crate::pagination::{Cursor, Page};
+use crate::purge::Expiring;
+use crate::query::FilteredSelect;
+use crate::routes::aggregates::{PriceBucket, PriceStats};
+use crate::routes::candles::{Candle, BASE_CANDLE_MS};
+use crate::routes::derivatives::{FundingRate, OpenInterest};
+use crate::routes::export::ExportQuery;
+use crate::routes::ingest::{NewPrediction, PredictionKey};
+use crate::routes::metrics::{
+    AccuracyMetrics, ErrorSums, EvaluatedPrediction, HitRate, IntervalCoverage,
+};
+use crate::routes::models::ModelSummary;
+use crate::routes::orderbook::OrderBook;
+use crate::routes::pairs::PairAlias;
+use crate::routes::pairs::PairSummary;
 use crate::routes::predictions::Prediction;
+use crate::routes::prices::PricePoint;
+use crate::routes::registry::{ModelStatus, RegisteredModel, RegistryQuery};
+use crate::routes::rejected::RejectedPrediction;
+use crate::routes::webhooks::{new_id, DeadLetter, Webhook};
+use crate::timestamp;
+
+/// How rows whose `predicted_price` is NaN or infinite are served.
+///
+/// serde_json writes such floats as `null`, which clients would read as a
+/// missing price, so they are never passed through unmarked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFinitePrice {
+    /// Drop the row: lists skip it, single lookups return 404
+    #[default]
+    Strict,
+    /// Serve the row with `"valid": false`
+    Lenient,
+}
+
+impl FromStr for NonFinitePrice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "lenient" => Ok(Self::Lenient),
+            other => Err(format!("unknown non-finite price mode: {}", other)),
+        }
+    }
+}
+
+/// Mode chosen at startup.
+static NON_FINITE_PRICE: OnceLock<NonFinitePrice> = OnceLock::new();
+
+/// Set the process-wide non-finite price mode. Only the first call has an
+/// effect.
+pub fn init(mode: NonFinitePrice) {
+    let _ = NON_FINITE_PRICE.set(mode);
+}
+
+fn non_finite_price() -> NonFinitePrice {
+    NON_FINITE_PRICE.get().copied().unwrap_or_default()
+}
+
+/// Condition excluding NaN and infinite prices from aggregates. Postgres
+/// sorts NaN above infinity, so the upper bound excludes it too.
+const FINITE_PRICE: &str =
+    "predicted_price > '-Infinity'::DOUBLE PRECISION AND predicted_price < 'Infinity'::DOUBLE PRECISION";
+
+/// Rows an export may read ahead of the client.
+const STREAM_BUFFER_ROWS: usize = 256;
+
+/// Spacing of the `prices` series, which holds one 60s candle close per
+/// minute.
+pub const PRICE_STEP_MS: i64 = 60_000;
+
+/// Columns the API reads from the `predictions` table.
+const PREDICTION_COLUMNS: &[&str] = &[
+    "pair",
+    "predicted_price",
+    "ts_ms",
+    "predicted_ts_ms",
+    "model_name",
+    "model_version",
+    "lower_bound",
+    "upper_bound",
+    "quantile",
+];
+
+/// Columns of the `models` registry that `SERVED_VERSION` reads on every
+/// default lookup.
+const SERVED_VERSION_COLUMNS: &[&str] = &["pair", "model_name", "model_version", "status"];
+
+/// Check that the `predictions` table has every column the API reads, and
+/// the `models` registry those its default lookups read.
+///
+/// Returns the missing columns as `table.column`; empty means the schema is
+/// usable.
+pub async fn probe_schema(pool: &PgPool) -> Result<Vec<String>, ApiError> {
+    let present: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT table_name::VARCHAR, column_name::VARCHAR
+        FROM information_schema.columns
+        WHERE table_name IN ('predictions', 'models')
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let expected = PREDICTION_COLUMNS
+        .iter()
+        .map(|column| ("predictions", column))
+        .chain(
+            SERVED_VERSION_COLUMNS
+                .iter()
+                .map(|column| ("models", column)),
+        );
+    Ok(expected
+        .filter(|(table, column)| !present.iter().any(|(t, c)| t == table && c == *column))
+        .map(|(table, column)| format!("{}.{}", table, column))
+        .collect())
+}
+
+/// Restricts lookups to one forecast series: a model, a model version
+/// and/or a horizon.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModelFilter<'a> {
+    pub name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    /// `predicted_ts_ms - ts_ms`; models are trained per horizon, so this
+    /// picks e.g. the 4h forecast among several written for a pair
+    pub horizon_ms: Option<i64>,
+}
+
+impl<'a> ModelFilter<'a> {
+    pub fn name(name: &'a str) -> Self {
+        Self {
+            name: Some(name),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.version.is_none() && self.horizon_ms.is_none()
+    }
+
+    fn apply<'args>(&self, select: FilteredSelect<'args>) -> FilteredSelect<'args>
+    where
+        'a: 'args,
+    {
+        select
+            .filter_opt("model_name", "=", self.name)
+            .filter_opt("model_version", "=", self.version)
+            .filter_opt("predicted_ts_ms - ts_ms", "=", self.horizon_ms)
+    }
+}
+
+/// Describes the filter in not-found messages, e.g. `model lgbm, horizon 300000 ms`.
+impl fmt::Display for ModelFilter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(name) = self.name {
+            parts.push(format!("model {}", name));
+        }
+        if let Some(version) = self.version {
+            parts.push(format!("version {}", version));
+        }
+        if let Some(horizon_ms) = self.horizon_ms {
+            parts.push(format!("horizon {} ms", horizon_ms));
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// Keeps only the production version's predictions of pairs that have one
+/// in the model registry; other pairs keep every model's.
+const SERVED_VERSION: &str = "NOT EXISTS (SELECT 1 FROM models m \
+     WHERE m.pair = predictions.pair AND m.status = 'production' \
+     AND (m.model_name IS DISTINCT FROM predictions.model_name \
+     OR m.model_version IS DISTINCT FROM predictions.model_version))";
 
 /// Get the latest prediction for a specific trading pair.
+///
+/// Only predictions matching `model` are considered. Without a model name
+/// or version, only the pair's production version is, if it has one.
+/// When `max_ts_ms` is given, predictions made after it are ignored, and
+/// with `max_interval_pct` those with wider intervals (see
+/// `within_interval`).
 pub async fn get_latest_prediction(
     pool: &PgPool,
     pair: &str,
+    model: ModelFilter<'_>,
+    max_ts_ms: Option<i64>,
+    max_interval_pct: Option<f64>,
 ) -> Result<Option<Prediction>, ApiError> {
+    let select = FilteredSelect::new(
+        "SELECT pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version, \
+         lower_bound, upper_bound, quantile \
+         FROM predictions",
+    )
+    .filter("pair", "=", pair);
+    let select = if model.name.is_none() && model.version.is_none() {
+        select.filter_with(|q| {
+            q.push(SERVED_VERSION);
+        })
+    } else {
+        select
+    };
+
+    let row = within_interval(model.apply(select), max_interval_pct)
+        .filter_opt("ts_ms", "<=", max_ts_ms)
+        .then("ORDER BY ts_ms DESC")
+        .limit(1)
+        .into_builder()
+        .build()
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row
+        .as_ref()
+        .map(prediction_from_row)
+        .transpose()?
+        .filter(servable))
+}
+
+/// Store a new prediction and return it as stored; `None` if a prediction
+/// with the same `(pair, ts_ms, model_name)` exists.
+///
+/// With `replace`, a prediction with the same key is deleted first, and
+/// earlier runs for the same target after storing it (see
+/// `delete_earlier_runs`); the flag returned says whether there were any.
+/// `None` then means a later run for the target is stored.
+///
+/// The prediction and its outbox event are not written in one transaction;
+/// see the module docs for what that leaves.
+pub async fn insert_prediction(
+    pool: &PgPool,
+    prediction: &NewPrediction,
+    replace: bool,
+) -> Result<Option<(Prediction, bool)>, ApiError> {
+    let rows = [prediction];
+    let mut replaced = if replace {
+        !delete_stored(pool, &rows).await?.is_empty()
+    } else if !get_stored(pool, &rows).await?.is_empty() {
+        return Ok(None);
+    } else {
+        false
+    };
+
+    let events = record_events(pool, &rows).await?;
+    let mut stored = if insert_rows(pool, &rows).await? {
+        get_stored(pool, &rows).await?
+    } else {
+        HashMap::new()
+    };
+    if replace && !stored.is_empty() {
+        let runs = delete_earlier_runs(pool, &rows).await?;
+        stored.retain(|key, _| !runs.superseded.contains(key));
+        replaced |= !runs.superseding.is_empty();
+    }
+
+    let (created, lost) = settle(&rows, events, &stored);
+    if !lost.is_empty() {
+        delete_events(pool, &lost).await?;
+    }
+    Ok(created
+        .first()
+        .and_then(|key| stored.remove(key))
+        .map(|stored| (stored, replaced)))
+}
+
+/// Check `rows` against the predictions `stored` after writing them: the
+/// keys of the rows stored as written, and the `events` of the rest, which
+/// another write of the key won and which must be deleted.
+///
+/// RisingWave overwrites a row with the same key where Postgres rejects it,
+/// so a concurrent write of the key may have won either way.
+fn settle(
+    rows: &[&NewPrediction],
+    events: Vec<String>,
+    stored: &HashMap<PredictionKey, Prediction>,
+) -> (Vec<PredictionKey>, Vec<String>) {
+    let mut created = Vec::new();
+    let mut lost = Vec::new();
+    for (row, event) in rows.iter().zip(events) {
+        match stored.get(&row.key()) {
+            Some(stored) if is_stored(row, stored) => created.push(row.key()),
+            _ => lost.push(event),
+        }
+    }
+    (created, lost)
+}
+
+/// Columns `insert_rows` and `record_events` write, in bind order.
+const WRITE_COLUMNS: &str = "pair, ts_ms, model_name, model_version, predicted_price, \
+                             predicted_ts_ms, lower_bound, upper_bound, quantile";
+
+fn push_prediction<'args>(
+    row: &mut Separated<'_, 'args, Postgres, &'static str>,
+    prediction: &'args NewPrediction,
+) {
+    row.push_bind(&prediction.pair)
+        .push_bind(prediction.ts_ms)
+        .push_bind(&prediction.model_name)
+        .push_bind(&prediction.model_version)
+        .push_bind(prediction.predicted_price)
+        .push_bind(prediction.predicted_ts_ms)
+        .push_bind(prediction.lower_bound)
+        .push_bind(prediction.upper_bound)
+        .push_bind(prediction.quantile);
+}
+
+/// Insert `predictions` in one statement. Returns false, inserting none, if
+/// Postgres rejects one for the key of a stored prediction.
+async fn insert_rows(pool: &PgPool, predictions: &[&NewPrediction]) -> Result<bool, ApiError> {
+    let mut insert =
+        QueryBuilder::<Postgres>::new(format!("INSERT INTO predictions ({WRITE_COLUMNS}) "));
+    insert.push_values(predictions, |mut row, prediction| {
+        push_prediction(&mut row, prediction)
+    });
+
+    match insert.build().execute(pool).await {
+        Ok(_) => Ok(true),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Append `predictions` to the outbox; returns the event ids.
+async fn record_events(
+    pool: &PgPool,
+    predictions: &[&NewPrediction],
+) -> Result<Vec<String>, ApiError> {
+    let ids: Vec<String> = predictions.iter().map(|_| new_id()).collect();
+    let created_ts_ms = timestamp::now_ms();
+
+    let mut insert = QueryBuilder::<Postgres>::new(format!(
+        "INSERT INTO prediction_events (id, {WRITE_COLUMNS}, created_ts_ms) "
+    ));
+    insert.push_values(ids.iter().zip(predictions), |mut row, (id, prediction)| {
+        row.push_bind(id);
+        push_prediction(&mut row, prediction);
+        row.push_bind(created_ts_ms);
+    });
+    insert.build().execute(pool).await?;
+    Ok(ids)
+}
+
+/// Drop the events of predictions that were not stored after all.
+async fn delete_events(pool: &PgPool, ids: &[String]) -> Result<(), ApiError> {
+    sqlx::query("DELETE FROM prediction_events WHERE id = ANY($1)")
+        .bind(ids)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// The stored predictions with the keys of `predictions`.
+async fn get_stored(
+    pool: &PgPool,
+    predictions: &[&NewPrediction],
+) -> Result<HashMap<PredictionKey, Prediction>, ApiError> {
+    let mut select = QueryBuilder::<Postgres>::new(
+        "SELECT pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version, \
+         lower_bound, upper_bound, quantile \
+         FROM predictions WHERE ",
+    );
+    for (i, ((pair, model_name), ts_ms)) in by_model(predictions, |p| Some(p.ts_ms))
+        .into_iter()
+        .enumerate()
+    {
+        if i > 0 {
+            select.push(" OR ");
+        }
+        select
+            .push("(pair = ")
+            .push_bind(pair)
+            .push(" AND model_name = ")
+            .push_bind(model_name)
+            .push(" AND ts_ms = ANY(")
+            .push_bind(ts_ms)
+            .push("))");
+    }
+
+    select
+        .build()
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let prediction = prediction_from_row(row)?;
+            let key = (
+                prediction.pair.clone(),
+                prediction.ts_ms,
+                prediction.model_name.clone(),
+            );
+            Ok((key, prediction))
+        })
+        .collect()
+}
+
+/// `value` of each of `predictions` that has one, grouped by pair and
+/// model, for matching rows with `= ANY`.
+fn by_model<'a>(
+    predictions: &[&'a NewPrediction],
+    value: impl Fn(&NewPrediction) -> Option<i64>,
+) -> BTreeMap<(&'a str, &'a str), Vec<i64>> {
+    let mut groups: BTreeMap<_, Vec<i64>> = BTreeMap::new();
+    for prediction in predictions {
+        if let Some(value) = value(prediction) {
+            groups
+                .entry((prediction.pair.as_str(), prediction.model_name.as_str()))
+                .or_default()
+                .push(value);
+        }
+    }
+    groups
+}
+
+/// Whether `stored` is `prediction` as written, rather than a prediction
+/// with the same key written by someone else.
+fn is_stored(prediction: &NewPrediction, stored: &Prediction) -> bool {
+    stored.model_version == prediction.model_version
+        && stored.predicted_price == prediction.predicted_price
+        && stored.predicted_ts_ms == prediction.predicted_ts_ms
+        && stored.lower_bound == prediction.lower_bound
+        && stored.upper_bound == prediction.upper_bound
+        && stored.quantile == prediction.quantile
+}
+
+/// Rows per multi-row statement, keeping each within the bind parameter
+/// limit.
+const INSERT_CHUNK: usize = 5_000;
+
+/// Store predictions that failed validation.
+pub async fn insert_rejected_predictions(
+    pool: &PgPool,
+    rejected: &[RejectedPrediction],
+) -> Result<(), ApiError> {
+    for chunk in rejected.chunks(INSERT_CHUNK) {
+        let mut insert = QueryBuilder::<Postgres>::new(
+            "INSERT INTO rejected_predictions \
+             (id, pair, model_name, payload, reason, rejected_ts_ms) ",
+        );
+        insert.push_values(chunk, |mut row, rejected| {
+            let field = |name| rejected.payload.get(name).and_then(|v| v.as_str());
+            row.push_bind(&rejected.id)
+                .push_bind(field("pair"))
+                .push_bind(field("model_name"))
+                .push_bind(rejected.payload.to_string())
+                .push_bind(&rejected.reason)
+                .push_bind(rejected.rejected_ts_ms);
+        });
+        insert.build().execute(pool).await?;
+    }
+    Ok(())
+}
+
+/// Up to `limit` rejected predictions, newest first, optionally only from
+/// one model.
+pub async fn get_rejected_predictions(
+    pool: &PgPool,
+    model_name: Option<&str>,
+    limit: i64,
+) -> Result<Vec<RejectedPrediction>, ApiError> {
+    let rows =
+        FilteredSelect::new("SELECT id, payload, reason, rejected_ts_ms FROM rejected_predictions")
+            .filter_opt("model_name", "=", model_name)
+            .then("ORDER BY rejected_ts_ms DESC, id")
+            .limit(limit)
+            .into_builder()
+            .build()
+            .fetch_all(pool)
+            .await?;
+
+    Ok(rows
+        .iter()
+        .map(rejected_from_row)
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// A rejected prediction by id.
+pub async fn get_rejected_prediction(
+    pool: &PgPool,
+    id: &str,
+) -> Result<Option<RejectedPrediction>, ApiError> {
     let row = sqlx::query(
-        r#"
-        SELECT pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version
-        FROM predictions
-        WHERE pair = $1
-        ORDER BY ts_ms DESC
-        LIMIT 1
-        "#,
+        "SELECT id, payload, reason, rejected_ts_ms FROM rejected_predictions WHERE id = $1",
     )
-    .bind(pair)
+    .bind(id)
     .fetch_optional(pool)
     .await?;
+    Ok(row.as_ref().map(rejected_from_row).transpose()?)
+}
+
+/// Remove a rejected prediction. Returns false when there was none.
+pub async fn delete_rejected_prediction(pool: &PgPool, id: &str) -> Result<bool, ApiError> {
+    let result = sqlx::query("DELETE FROM rejected_predictions WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+fn rejected_from_row(row: &PgRow) -> Result<RejectedPrediction, sqlx::Error> {
+    let payload: String = row.try_get("payload")?;
+    Ok(RejectedPrediction {
+        id: row.try_get("id")?,
+        // Stored payloads are always JSON; keep anything else as text
+        payload: serde_json::from_str(&payload).unwrap_or(serde_json::Value::String(payload)),
+        reason: row.try_get("reason")?,
+        rejected_ts_ms: row.try_get("rejected_ts_ms")?,
+    })
+}
+
+/// Keys of the rows `insert_predictions` stored.
+#[derive(Debug, Default)]
+pub struct Inserted {
+    /// Rows stored
+    pub created: HashSet<PredictionKey>,
+    /// Of those, rows that superseded earlier ones
+    pub replaced: HashSet<PredictionKey>,
+}
+
+/// Insert predictions with multi-row statements, skipping those whose key
+/// exists.
+///
+/// With `replace`, stored predictions with the same keys are deleted first
+/// and earlier runs for the same targets after (see `delete_earlier_runs`),
+/// so only predictions of which a later run is stored are skipped.
+///
+/// Neither the batch nor a chunk of it is written in one transaction, so
+/// when a write fails the chunks written before it stay stored; see the
+/// module docs.
+pub async fn insert_predictions(
+    pool: &PgPool,
+    predictions: &[&NewPrediction],
+    replace: bool,
+) -> Result<Inserted, ApiError> {
+    let mut inserted = Inserted::default();
+
+    for chunk in predictions.chunks(INSERT_CHUNK) {
+        let rows: Vec<&NewPrediction> = if replace {
+            inserted.replaced.extend(delete_stored(pool, chunk).await?);
+            chunk.to_vec()
+        } else {
+            let existing = get_stored(pool, chunk).await?;
+            chunk
+                .iter()
+                .filter(|prediction| !existing.contains_key(&prediction.key()))
+                .copied()
+                .collect()
+        };
+        if rows.is_empty() {
+            continue;
+        }
+
+        let events = record_events(pool, &rows).await?;
+        if !insert_rows(pool, &rows).await? {
+            // A row was written concurrently; insert the others one by one
+            for row in &rows {
+                insert_rows(pool, std::slice::from_ref(row)).await?;
+            }
+        }
+
+        let mut stored = get_stored(pool, &rows).await?;
+        if replace {
+            let runs = delete_earlier_runs(pool, &rows).await?;
+            stored.retain(|key, _| !runs.superseded.contains(key));
+            inserted.replaced.extend(runs.superseding);
+        }
+        let (created, lost) = settle(&rows, events, &stored);
+        inserted.created.extend(created);
+        if !lost.is_empty() {
+            delete_events(pool, &lost).await?;
+        }
+    }
+
+    inserted
+        .replaced
+        .retain(|key| inserted.created.contains(key));
+    Ok(inserted)
+}
+
+/// Delete the stored predictions with the keys of `predictions`. Returns
+/// the keys deleted.
+async fn delete_stored(
+    pool: &PgPool,
+    predictions: &[&NewPrediction],
+) -> Result<HashSet<PredictionKey>, ApiError> {
+    let mut delete = QueryBuilder::<Postgres>::new("DELETE FROM predictions WHERE ");
+    for (i, ((pair, model_name), ts_ms)) in by_model(predictions, |p| Some(p.ts_ms))
+        .into_iter()
+        .enumerate()
+    {
+        if i > 0 {
+            delete.push(" OR ");
+        }
+        delete
+            .push("(pair = ")
+            .push_bind(pair)
+            .push(" AND model_name = ")
+            .push_bind(model_name)
+            .push(" AND ts_ms = ANY(")
+            .push_bind(ts_ms)
+            .push("))");
+    }
+    delete.push(" RETURNING pair, ts_ms, model_name");
+
+    delete
+        .build()
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok((
+                row.try_get("pair")?,
+                row.try_get("ts_ms")?,
+                row.try_get("model_name")?,
+            ))
+        })
+        .collect()
+}
+
+/// Rows `delete_earlier_runs` deleted, by what they mean for the
+/// predictions it was given.
+#[derive(Debug, Default)]
+struct Runs {
+    /// Predictions that superseded an earlier run
+    superseding: HashSet<PredictionKey>,
+    /// Predictions superseded themselves by a later run
+    superseded: HashSet<PredictionKey>,
+}
+
+/// Keep only the latest run of each target of the stored `predictions`:
+/// delete the rows of the same model version for the same pair and
+/// `predicted_ts_ms` with an earlier `ts_ms`.
+///
+/// Run after inserting, so that replacing writes racing for a target
+/// converge on the latest run whichever order they land in: no writer
+/// deletes a row at or after the latest `ts_ms` it read, so that row
+/// survives every writer's delete.
+async fn delete_earlier_runs(
+    pool: &PgPool,
+    predictions: &[&NewPrediction],
+) -> Result<Runs, ApiError> {
+    let mut targets: BTreeMap<(&str, &str, &str), Vec<i64>> = BTreeMap::new();
+    for prediction in predictions {
+        if let Some((pair, model_name, model_version, predicted_ts_ms)) = prediction.target() {
+            targets
+                .entry((pair, model_name, model_version))
+                .or_default()
+                .push(predicted_ts_ms);
+        }
+    }
+    if targets.is_empty() {
+        return Ok(Runs::default());
+    }
+
+    let mut select = QueryBuilder::<Postgres>::new(
+        "SELECT pair, model_name, model_version, predicted_ts_ms, MAX(ts_ms) AS latest_ts_ms \
+         FROM predictions WHERE ",
+    );
+    for (i, ((pair, model_name, model_version), predicted_ts_ms)) in targets.iter().enumerate() {
+        if i > 0 {
+            select.push(" OR ");
+        }
+        select
+            .push("(pair = ")
+            .push_bind(*pair)
+            .push(" AND model_name = ")
+            .push_bind(*model_name)
+            .push(" AND model_version = ")
+            .push_bind(*model_version)
+            .push(" AND predicted_ts_ms = ANY(")
+            .push_bind(predicted_ts_ms)
+            .push("))");
+    }
+    select.push(" GROUP BY pair, model_name, model_version, predicted_ts_ms");
+    let latest = select.build().fetch_all(pool).await?;
+    if latest.is_empty() {
+        return Ok(Runs::default());
+    }
+
+    let mut delete = QueryBuilder::<Postgres>::new("DELETE FROM predictions WHERE ");
+    for (i, row) in latest.iter().enumerate() {
+        if i > 0 {
+            delete.push(" OR ");
+        }
+        delete
+            .push("(pair = ")
+            .push_bind(row.try_get::<String, _>("pair")?)
+            .push(" AND model_name = ")
+            .push_bind(row.try_get::<String, _>("model_name")?)
+            .push(" AND model_version = ")
+            .push_bind(row.try_get::<String, _>("model_version")?)
+            .push(" AND predicted_ts_ms = ")
+            .push_bind(row.try_get::<i64, _>("predicted_ts_ms")?)
+            .push(" AND ts_ms < ")
+            .push_bind(row.try_get::<i64, _>("latest_ts_ms")?)
+            .push(")");
+    }
+    delete.push(" RETURNING pair, ts_ms, model_name, model_version, predicted_ts_ms");
+
+    let keys: HashSet<PredictionKey> = predictions.iter().map(|p| p.key()).collect();
+    let targets: HashMap<_, PredictionKey> = predictions
+        .iter()
+        .filter_map(|p| Some((p.target()?, p.key())))
+        .collect();
 
-    match row {
-        Some(row) => Ok(Some(Prediction {
-            pair: row.get("pair"),
-            predicted_price: row.get("predicted_price"),
-            ts_ms: row.get("ts_ms"),
-            predicted_ts_ms: row.get("predicted_ts_ms"),
-            model_name: row.get("model_name"),
-            model_version: row.get("model_version"),
-        })),
-        None => Ok(None),
+    let mut runs = Runs::default();
+    for row in delete.build().fetch_all(pool).await? {
+        let key: PredictionKey = (
+            row.try_get("pair")?,
+            row.try_get("ts_ms")?,
+            row.try_get("model_name")?,
+        );
+        let model_version: String = row.try_get("model_version")?;
+        let predicted_ts_ms: i64 = row.try_get("predicted_ts_ms")?;
+        let target = (
+            key.0.as_str(),
+            key.2.as_str(),
+            model_version.as_str(),
+            predicted_ts_ms,
+        );
+        if let Some(superseder) = targets.get(&target) {
+            runs.superseding.insert(superseder.clone());
+        }
+        if keys.contains(&key) {
+            runs.superseded.insert(key);
+        }
     }
+    runs.superseding
+        .retain(|key| !runs.superseded.contains(key));
+    Ok(runs)
 }
 
 /// Get the latest predictions for all trading pairs.
-pub async fn get_all_latest_predictions(pool: &PgPool) -> Result<Vec<Prediction>, ApiError> {
+///
+/// When `model_name` is given, only predictions from that model are
+/// considered; otherwise only the production version's, for pairs that
+/// have one.
+/// When `pair_pattern` is given, only pairs matching that `LIKE` pattern are.
+/// When `limit` is given, at most that many pairs are returned.
+pub async fn get_all_latest_predictions(
+    pool: &PgPool,
+    model_name: Option<&str>,
+    pair_pattern: Option<&str>,
+    max_interval_pct: Option<f64>,
+    limit: Option<i64>,
+) -> Result<Vec<Prediction>, ApiError> {
+    let mut select = FilteredSelect::new(
+        "SELECT DISTINCT ON (pair) \
+         pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version, \
+         lower_bound, upper_bound, quantile \
+         FROM predictions",
+    )
+    .filter_opt("pair", "LIKE", pair_pattern);
+    select = match model_name {
+        Some(model_name) => select.filter("model_name", "=", model_name),
+        None => select.filter_with(|q| {
+            q.push(SERVED_VERSION);
+        }),
+    };
+    select = within_interval(select, max_interval_pct).then("ORDER BY pair, ts_ms DESC");
+    if let Some(limit) = limit {
+        select = select.limit(limit);
+    }
+
+    let rows = select.into_builder().build().fetch_all(pool).await?;
+
+    Ok(rows
+        .iter()
+        .map(prediction_from_row)
+        .filter(|p| p.as_ref().map_or(true, servable))
+        .collect::<Result<_, _>>()?)
+}
+
+/// Get a pair's `n` most recent predictions, oldest first, of those within
+/// `max_interval_pct` when given.
+pub async fn get_recent_predictions(
+    pool: &PgPool,
+    pair: &str,
+    max_interval_pct: Option<f64>,
+    n: i64,
+) -> Result<Vec<Prediction>, ApiError> {
+    let select = FilteredSelect::new(
+        "SELECT pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version, \
+         lower_bound, upper_bound, quantile \
+         FROM predictions",
+    )
+    .filter("pair", "=", pair);
+    let rows = within_interval(select, max_interval_pct)
+        .then("ORDER BY ts_ms DESC, model_name")
+        .limit(n)
+        .into_builder()
+        .build()
+        .fetch_all(pool)
+        .await?;
+
+    let mut predictions = rows
+        .iter()
+        .map(prediction_from_row)
+        .filter(|p| p.as_ref().map_or(true, servable))
+        .collect::<Result<Vec<_>, _>>()?;
+    predictions.reverse();
+    Ok(predictions)
+}
+
+/// Get the latest prediction for each of several trading pairs, from the
+/// production version for pairs that have one.
+///
+/// Pairs without predictions are simply absent from the result.
+pub async fn get_latest_for_pairs(
+    pool: &PgPool,
+    pairs: &[String],
+) -> Result<Vec<Prediction>, ApiError> {
+    let rows = FilteredSelect::new(
+        "SELECT DISTINCT ON (pair) \
+         pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version, \
+         lower_bound, upper_bound, quantile \
+         FROM predictions",
+    )
+    .filter_with(|q| {
+        q.push("pair = ANY(").push_bind(pairs).push(")");
+    })
+    .filter_with(|q| {
+        q.push(SERVED_VERSION);
+    })
+    .then("ORDER BY pair, ts_ms DESC")
+    .into_builder()
+    .build()
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(prediction_from_row)
+        .filter(|p| p.as_ref().map_or(true, servable))
+        .collect::<Result<_, _>>()?)
+}
+
+/// Get the latest prediction of each candidate's version for its pair.
+///
+/// `model`, `max_ts_ms` and `max_interval_pct` filter them as in
+/// `get_latest_prediction`, except that the model name and version are the
+/// candidates'. Candidates without a matching prediction are absent; each
+/// pair may have one candidate.
+pub async fn get_latest_for_candidates(
+    pool: &PgPool,
+    candidates: &[&RegisteredModel],
+    model: ModelFilter<'_>,
+    max_ts_ms: Option<i64>,
+    max_interval_pct: Option<f64>,
+) -> Result<Vec<Prediction>, ApiError> {
+    if candidates.is_empty() {
+        return Ok(Vec::new());
+    }
+    let select = FilteredSelect::new(
+        "SELECT DISTINCT ON (pair) \
+         pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version, \
+         lower_bound, upper_bound, quantile \
+         FROM predictions",
+    )
+    .filter_with(|q| {
+        q.push("(");
+        for (i, candidate) in candidates.iter().enumerate() {
+            if i > 0 {
+                q.push(" OR ");
+            }
+            q.push("(pair = ")
+                .push_bind(&candidate.pair)
+                .push(" AND model_name = ")
+                .push_bind(&candidate.model_name)
+                .push(" AND model_version = ")
+                .push_bind(&candidate.model_version)
+                .push(")");
+        }
+        q.push(")");
+    });
+
+    let select = ModelFilter {
+        name: None,
+        version: None,
+        ..model
+    }
+    .apply(select);
+
+    let rows = within_interval(select, max_interval_pct)
+        .filter_opt("ts_ms", "<=", max_ts_ms)
+        .then("ORDER BY pair, ts_ms DESC")
+        .into_builder()
+        .build()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(prediction_from_row)
+        .filter(|p| p.as_ref().map_or(true, servable))
+        .collect::<Result<_, _>>()?)
+}
+
+/// Get each listed model's latest prediction for a trading pair.
+///
+/// When `horizon_ms` is given, only predictions for that horizon are
+/// considered. Models without a matching prediction are absent.
+pub async fn get_latest_for_models(
+    pool: &PgPool,
+    pair: &str,
+    models: &[String],
+    horizon_ms: Option<i64>,
+) -> Result<Vec<Prediction>, ApiError> {
+    let select = FilteredSelect::new(
+        "SELECT DISTINCT ON (model_name) \
+         pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version, \
+         lower_bound, upper_bound, quantile \
+         FROM predictions",
+    )
+    .filter("pair", "=", pair)
+    .filter_with(|q| {
+        q.push("model_name = ANY(").push_bind(models).push(")");
+    });
+
+    let rows = ModelFilter {
+        horizon_ms,
+        ..ModelFilter::default()
+    }
+    .apply(select)
+    .then("ORDER BY model_name, ts_ms DESC")
+    .into_builder()
+    .build()
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(prediction_from_row)
+        .filter(|p| p.as_ref().map_or(true, servable))
+        .collect::<Result<_, _>>()?)
+}
+
+/// Get the latest candle close for each of several trading pairs.
+///
+/// Pairs without candles are simply absent from the result.
+pub async fn get_current_prices(
+    pool: &PgPool,
+    pairs: &[String],
+) -> Result<HashMap<String, f64>, ApiError> {
     let rows = sqlx::query(
         r#"
-        SELECT DISTINCT ON (pair)
-            pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version
-        FROM predictions
-        ORDER BY pair, ts_ms DESC
+        SELECT DISTINCT ON (pair) pair, close
+        FROM candles
+        WHERE pair = ANY($1)
+        ORDER BY pair, window_start_ms DESC
         "#,
     )
+    .bind(pairs)
     .fetch_all(pool)
     .await?;
 
-    let predictions = rows
-        .into_iter()
-        .map(|row| Prediction {
-            pair: row.get("pair"),
-            predicted_price: row.get("predicted_price"),
-            ts_ms: row.get("ts_ms"),
-            predicted_ts_ms: row.get("predicted_ts_ms"),
-            model_name: row.get("model_name"),
-            model_version: row.get("model_version"),
+    Ok(rows
+        .iter()
+        .map(|row| Ok((row.try_get("pair")?, row.try_get("close")?)))
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Run the prediction read queries once to surface schema or permission
+/// problems before serving traffic.
+///
+/// Returns how many pairs the sample query saw.
+pub async fn self_test(pool: &PgPool) -> Result<usize, ApiError> {
+    let sample = get_all_latest_predictions(pool, None, None, None, Some(1)).await?;
+    // A pair that never exists still exercises the single-pair query path
+    get_latest_prediction(pool, "SELFTEST", ModelFilter::default(), None, None).await?;
+    Ok(sample.len())
+}
+
+/// Get one page of predictions made within `ts_ms_range`, ordered by
+/// `(ts_ms, pair, model_name)` and starting after `after`.
+///
+/// When `pair` is given, only that pair's predictions are returned; likewise
+/// only predictions matching `model`, and within `max_interval_pct`.
+pub async fn get_history_page(
+    pool: &PgPool,
+    pair: Option<&str>,
+    model: ModelFilter<'_>,
+    max_interval_pct: Option<f64>,
+    ts_ms_range: RangeInclusive<i64>,
+    after: Option<Cursor>,
+    limit: usize,
+) -> Result<Page<Prediction>, ApiError> {
+    let select = FilteredSelect::new(
+        "SELECT pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version, \
+         lower_bound, upper_bound, quantile \
+         FROM predictions",
+    )
+    .filter_opt("pair", "=", pair);
+
+    let select = within_interval(model.apply(select), max_interval_pct)
+        .filter("ts_ms", ">=", *ts_ms_range.start())
+        .filter("ts_ms", "<=", *ts_ms_range.end());
+
+    let rows = after_cursor(select, after)
+        .then("ORDER BY ts_ms, pair, model_name")
+        .limit(limit as i64 + 1)
+        .into_builder()
+        .build()
+        .fetch_all(pool)
+        .await?;
+
+    let rows = rows
+        .iter()
+        .map(prediction_from_row)
+        .collect::<Result<_, _>>()?;
+
+    // Place the cursor before dropping rows, so a dropped row can't end paging early
+    let mut page = Page::from_rows(rows, limit);
+    page.items.retain(servable);
+    Ok(page)
+}
+
+/// An outbox event whose prediction was stored.
+#[derive(Debug)]
+pub struct Event {
+    pub id: String,
+    /// When the event was written, by the writer's clock (ms)
+    pub created_ts_ms: i64,
+    /// `None` when the prediction may not be served, so the relay still
+    /// counts the event as read
+    pub prediction: Option<Prediction>,
+}
+
+/// Prediction events joined with their stored predictions. Events of
+/// predictions never stored, or replaced since, match none.
+const STORED_EVENTS: &str = "SELECT e.id, e.created_ts_ms, \
+     e.pair, e.predicted_price, e.ts_ms, e.predicted_ts_ms, e.model_name, e.model_version, \
+     e.lower_bound, e.upper_bound, e.quantile \
+     FROM prediction_events e JOIN predictions p \
+     ON p.pair = e.pair AND p.ts_ms = e.ts_ms AND p.model_name = e.model_name \
+     AND p.model_version IS NOT DISTINCT FROM e.model_version \
+     AND p.predicted_price IS NOT DISTINCT FROM e.predicted_price \
+     AND p.predicted_ts_ms IS NOT DISTINCT FROM e.predicted_ts_ms \
+     AND p.lower_bound IS NOT DISTINCT FROM e.lower_bound \
+     AND p.upper_bound IS NOT DISTINCT FROM e.upper_bound \
+     AND p.quantile IS NOT DISTINCT FROM e.quantile";
+
+/// Up to `limit` events after `(created_ts_ms, id)` in that order, whose
+/// predictions are stored, oldest first.
+pub async fn get_events_after(
+    pool: &PgPool,
+    created_ts_ms: i64,
+    id: &str,
+    limit: usize,
+) -> Result<Vec<Event>, ApiError> {
+    let rows = sqlx::query(&format!(
+        "{STORED_EVENTS} \
+         WHERE e.created_ts_ms > $1 OR (e.created_ts_ms = $1 AND e.id > $2) \
+         ORDER BY e.created_ts_ms, e.id LIMIT $3"
+    ))
+    .bind(created_ts_ms)
+    .bind(id)
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await?;
+    events_from_rows(&rows)
+}
+
+/// Ids and write times of the events written after `after_ms` whose
+/// predictions are stored.
+pub async fn get_event_ids_since(
+    pool: &PgPool,
+    after_ms: i64,
+) -> Result<Vec<(String, i64)>, ApiError> {
+    let rows = sqlx::query(&format!(
+        "SELECT id, created_ts_ms FROM ({STORED_EVENTS} WHERE e.created_ts_ms > $1) stored"
+    ))
+    .bind(after_ms)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| Ok((row.try_get("id")?, row.try_get("created_ts_ms")?)))
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// The events with `ids` whose predictions are stored, oldest first.
+pub async fn get_events(pool: &PgPool, ids: &[String]) -> Result<Vec<Event>, ApiError> {
+    let mut events = Vec::new();
+    for chunk in ids.chunks(INSERT_CHUNK) {
+        let rows = sqlx::query(&format!(
+            "{STORED_EVENTS} WHERE e.id = ANY($1) ORDER BY e.created_ts_ms, e.id"
+        ))
+        .bind(chunk)
+        .fetch_all(pool)
+        .await?;
+        events.extend(events_from_rows(&rows)?);
+    }
+    Ok(events)
+}
+
+fn events_from_rows(rows: &[PgRow]) -> Result<Vec<Event>, ApiError> {
+    rows.iter()
+        .map(|row| {
+            let prediction = prediction_from_row(row)?;
+            Ok(Event {
+                id: row.try_get("id")?,
+                created_ts_ms: row.try_get("created_ts_ms")?,
+                prediction: servable(&prediction).then_some(prediction),
+            })
         })
-        .collect();
+        .collect()
+}
 
-    Ok(predictions)
+/// The offset `consumer` last saved in the outbox, if it ever did.
+pub async fn get_event_offset(pool: &PgPool, consumer: &str) -> Result<Option<i64>, ApiError> {
+    Ok(
+        sqlx::query_scalar(
+            "SELECT settled_ts_ms FROM prediction_event_offsets WHERE consumer = $1",
+        )
+        .bind(consumer)
+        .fetch_optional(pool)
+        .await?,
+    )
+}
+
+/// Save `consumer`'s offset in the outbox: every event written at or before
+/// `settled_ts_ms` was handled.
+pub async fn save_event_offset(
+    pool: &PgPool,
+    consumer: &str,
+    settled_ts_ms: i64,
+) -> Result<(), ApiError> {
+    let now_ms = timestamp::now_ms();
+    let update = || {
+        sqlx::query(
+            "UPDATE prediction_event_offsets SET settled_ts_ms = $2, updated_ts_ms = $3 \
+             WHERE consumer = $1",
+        )
+        .bind(consumer)
+        .bind(settled_ts_ms)
+        .bind(now_ms)
+        .execute(pool)
+    };
+    if update().await?.rows_affected() > 0 {
+        return Ok(());
+    }
+
+    let insert = sqlx::query(
+        "INSERT INTO prediction_event_offsets (consumer, settled_ts_ms, updated_ts_ms) \
+         VALUES ($1, $2, $3)",
+    )
+    .bind(consumer)
+    .bind(settled_ts_ms)
+    .bind(now_ms)
+    .execute(pool)
+    .await;
+    match insert {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            update().await?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Delete up to `limit` rows of `expiring` written before `before_ms`,
+/// waiting at most `lock_timeout` for locks. Returns how many were deleted.
+///
+/// The keys are selected first and deleted by key, as there is no
+/// `DELETE … LIMIT`. RisingWave takes no row locks and has no
+/// `lock_timeout`, so failing to set it is not an error.
+pub async fn delete_expired(
+    pool: &PgPool,
+    expiring: Expiring,
+    before_ms: i64,
+    limit: i64,
+    lock_timeout: Duration,
+) -> Result<u64, ApiError> {
+    let Expiring {
+        table,
+        key,
+        written,
+    } = expiring;
+    let mut conn = pool.acquire().await?;
+    let limited = sqlx::query(&format!("SET lock_timeout = {}", lock_timeout.as_millis()))
+        .execute(&mut *conn)
+        .await
+        .is_ok();
+
+    let deleted = async {
+        let keys: Vec<String> = sqlx::query_scalar(&format!(
+            "SELECT {key} FROM {table} WHERE {written} < $1 LIMIT $2"
+        ))
+        .bind(before_ms)
+        .bind(limit)
+        .fetch_all(&mut *conn)
+        .await?;
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        sqlx::query(&format!("DELETE FROM {table} WHERE {key} = ANY($1)"))
+            .bind(&keys)
+            .execute(&mut *conn)
+            .await
+            .map(|done| done.rows_affected())
+    }
+    .await;
+
+    if limited
+        && sqlx::query("RESET lock_timeout")
+            .execute(&mut *conn)
+            .await
+            .is_err()
+    {
+        // Close it rather than pool a connection with the timeout set
+        conn.detach();
+    }
+    Ok(deleted?)
+}
+
+/// Restrict a select to predictions whose interval, `upper_bound -
+/// lower_bound`, is at most `max_pct` percent of `predicted_price`.
+///
+/// Predictions without an interval can't show they are that tight, so they
+/// are left out too.
+fn within_interval(select: FilteredSelect<'_>, max_pct: Option<f64>) -> FilteredSelect<'_> {
+    match max_pct {
+        // Multiplied out, so a zero price can't divide by zero
+        Some(max_pct) => select.filter_with(|q| {
+            q.push("upper_bound - lower_bound <= predicted_price * ")
+                .push_bind(max_pct / 100.0);
+        }),
+        None => select,
+    }
+}
+
+/// Restrict a select to rows strictly after `after` in
+/// `(ts_ms, pair, model_name)` order.
+fn after_cursor(select: FilteredSelect<'_>, after: Option<Cursor>) -> FilteredSelect<'_> {
+    match after {
+        Some(after) => select.filter_with(|q| {
+            q.push("(ts_ms, pair, model_name) > (")
+                .push_bind(after.ts_ms)
+                .push(", ")
+                .push_bind(after.pair)
+                .push(", ")
+                .push_bind(after.model_name)
+                .push(")");
+        }),
+        None => select,
+    }
+}
+
+/// Stream the predictions matching an export, oldest first.
+///
+/// Rows are read on a spawned task and handed over through a bounded
+/// channel, so a slow client holds the query back instead of piling rows up
+/// in memory, and a client that disconnects ends it. A database error ends
+/// the stream with that error. The task holds `permit` until the query
+/// ends, along with its connection.
+pub fn stream_history(
+    pool: PgPool,
+    query: ExportQuery,
+    permit: OwnedSemaphorePermit,
+) -> impl Stream<Item = Result<Prediction, ApiError>> {
+    let (tx, mut rx) = mpsc::channel(STREAM_BUFFER_ROWS);
+
+    // Rows are mapped on the spawned task, so carry the request's format over
+    let format = timestamp::current();
+    tokio::spawn(timestamp::with_format(format, async move {
+        let result: Result<(), ApiError> = async {
+            let select = FilteredSelect::new(
+                "SELECT pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version, \
+                 lower_bound, upper_bound, quantile \
+                 FROM predictions",
+            )
+            .filter_opt("pair", "=", query.pair.as_deref());
+
+            let select = query
+                .model()?
+                .apply(select)
+                .filter("ts_ms", ">=", query.from_ts_ms)
+                .filter("ts_ms", "<=", query.to_ts_ms);
+
+            let mut builder = after_cursor(select, query.after()?)
+                .then("ORDER BY ts_ms, pair, model_name")
+                .into_builder();
+            let mut rows = builder.build().fetch(&pool);
+
+            while let Some(row) = rows.try_next().await? {
+                let prediction = prediction_from_row(&row)?;
+                if servable(&prediction) && tx.send(Ok(prediction)).await.is_err() {
+                    tracing::debug!("Export client disconnected");
+                    break;
+                }
+            }
+            Ok(())
+        }
+        .await;
+        drop(permit);
+
+        if let Err(e) = result {
+            tracing::error!(error = %e, "Export failed");
+            let _ = tx.send(Err(e)).await;
+        }
+    }));
+
+    // Unlike `unfold`, keeps returning `None` if polled after the end, which
+    // the compression layer does
+    stream::poll_fn(move |cx| rx.poll_recv(cx))
+}
+
+/// Aggregate a pair's predictions in a time range into buckets of
+/// `bucket_ms`, oldest first. Non-finite prices are excluded.
+pub async fn get_downsampled(
+    pool: &PgPool,
+    pair: &str,
+    model: ModelFilter<'_>,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+    bucket_ms: i64,
+) -> Result<Vec<PriceBucket>, ApiError> {
+    let mut select = QueryBuilder::new("SELECT (ts_ms / ");
+    select
+        .push_bind(bucket_ms)
+        .push(") * ")
+        .push_bind(bucket_ms)
+        .push(
+            " AS bucket_start_ms, \
+             COUNT(*) AS count, \
+             AVG(predicted_price) AS avg_price, \
+             (ARRAY_AGG(predicted_price ORDER BY ts_ms))[1] AS first_price, \
+             (ARRAY_AGG(predicted_price ORDER BY ts_ms DESC))[1] AS last_price \
+             FROM predictions",
+        );
+
+    let select = FilteredSelect::from_builder(select).filter("pair", "=", pair);
+    let rows = model
+        .apply(select)
+        .filter("ts_ms", ">=", from_ts_ms)
+        .filter("ts_ms", "<=", to_ts_ms)
+        .filter_with(|q| {
+            q.push(FINITE_PRICE);
+        })
+        .then("GROUP BY 1 ORDER BY 1")
+        .into_builder()
+        .build()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            Ok(PriceBucket {
+                bucket_start_ms: row.try_get("bucket_start_ms")?,
+                count: row.try_get("count")?,
+                avg_price: row.try_get("avg_price")?,
+                first_price: row.try_get("first_price")?,
+                last_price: row.try_get("last_price")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Summary statistics of a pair's predicted prices over the last
+/// `window_ms`. Non-finite prices are excluded.
+pub async fn get_price_stats(
+    pool: &PgPool,
+    pair: &str,
+    model: ModelFilter<'_>,
+    window_ms: i64,
+) -> Result<PriceStats, ApiError> {
+    let from_ts_ms = timestamp::now_ms().saturating_sub(window_ms);
+
+    let select = FilteredSelect::new(
+        "SELECT COUNT(*) AS count, \
+         MIN(predicted_price) AS min, \
+         MAX(predicted_price) AS max, \
+         AVG(predicted_price) AS mean, \
+         PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY predicted_price) AS median, \
+         STDDEV_SAMP(predicted_price) AS stddev \
+         FROM predictions",
+    )
+    .filter("pair", "=", pair);
+
+    let row = model
+        .apply(select)
+        .filter("ts_ms", ">=", from_ts_ms)
+        .filter_with(|q| {
+            q.push(FINITE_PRICE);
+        })
+        .into_builder()
+        .build()
+        .fetch_one(pool)
+        .await?;
+
+    Ok(PriceStats {
+        pair: pair.to_string(),
+        window_ms,
+        count: row.try_get("count")?,
+        min: row.try_get("min")?,
+        max: row.try_get("max")?,
+        mean: row.try_get("mean")?,
+        median: row.try_get("median")?,
+        stddev: row.try_get("stddev")?,
+    })
+}
+
+/// Get the minutes within a time range a pair has a price for, oldest
+/// first.
+pub async fn get_price_times(
+    pool: &PgPool,
+    pair: &str,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+) -> Result<Vec<i64>, ApiError> {
+    Ok(sqlx::query_scalar(
+        r#"
+        SELECT ts_ms
+        FROM prices
+        WHERE pair = $1
+          AND ts_ms BETWEEN $2 AND $3
+        ORDER BY ts_ms
+        "#,
+    )
+    .bind(pair)
+    .bind(from_ts_ms)
+    .bind(to_ts_ms)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Get a pair's realized prices within a time range, oldest first.
+///
+/// At most `limit` rows are returned; callers pass their row cap plus one to
+/// detect overflow.
+pub async fn get_prices(
+    pool: &PgPool,
+    pair: &str,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+    limit: i64,
+) -> Result<Vec<PricePoint>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT ts_ms, price
+        FROM prices
+        WHERE pair = $1
+          AND ts_ms BETWEEN $2 AND $3
+        ORDER BY ts_ms
+        LIMIT $4
+        "#,
+    )
+    .bind(pair)
+    .bind(from_ts_ms)
+    .bind(to_ts_ms)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            Ok(PricePoint {
+                ts_ms: row.try_get("ts_ms")?,
+                price: row.try_get("price")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Store an order book snapshot.
+pub async fn insert_orderbook(pool: &PgPool, book: &OrderBook) -> Result<(), ApiError> {
+    let (bid_prices, bid_quantities): (Vec<f64>, Vec<f64>) =
+        book.bids.iter().map(|[p, q]| (*p, *q)).unzip();
+    let (ask_prices, ask_quantities): (Vec<f64>, Vec<f64>) =
+        book.asks.iter().map(|[p, q]| (*p, *q)).unzip();
+    sqlx::query(
+        r#"
+        INSERT INTO orderbook_snapshots
+            (pair, ts_ms, last_update_id, bid_prices, bid_quantities, ask_prices, ask_quantities)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(&book.pair)
+    .bind(book.ts_ms)
+    .bind(book.last_update_id)
+    .bind(bid_prices)
+    .bind(bid_quantities)
+    .bind(ask_prices)
+    .bind(ask_quantities)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Get a pair's latest order book snapshot, or the latest taken at or
+/// before `max_ts_ms`.
+pub async fn get_orderbook(
+    pool: &PgPool,
+    pair: &str,
+    max_ts_ms: Option<i64>,
+) -> Result<Option<OrderBook>, ApiError> {
+    let row = FilteredSelect::new(
+        "SELECT pair, ts_ms, last_update_id, bid_prices, bid_quantities, ask_prices, \
+         ask_quantities \
+         FROM orderbook_snapshots",
+    )
+    .filter("pair", "=", pair)
+    .filter_opt("ts_ms", "<=", max_ts_ms)
+    .then("ORDER BY ts_ms DESC")
+    .limit(1)
+    .into_builder()
+    .build()
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let levels = |prices: Vec<f64>, quantities: Vec<f64>| {
+        prices
+            .into_iter()
+            .zip(quantities)
+            .map(|(p, q)| [p, q])
+            .collect()
+    };
+    Ok(Some(OrderBook {
+        pair: row.try_get("pair")?,
+        ts_ms: row.try_get("ts_ms")?,
+        last_update_id: row.try_get("last_update_id")?,
+        bids: levels(row.try_get("bid_prices")?, row.try_get("bid_quantities")?),
+        asks: levels(row.try_get("ask_prices")?, row.try_get("ask_quantities")?),
+    }))
+}
+
+/// Store a pair's funding rates.
+pub async fn insert_funding_rates(
+    pool: &PgPool,
+    pair: &str,
+    rates: &[FundingRate],
+) -> Result<(), ApiError> {
+    if rates.is_empty() {
+        return Ok(());
+    }
+    let mut insert = QueryBuilder::<Postgres>::new(
+        "INSERT INTO funding_rates (pair, ts_ms, funding_rate, mark_price) ",
+    );
+    insert.push_values(rates, |mut row, rate| {
+        row.push_bind(pair)
+            .push_bind(rate.ts_ms)
+            .push_bind(rate.funding_rate)
+            .push_bind(rate.mark_price);
+    });
+    insert.build().execute(pool).await?;
+    Ok(())
+}
+
+/// Funding time of a pair's latest stored funding rate, if any.
+pub async fn get_latest_funding_ts(pool: &PgPool, pair: &str) -> Result<Option<i64>, ApiError> {
+    let ts_ms: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(ts_ms) FROM funding_rates WHERE pair = $1")
+            .bind(pair)
+            .fetch_one(pool)
+            .await?;
+    Ok(ts_ms)
+}
+
+/// Get a pair's funding rates within `[from_ts_ms, to_ts_ms]`, oldest
+/// first.
+pub async fn get_funding_rates(
+    pool: &PgPool,
+    pair: &str,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+    limit: i64,
+) -> Result<Vec<FundingRate>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT ts_ms, funding_rate, mark_price
+        FROM funding_rates
+        WHERE pair = $1
+          AND ts_ms BETWEEN $2 AND $3
+        ORDER BY ts_ms
+        LIMIT $4
+        "#,
+    )
+    .bind(pair)
+    .bind(from_ts_ms)
+    .bind(to_ts_ms)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            Ok(FundingRate {
+                ts_ms: row.try_get("ts_ms")?,
+                funding_rate: row.try_get("funding_rate")?,
+                mark_price: row.try_get("mark_price")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Store a reading of a pair's open interest.
+pub async fn insert_open_interest(
+    pool: &PgPool,
+    pair: &str,
+    reading: &OpenInterest,
+) -> Result<(), ApiError> {
+    sqlx::query("INSERT INTO open_interest (pair, ts_ms, open_interest) VALUES ($1, $2, $3)")
+        .bind(pair)
+        .bind(reading.ts_ms)
+        .bind(reading.open_interest)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Get a pair's open interest within `[from_ts_ms, to_ts_ms]`, oldest
+/// first.
+pub async fn get_open_interest(
+    pool: &PgPool,
+    pair: &str,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+    limit: i64,
+) -> Result<Vec<OpenInterest>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT ts_ms, open_interest
+        FROM open_interest
+        WHERE pair = $1
+          AND ts_ms BETWEEN $2 AND $3
+        ORDER BY ts_ms
+        LIMIT $4
+        "#,
+    )
+    .bind(pair)
+    .bind(from_ts_ms)
+    .bind(to_ts_ms)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            Ok(OpenInterest {
+                ts_ms: row.try_get("ts_ms")?,
+                open_interest: row.try_get("open_interest")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Build a pair's candles of `interval_ms` from the 1m candles starting in
+/// `[from_ms, until_ms)`, oldest first.
+pub async fn get_candles(
+    pool: &PgPool,
+    pair: &str,
+    from_ms: i64,
+    until_ms: i64,
+    interval_ms: i64,
+) -> Result<Vec<Candle>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT (window_start_ms / $4) * $4 AS open_ts_ms,
+               (ARRAY_AGG(open ORDER BY window_start_ms))[1] AS open,
+               MAX(high) AS high,
+               MIN(low) AS low,
+               (ARRAY_AGG(close ORDER BY window_start_ms DESC))[1] AS close,
+               SUM(volume) AS volume,
+               COUNT(*) AS count
+        FROM candles
+        WHERE pair = $1
+          AND candle_seconds = $5
+          AND window_start_ms >= $2
+          AND window_start_ms < $3
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+    )
+    .bind(pair)
+    .bind(from_ms)
+    .bind(until_ms)
+    .bind(interval_ms)
+    .bind((BASE_CANDLE_MS / 1000) as i32)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let open_ts_ms: i64 = row.try_get("open_ts_ms")?;
+            Ok(Candle {
+                open_ts_ms,
+                close_ts_ms: open_ts_ms + interval_ms,
+                open: row.try_get("open")?,
+                high: row.try_get("high")?,
+                low: row.try_get("low")?,
+                close: row.try_get("close")?,
+                volume: row.try_get("volume")?,
+                count: row.try_get("count")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Complete `select`, a `SELECT` list over `p` and `a`, into a query over
+/// evaluated predictions: finite predictions made in a time range (`p`),
+/// each joined with the actual price at its target time (`a`). When `pair`
+/// is given, only that pair's predictions are evaluated.
+///
+/// The actual price is the last `prices` close at or before the target
+/// time; a fair value (no `predicted_ts_ms`) is judged at `ts_ms`.
+/// Predictions whose target has no price yet are left out.
+fn evaluated<'args>(
+    mut select: QueryBuilder<'args, Postgres>,
+    pair: Option<&'args str>,
+    model: ModelFilter<'args>,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+) -> QueryBuilder<'args, Postgres> {
+    select.push(
+        " FROM (\
+         SELECT pair, model_name, ts_ms, predicted_price, lower_bound, upper_bound, \
+         COALESCE(predicted_ts_ms, ts_ms) AS target_ts_ms \
+         FROM predictions",
+    );
+    let select = FilteredSelect::from_builder(select).filter_opt("pair", "=", pair);
+
+    let mut builder = model
+        .apply(select)
+        .filter("ts_ms", ">=", from_ts_ms)
+        .filter("ts_ms", "<=", to_ts_ms)
+        .filter_with(|q| {
+            q.push(FINITE_PRICE);
+        })
+        .into_builder();
+    builder.push(format!(
+        ") p JOIN prices a ON a.pair = p.pair \
+         AND a.ts_ms = p.target_ts_ms / {PRICE_STEP_MS} * {PRICE_STEP_MS}"
+    ));
+    builder
+}
+
+/// Error metrics of a pair's predictions made in the last `window_ms`,
+/// against actual prices.
+pub async fn get_accuracy(
+    pool: &PgPool,
+    pair: &str,
+    model: ModelFilter<'_>,
+    window_ms: i64,
+) -> Result<AccuracyMetrics, ApiError> {
+    let now_ms = timestamp::now_ms();
+    let select = QueryBuilder::new(
+        "SELECT COUNT(*) AS count, \
+         AVG(ABS(p.predicted_price - a.price)) AS mae, \
+         SQRT(AVG((p.predicted_price - a.price) ^ 2)) AS rmse, \
+         100 * AVG(ABS((p.predicted_price - a.price) / NULLIF(a.price, 0))) AS mape",
+    );
+
+    let row = evaluated(
+        select,
+        Some(pair),
+        model,
+        now_ms.saturating_sub(window_ms),
+        now_ms,
+    )
+    .build()
+    .fetch_one(pool)
+    .await?;
+
+    Ok(AccuracyMetrics {
+        pair: pair.to_string(),
+        window_ms,
+        count: row.try_get("count")?,
+        mae: row.try_get("mae")?,
+        rmse: row.try_get("rmse")?,
+        mape: row.try_get("mape")?,
+    })
+}
+
+/// Error totals of a pair's evaluated predictions in a time range, per
+/// bucket of `bucket_ms` by prediction time, oldest first. Empty buckets are
+/// omitted.
+pub async fn get_error_sums(
+    pool: &PgPool,
+    pair: &str,
+    model: ModelFilter<'_>,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+    bucket_ms: i64,
+) -> Result<Vec<(i64, ErrorSums)>, ApiError> {
+    let mut select = QueryBuilder::new("SELECT (p.ts_ms / ");
+    select
+        .push_bind(bucket_ms)
+        .push(") * ")
+        .push_bind(bucket_ms)
+        .push(
+            " AS bucket_start_ms, \
+             COUNT(*) AS count, \
+             SUM(ABS(p.predicted_price - a.price)) AS abs_error, \
+             SUM((p.predicted_price - a.price) ^ 2) AS squared_error, \
+             COUNT(NULLIF(a.price, 0)) AS pct_count, \
+             SUM(ABS((p.predicted_price - a.price) / NULLIF(a.price, 0))) AS abs_pct_error",
+        );
+
+    let mut query = evaluated(select, Some(pair), model, from_ts_ms, to_ts_ms);
+    query.push(" GROUP BY 1 ORDER BY 1");
+    let rows = query.build().fetch_all(pool).await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let abs_pct_error: Option<f64> = row.try_get("abs_pct_error")?;
+            Ok((
+                row.try_get("bucket_start_ms")?,
+                ErrorSums {
+                    count: row.try_get("count")?,
+                    abs_error: row.try_get("abs_error")?,
+                    squared_error: row.try_get("squared_error")?,
+                    pct_count: row.try_get("pct_count")?,
+                    abs_pct_error: abs_pct_error.unwrap_or(0.0),
+                },
+            ))
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Get a pair's evaluated predictions in a time range, ordered by `ts_ms`
+/// then model name.
+///
+/// At most `limit` rows are returned; callers pass their row cap plus one to
+/// detect overflow.
+pub async fn get_evaluated_predictions(
+    pool: &PgPool,
+    pair: &str,
+    model: ModelFilter<'_>,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+    limit: i64,
+) -> Result<Vec<EvaluatedPrediction>, ApiError> {
+    let select = QueryBuilder::new(
+        "SELECT p.model_name, p.ts_ms, p.target_ts_ms, p.predicted_price, \
+         a.price AS actual_price",
+    );
+
+    let mut query = evaluated(select, Some(pair), model, from_ts_ms, to_ts_ms);
+    query
+        .push(" ORDER BY p.ts_ms, p.model_name LIMIT ")
+        .push_bind(limit);
+    let rows = query.build().fetch_all(pool).await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let predicted_price: f64 = row.try_get("predicted_price")?;
+            let actual_price: f64 = row.try_get("actual_price")?;
+            Ok(EvaluatedPrediction {
+                model_name: row.try_get("model_name")?,
+                ts_ms: row.try_get("ts_ms")?,
+                predicted_ts_ms: row.try_get("target_ts_ms")?,
+                predicted_price,
+                actual_price,
+                error: predicted_price - actual_price,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Direction hit rates of predictions made in the last `window_ms`, per pair
+/// and model, ordered by pair then model.
+///
+/// A prediction's direction is taken against the price when it was made,
+/// as is the realized direction of the actual price; moves within
+/// `flat_threshold_pct` of that price count as flat. Predictions without a
+/// price at either end are left out.
+pub async fn get_hit_rates(
+    pool: &PgPool,
+    pair: Option<&str>,
+    model: ModelFilter<'_>,
+    window_ms: i64,
+    flat_threshold_pct: f64,
+) -> Result<Vec<HitRate>, ApiError> {
+    let now_ms = timestamp::now_ms();
+    let mut select = QueryBuilder::new(
+        "SELECT p.pair, p.model_name, COUNT(*) AS count, \
+         COUNT(*) FILTER (WHERE \
+         CASE WHEN ABS(p.predicted_price - b.price) <= ABS(b.price) * ",
+    );
+    select
+        .push_bind(flat_threshold_pct / 100.0)
+        .push(
+            " THEN 0 ELSE SIGN(p.predicted_price - b.price) END = \
+             CASE WHEN ABS(a.price - b.price) <= ABS(b.price) * ",
+        )
+        .push_bind(flat_threshold_pct / 100.0)
+        .push(" THEN 0 ELSE SIGN(a.price - b.price) END) AS hits");
+
+    let mut query = evaluated(
+        select,
+        pair,
+        model,
+        now_ms.saturating_sub(window_ms),
+        now_ms,
+    );
+    query.push(format!(
+        " JOIN prices b ON b.pair = p.pair \
+         AND b.ts_ms = p.ts_ms / {PRICE_STEP_MS} * {PRICE_STEP_MS} \
+         GROUP BY p.pair, p.model_name \
+         ORDER BY p.pair, p.model_name"
+    ));
+    let rows = query.build().fetch_all(pool).await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let count: i64 = row.try_get("count")?;
+            let hits: i64 = row.try_get("hits")?;
+            Ok(HitRate {
+                pair: row.try_get("pair")?,
+                model_name: row.try_get("model_name")?,
+                count,
+                hits,
+                hit_rate: hits as f64 / count as f64,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Residual range and interval coverage of a pair's predictions in a time
+/// range: `(count, min residual, max residual, coverage)`, where a residual
+/// is predicted minus actual price.
+pub async fn get_residual_summary(
+    pool: &PgPool,
+    pair: &str,
+    model: ModelFilter<'_>,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+) -> Result<(i64, Option<f64>, Option<f64>, IntervalCoverage), ApiError> {
+    let select = QueryBuilder::new(
+        "SELECT COUNT(*) AS count, \
+         MIN(p.predicted_price - a.price) AS min_residual, \
+         MAX(p.predicted_price - a.price) AS max_residual, \
+         COUNT(*) FILTER (WHERE p.lower_bound IS NOT NULL AND p.upper_bound IS NOT NULL) \
+         AS with_interval, \
+         COUNT(*) FILTER (WHERE a.price BETWEEN p.lower_bound AND p.upper_bound) AS covered, \
+         COUNT(*) FILTER (WHERE a.price < p.lower_bound AND p.upper_bound IS NOT NULL) AS below, \
+         COUNT(*) FILTER (WHERE a.price > p.upper_bound AND p.lower_bound IS NOT NULL) AS above, \
+         AVG(p.upper_bound - p.lower_bound) AS mean_width",
+    );
+
+    let row = evaluated(select, Some(pair), model, from_ts_ms, to_ts_ms)
+        .build()
+        .fetch_one(pool)
+        .await?;
+
+    let with_interval: i64 = row.try_get("with_interval")?;
+    let covered: i64 = row.try_get("covered")?;
+    Ok((
+        row.try_get("count")?,
+        row.try_get("min_residual")?,
+        row.try_get("max_residual")?,
+        IntervalCoverage {
+            with_interval,
+            covered,
+            below: row.try_get("below")?,
+            above: row.try_get("above")?,
+            coverage: (with_interval > 0).then(|| covered as f64 / with_interval as f64),
+            mean_width: row.try_get("mean_width")?,
+        },
+    ))
+}
+
+/// Count a pair's residuals in a time range per bin of `bin_width` starting
+/// at `min_residual`, as `(bin index, count)`.
+pub async fn get_residual_bins(
+    pool: &PgPool,
+    pair: &str,
+    model: ModelFilter<'_>,
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+    min_residual: f64,
+    bin_width: f64,
+) -> Result<Vec<(i64, i64)>, ApiError> {
+    let mut select = QueryBuilder::new("SELECT FLOOR((p.predicted_price - a.price - ");
+    select
+        .push_bind(min_residual)
+        .push(") / ")
+        .push_bind(bin_width)
+        .push(")::BIGINT AS bin, COUNT(*) AS count");
+
+    let mut query = evaluated(select, Some(pair), model, from_ts_ms, to_ts_ms);
+    query.push(" GROUP BY 1 ORDER BY 1");
+    let rows = query.build().fetch_all(pool).await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Ok((row.try_get("bin")?, row.try_get("count")?)))
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Summarise every pair in the `predictions` table, ordered by pair.
+pub async fn get_pair_summaries(pool: &PgPool) -> Result<Vec<PairSummary>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT pair,
+               MIN(ts_ms) AS first_ts_ms,
+               MAX(ts_ms) AS last_ts_ms,
+               COUNT(*) AS count
+        FROM predictions
+        GROUP BY pair
+        ORDER BY pair
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            Ok(PairSummary {
+                pair: row.try_get("pair")?,
+                first_ts_ms: row.try_get("first_ts_ms")?,
+                last_ts_ms: row.try_get("last_ts_ms")?,
+                count: row.try_get("count")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Every pair alias, ordered by alias.
+pub async fn get_pair_aliases(pool: &PgPool) -> Result<Vec<PairAlias>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT alias, pair, created_ts_ms
+        FROM pair_aliases
+        ORDER BY alias
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            Ok(PairAlias {
+                alias: row.try_get("alias")?,
+                pair: row.try_get("pair")?,
+                created_ts_ms: row.try_get("created_ts_ms")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Every pair alias, as a map from alias to canonical pair.
+pub async fn get_pair_alias_map(pool: &PgPool) -> Result<HashMap<String, String>, ApiError> {
+    Ok(get_pair_aliases(pool)
+        .await?
+        .into_iter()
+        .map(|alias| (alias.alias, alias.pair))
+        .collect())
+}
+
+/// Map `alias` to a canonical pair, replacing any earlier mapping.
+///
+/// Updates first and inserts when there was no mapping, as RisingWave has
+/// no `ON CONFLICT`; an insert losing a race to another one updates again.
+pub async fn upsert_pair_alias(pool: &PgPool, alias: &PairAlias) -> Result<(), ApiError> {
+    let update = || {
+        sqlx::query("UPDATE pair_aliases SET pair = $2, created_ts_ms = $3 WHERE alias = $1")
+            .bind(&alias.alias)
+            .bind(&alias.pair)
+            .bind(alias.created_ts_ms)
+            .execute(pool)
+    };
+    if update().await?.rows_affected() > 0 {
+        return Ok(());
+    }
+
+    let insert =
+        sqlx::query("INSERT INTO pair_aliases (alias, pair, created_ts_ms) VALUES ($1, $2, $3)")
+            .bind(&alias.alias)
+            .bind(&alias.pair)
+            .bind(alias.created_ts_ms)
+            .execute(pool)
+            .await;
+    match insert {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            update().await?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove a pair alias. Returns false when there was none.
+pub async fn delete_pair_alias(pool: &PgPool, alias: &str) -> Result<bool, ApiError> {
+    let result = sqlx::query("DELETE FROM pair_aliases WHERE alias = $1")
+        .bind(alias)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Summarise every model name and version in the `predictions` table,
+/// ordered by name then version.
+pub async fn get_model_summaries(pool: &PgPool) -> Result<Vec<ModelSummary>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT model_name,
+               model_version,
+               ARRAY_AGG(DISTINCT pair ORDER BY pair) AS pairs,
+               MIN(ts_ms) AS first_ts_ms,
+               MAX(ts_ms) AS last_ts_ms,
+               COUNT(*) AS count
+        FROM predictions
+        GROUP BY model_name, model_version
+        ORDER BY model_name, model_version
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            Ok(ModelSummary {
+                model_name: row.try_get("model_name")?,
+                model_version: row.try_get("model_version")?,
+                pairs: row.try_get("pairs")?,
+                first_ts_ms: row.try_get("first_ts_ms")?,
+                last_ts_ms: row.try_get("last_ts_ms")?,
+                count: row.try_get("count")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Every registered webhook, oldest first.
+pub async fn get_webhooks(pool: &PgPool) -> Result<Vec<Webhook>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, url, pairs, secret, created_ts_ms
+        FROM webhooks
+        ORDER BY created_ts_ms, id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(webhook_from_row)
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Get one webhook by id.
+pub async fn get_webhook(pool: &PgPool, id: &str) -> Result<Option<Webhook>, ApiError> {
+    let row = sqlx::query(
+        r#"
+        SELECT id, url, pairs, secret, created_ts_ms
+        FROM webhooks
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.as_ref().map(webhook_from_row).transpose()?)
+}
+
+/// Register a webhook.
+pub async fn insert_webhook(pool: &PgPool, webhook: &Webhook) -> Result<(), ApiError> {
+    sqlx::query(
+        r#"
+        INSERT INTO webhooks (id, url, pairs, secret, created_ts_ms)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(&webhook.id)
+    .bind(&webhook.url)
+    .bind(&webhook.pairs)
+    .bind(&webhook.secret)
+    .bind(webhook.created_ts_ms)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Change where a webhook delivers and what; false if there is no such
+/// webhook.
+pub async fn update_webhook(
+    pool: &PgPool,
+    id: &str,
+    url: &str,
+    pairs: Option<&[String]>,
+) -> Result<bool, ApiError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE webhooks
+        SET url = $2, pairs = $3
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(url)
+    .bind(pairs)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Remove a webhook and its dead letters; false if there is no such
+/// webhook.
+pub async fn delete_webhook(pool: &PgPool, id: &str) -> Result<bool, ApiError> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM webhook_dead_letters WHERE webhook_id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Record a delivery that failed every attempt.
+pub async fn insert_dead_letter(pool: &PgPool, dead_letter: &DeadLetter) -> Result<(), ApiError> {
+    sqlx::query(
+        r#"
+        INSERT INTO webhook_dead_letters
+            (id, webhook_id, url, payload, attempts, last_status, last_error, failed_ts_ms)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(&dead_letter.id)
+    .bind(&dead_letter.webhook_id)
+    .bind(&dead_letter.url)
+    .bind(dead_letter.payload.to_string())
+    .bind(dead_letter.attempts)
+    .bind(dead_letter.last_status)
+    .bind(&dead_letter.last_error)
+    .bind(dead_letter.failed_ts_ms)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Up to `limit` dead letters of a webhook, newest first.
+pub async fn get_dead_letters(
+    pool: &PgPool,
+    webhook_id: &str,
+    limit: i64,
+) -> Result<Vec<DeadLetter>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, webhook_id, url, payload, attempts, last_status, last_error, failed_ts_ms
+        FROM webhook_dead_letters
+        WHERE webhook_id = $1
+        ORDER BY failed_ts_ms DESC, id
+        LIMIT $2
+        "#,
+    )
+    .bind(webhook_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let payload: String = row.try_get("payload")?;
+            Ok(DeadLetter {
+                id: row.try_get("id")?,
+                webhook_id: row.try_get("webhook_id")?,
+                url: row.try_get("url")?,
+                // Stored payloads are always JSON; keep anything else as text
+                payload: serde_json::from_str(&payload)
+                    .unwrap_or(serde_json::Value::String(payload)),
+                attempts: row.try_get("attempts")?,
+                last_status: row.try_get("last_status")?,
+                last_error: row.try_get("last_error")?,
+                failed_ts_ms: row.try_get("failed_ts_ms")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Columns of `models`, in the order `model_from_row` expects.
+const MODEL_COLUMNS: &str = "id, pair, model_name, model_version, artifact_uri, features, \
+    training_from_ts_ms, training_to_ts_ms, status, traffic_pct, promoted_ts_ms, created_ts_ms, \
+    updated_ts_ms";
+
+/// Registered models matching `query`, by pair, name and registration time.
+pub async fn get_registered_models(
+    pool: &PgPool,
+    query: &RegistryQuery,
+) -> Result<Vec<RegisteredModel>, ApiError> {
+    let rows = FilteredSelect::new(
+        "SELECT id, pair, model_name, model_version, artifact_uri, features, \
+         training_from_ts_ms, training_to_ts_ms, status, traffic_pct, promoted_ts_ms, \
+         created_ts_ms, updated_ts_ms \
+         FROM models",
+    )
+    .filter_opt("pair", "=", query.pair.as_deref())
+    .filter_opt("model_name", "=", query.model_name.as_deref())
+    .filter_opt("status", "=", query.status.map(ModelStatus::as_str))
+    .then("ORDER BY pair, model_name, created_ts_ms, id")
+    .into_builder()
+    .build()
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(model_from_row)
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// A registered model by id.
+pub async fn get_registered_model(
+    pool: &PgPool,
+    id: &str,
+) -> Result<Option<RegisteredModel>, ApiError> {
+    let row = sqlx::query(&format!("SELECT {MODEL_COLUMNS} FROM models WHERE id = $1"))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(model_from_row).transpose()?)
+}
+
+/// How long a registration that won a race waits for the losers to back
+/// out before concluding one of them had already succeeded.
+const REGISTRATION_GRACE: Duration = Duration::from_secs(1);
+
+/// Register a model version; false if its pair, name and version are
+/// already registered.
+///
+/// RisingWave has no unique indexes, so the entry is looked for before
+/// inserting and again after. Of registrations racing each other, the one
+/// with the lowest id keeps its row and the others delete theirs. A higher
+/// id still there after `REGISTRATION_GRACE` belongs to a registration that
+/// checked before this one inserted and so succeeded; this one backs out
+/// instead.
+pub async fn insert_registered_model(
+    pool: &PgPool,
+    model: &RegisteredModel,
+) -> Result<bool, ApiError> {
+    let registered = || {
+        sqlx::query_scalar::<_, String>(
+            "SELECT id FROM models WHERE pair = $1 AND model_name = $2 AND model_version = $3",
+        )
+        .bind(&model.pair)
+        .bind(&model.model_name)
+        .bind(&model.model_version)
+        .fetch_all(pool)
+    };
+    if !registered().await?.is_empty() {
+        return Ok(false);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO models
+            (id, pair, model_name, model_version, artifact_uri, features,
+             training_from_ts_ms, training_to_ts_ms, status, traffic_pct, created_ts_ms,
+             updated_ts_ms)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        "#,
+    )
+    .bind(&model.id)
+    .bind(&model.pair)
+    .bind(&model.model_name)
+    .bind(&model.model_version)
+    .bind(&model.artifact_uri)
+    .bind(&model.features)
+    .bind(model.training_from_ts_ms)
+    .bind(model.training_to_ts_ms)
+    .bind(model.status.as_str())
+    .bind(model.traffic_pct)
+    .bind(model.created_ts_ms)
+    .bind(model.updated_ts_ms)
+    .execute(pool)
+    .await?;
+
+    let others = |ids: Vec<String>| {
+        ids.into_iter()
+            .filter(|id| *id != model.id)
+            .collect::<Vec<_>>()
+    };
+    let racing = others(registered().await?);
+    if racing.iter().any(|id| *id < model.id) {
+        delete_registered_model(pool, &model.id).await?;
+        return Ok(false);
+    }
+    if !racing.is_empty() {
+        tokio::time::sleep(REGISTRATION_GRACE).await;
+        if !others(registered().await?).is_empty() {
+            delete_registered_model(pool, &model.id).await?;
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Overwrite a registered model; false if there is no such entry or another
+/// one has the same pair, name and version.
+pub async fn update_registered_model(
+    pool: &PgPool,
+    model: &RegisteredModel,
+) -> Result<bool, ApiError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE models
+        SET pair = $2, model_name = $3, model_version = $4, artifact_uri = $5,
+            features = $6, training_from_ts_ms = $7, training_to_ts_ms = $8,
+            status = $9, traffic_pct = $10, updated_ts_ms = $11
+        WHERE id = $1
+          AND NOT EXISTS (
+              SELECT 1 FROM models
+              WHERE pair = $2 AND model_name = $3 AND model_version = $4 AND id <> $1
+          )
+        "#,
+    )
+    .bind(&model.id)
+    .bind(&model.pair)
+    .bind(&model.model_name)
+    .bind(&model.model_version)
+    .bind(&model.artifact_uri)
+    .bind(&model.features)
+    .bind(model.training_from_ts_ms)
+    .bind(model.training_to_ts_ms)
+    .bind(model.status.as_str())
+    .bind(model.traffic_pct)
+    .bind(model.updated_ts_ms)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Staging versions taking a share of their pair's traffic, for pairs with
+/// a production version to split it with.
+pub async fn get_candidates(pool: &PgPool) -> Result<Vec<RegisteredModel>, ApiError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {MODEL_COLUMNS} FROM models c \
+         WHERE status = 'staging' AND traffic_pct > 0 \
+         AND EXISTS (SELECT 1 FROM models p WHERE p.pair = c.pair AND p.status = 'production')"
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(model_from_row)
+        .collect::<Result<_, sqlx::Error>>()?)
+}
+
+/// Whether `model_name` `model_version` has written a prediction for `pair`.
+pub async fn has_predictions(
+    pool: &PgPool,
+    pair: &str,
+    model_name: &str,
+    model_version: &str,
+) -> Result<bool, ApiError> {
+    Ok(sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM predictions
+            WHERE pair = $1 AND model_name = $2 AND model_version = $3
+        )
+        "#,
+    )
+    .bind(pair)
+    .bind(model_name)
+    .bind(model_version)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Make a registered model its pair's production version, retiring the
+/// current one, in a single statement; false if there is no such entry.
+pub async fn promote_registered_model(
+    pool: &PgPool,
+    id: &str,
+    now_ms: i64,
+) -> Result<bool, ApiError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE models
+        SET status = CASE WHEN id = $1 THEN 'production' ELSE 'retired' END,
+            promoted_ts_ms = CASE WHEN id = $1 THEN $2 ELSE promoted_ts_ms END,
+            traffic_pct = 0,
+            updated_ts_ms = $2
+        WHERE pair = (SELECT pair FROM models WHERE id = $1)
+          AND (id = $1 OR status = 'production')
+        "#,
+    )
+    .bind(id)
+    .bind(now_ms)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Retire the production version `id` and restore the version of the same
+/// pair promoted before it, in a single statement.
+///
+/// Returns false, changing nothing, if `id` is not in production or no
+/// version was promoted before it.
+pub async fn rollback_registered_model(
+    pool: &PgPool,
+    id: &str,
+    now_ms: i64,
+) -> Result<bool, ApiError> {
+    let result = sqlx::query(
+        r#"
+        WITH current AS (
+            SELECT id, pair, promoted_ts_ms FROM models
+            WHERE id = $1 AND status = 'production'
+        ),
+        previous AS (
+            SELECT m.id FROM models m, current c
+            WHERE m.pair = c.pair AND m.id <> c.id AND m.promoted_ts_ms < c.promoted_ts_ms
+            ORDER BY m.promoted_ts_ms DESC, m.id
+            LIMIT 1
+        )
+        UPDATE models
+        SET status = CASE WHEN id = $1 THEN 'retired' ELSE 'production' END,
+            traffic_pct = 0,
+            updated_ts_ms = $2
+        WHERE EXISTS (SELECT 1 FROM previous)
+          AND (id IN (SELECT id FROM current) OR id IN (SELECT id FROM previous))
+        "#,
+    )
+    .bind(id)
+    .bind(now_ms)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Remove a model from the registry; false if there is no such entry.
+pub async fn delete_registered_model(pool: &PgPool, id: &str) -> Result<bool, ApiError> {
+    let result = sqlx::query("DELETE FROM models WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+fn model_from_row(row: &PgRow) -> Result<RegisteredModel, sqlx::Error> {
+    let status: String = row.try_get("status")?;
+    Ok(RegisteredModel {
+        id: row.try_get("id")?,
+        pair: row.try_get("pair")?,
+        model_name: row.try_get("model_name")?,
+        model_version: row.try_get("model_version")?,
+        artifact_uri: row.try_get("artifact_uri")?,
+        features: row
+            .try_get::<Option<Vec<String>>, _>("features")?
+            .unwrap_or_default(),
+        training_from_ts_ms: row.try_get("training_from_ts_ms")?,
+        training_to_ts_ms: row.try_get("training_to_ts_ms")?,
+        status: status.parse().map_err(|_| sqlx::Error::ColumnDecode {
+            index: "status".to_string(),
+            source: format!("unknown model status: {status}").into(),
+        })?,
+        traffic_pct: row
+            .try_get::<Option<i32>, _>("traffic_pct")?
+            .unwrap_or_default(),
+        promoted_ts_ms: row.try_get("promoted_ts_ms")?,
+        created_ts_ms: row.try_get("created_ts_ms")?,
+        updated_ts_ms: row.try_get("updated_ts_ms")?,
+    })
+}
+
+fn webhook_from_row(row: &PgRow) -> Result<Webhook, sqlx::Error> {
+    Ok(Webhook {
+        id: row.try_get("id")?,
+        url: row.try_get("url")?,
+        pairs: row.try_get("pairs")?,
+        secret: row.try_get("secret")?,
+        created_ts_ms: row.try_get("created_ts_ms")?,
+    })
+}
+
+/// Claim an idempotency key for a request with `request_hash`, at `now_ms`,
+/// with the random token `claim`.
+///
+/// The key is forgotten first if it was created before `expired_before_ms`,
+/// or is still in progress but was claimed before `abandoned_before_ms`, by
+/// a request that must have died before completing it.
+///
+/// Returns `None` when the key is now claimed by the caller, or the stored
+/// record when an earlier request claimed it. RisingWave has no
+/// `ON CONFLICT`, and overwrites rows with the same key where Postgres
+/// rejects them, so the key is checked before the insert and the caller
+/// holds it only if its own token is what is read back after.
+pub async fn claim_idempotency_key(
+    pool: &PgPool,
+    key: &str,
+    claim: &str,
+    request_hash: &str,
+    now_ms: i64,
+    expired_before_ms: i64,
+    abandoned_before_ms: i64,
+) -> Result<Option<IdempotencyRecord>, ApiError> {
+    sqlx::query(
+        r#"
+        DELETE FROM idempotency_keys
+        WHERE key = $1
+          AND (created_ts_ms < $2 OR (status IS NULL AND created_ts_ms < $3))
+        "#,
+    )
+    .bind(key)
+    .bind(expired_before_ms)
+    .bind(abandoned_before_ms)
+    .execute(pool)
+    .await?;
+
+    if let Some(record) = get_idempotency_key(pool, key).await? {
+        return Ok(Some(record));
+    }
+
+    let insert = sqlx::query(
+        r#"
+        INSERT INTO idempotency_keys (key, request_hash, created_ts_ms, claim)
+        VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(key)
+    .bind(request_hash)
+    .bind(now_ms)
+    .bind(claim)
+    .execute(pool)
+    .await;
+    match insert {
+        Ok(_) => {}
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(match get_idempotency_key(pool, key).await? {
+        Some(record) if record.status.is_none() && record.claim.as_deref() == Some(claim) => None,
+        Some(record) => Some(record),
+        // Released by the request that held it since the insert; report it
+        // as in progress so the client retries
+        None => Some(IdempotencyRecord {
+            request_hash: request_hash.to_string(),
+            status: None,
+            content_type: None,
+            location: None,
+            body: None,
+            claim: None,
+        }),
+    })
+}
+
+async fn get_idempotency_key(
+    pool: &PgPool,
+    key: &str,
+) -> Result<Option<IdempotencyRecord>, ApiError> {
+    let row = sqlx::query(
+        r#"
+        SELECT request_hash, status, content_type, location, body, claim
+        FROM idempotency_keys
+        WHERE key = $1
+        "#,
+    )
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+
+    row.map(|row| {
+        Ok(IdempotencyRecord {
+            request_hash: row.try_get("request_hash")?,
+            status: row.try_get("status")?,
+            content_type: row.try_get("content_type")?,
+            location: row.try_get("location")?,
+            body: row.try_get("body")?,
+            claim: row.try_get("claim")?,
+        })
+    })
+    .transpose()
+}
+
+/// Store the response to the request holding the idempotency key with
+/// `claim`. Returns false, storing nothing, if the claim was given up as
+/// abandoned and the key claimed again.
+pub async fn complete_idempotency_key(
+    pool: &PgPool,
+    key: &str,
+    claim: &str,
+    status: i32,
+    content_type: Option<&str>,
+    location: Option<&str>,
+    body: &[u8],
+) -> Result<bool, ApiError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE idempotency_keys
+        SET status = $3, content_type = $4, location = $5, body = $6
+        WHERE key = $1 AND claim = $2 AND status IS NULL
+        "#,
+    )
+    .bind(key)
+    .bind(claim)
+    .bind(status)
+    .bind(content_type)
+    .bind(location)
+    .bind(body)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Forget the idempotency key held with `claim`, so a retry runs the
+/// request again; a later claim of the key is left alone.
+pub async fn release_idempotency_key(
+    pool: &PgPool,
+    key: &str,
+    claim: &str,
+) -> Result<(), ApiError> {
+    sqlx::query("DELETE FROM idempotency_keys WHERE key = $1 AND claim = $2 AND status IS NULL")
+        .bind(key)
+        .bind(claim)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Get predictions for several pairs within a time range, ordered by pair
+/// then time.
+///
+/// At most `limit` rows are returned; callers pass their row cap plus one to
+/// detect overflow.
+pub async fn get_history_for_pairs(
+    pool: &PgPool,
+    pairs: &[String],
+    from_ts_ms: i64,
+    to_ts_ms: i64,
+    limit: i64,
+) -> Result<Vec<Prediction>, ApiError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT pair, predicted_price, ts_ms, predicted_ts_ms, model_name, model_version,
+               lower_bound, upper_bound, quantile
+        FROM predictions
+        WHERE pair = ANY($1)
+          AND ts_ms BETWEEN $2 AND $3
+        ORDER BY pair, ts_ms
+        LIMIT $4
+        "#,
+    )
+    .bind(pairs)
+    .bind(from_ts_ms)
+    .bind(to_ts_ms)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(prediction_from_row)
+        .filter(|p| p.as_ref().map_or(true, servable))
+        .collect::<Result<_, _>>()?)
+}
+
+/// Map a `predictions` row to a `Prediction`.
+fn prediction_from_row(row: &PgRow) -> Result<Prediction, sqlx::Error> {
+    let ts_ms: i64 = row.try_get("ts_ms")?;
+    let predicted_ts_ms: Option<i64> = row.try_get("predicted_ts_ms")?;
+    let predicted_price: f64 = row.try_get("predicted_price")?;
+    let horizon_ms = predicted_ts_ms.map(|target| target - ts_ms);
+
+    Ok(Prediction {
+        pair: row.try_get("pair")?,
+        predicted_price,
+        ts_ms,
+        ts_iso: timestamp::iso_if_enabled(ts_ms),
+        predicted_ts_ms,
+        predicted_ts_iso: predicted_ts_ms.and_then(timestamp::iso_if_enabled),
+        horizon_ms,
+        lower_bound: row.try_get("lower_bound")?,
+        upper_bound: row.try_get("upper_bound")?,
+        quantile: row.try_get("quantile")?,
+        current_price: None,
+        delta_abs: None,
+        delta_pct: None,
+        direction: None,
+        model_name: row.try_get("model_name")?,
+        model_version: row.try_get("model_version")?,
+        fallback: false,
+        variant: None,
+        valid: predicted_price.is_finite(),
+    })
+}
+
+/// Whether a mapped row may be served under the current `NON_FINITE_PRICE`
+/// mode. Dropped rows are logged, since they point at a broken model.
+fn servable(prediction: &Prediction) -> bool {
+    if prediction.valid {
+        return true;
+    }
+    tracing::warn!(
+        pair = %prediction.pair,
+        ts_ms = prediction.ts_ms,
+        model_name = %prediction.model_name,
+        price = prediction.predicted_price,
+        "Non-finite predicted_price"
+    );
+    non_finite_price() == NonFinitePrice::Lenient
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_prediction(model_name: &str, predicted_price: f64) -> NewPrediction {
+        NewPrediction {
+            pair: "BTCUSDT".to_string(),
+            model_name: model_name.to_string(),
+            model_version: "v1".to_string(),
+            ts_ms: 1_700_000_000_000,
+            predicted_ts_ms: None,
+            predicted_price,
+            lower_bound: None,
+            upper_bound: None,
+            quantile: None,
+        }
+    }
+
+    fn stored(row: &NewPrediction) -> (PredictionKey, Prediction) {
+        let prediction = Prediction {
+            model_name: row.model_name.clone(),
+            predicted_price: row.predicted_price,
+            ..Prediction::sample(&row.pair, row.ts_ms)
+        };
+        (row.key(), prediction)
+    }
+
+    #[test]
+    fn keeps_events_of_rows_stored_as_written() {
+        let row = new_prediction("lgbm", 65_000.0);
+        let (created, lost) = settle(
+            &[&row],
+            vec!["e1".to_string()],
+            &HashMap::from([stored(&row)]),
+        );
+        assert_eq!(created, [row.key()]);
+        assert!(lost.is_empty());
+    }
+
+    #[test]
+    fn drops_events_of_rows_another_write_won() {
+        let won = new_prediction("lgbm", 65_000.0);
+        let overwritten = new_prediction("xgb", 65_000.0);
+        let missing = new_prediction("lstm", 65_000.0);
+        let (created, lost) = settle(
+            &[&won, &overwritten, &missing],
+            vec!["e1".to_string(), "e2".to_string(), "e3".to_string()],
+            &HashMap::from([stored(&won), stored(&new_prediction("xgb", 64_000.0))]),
+        );
+        assert_eq!(created, [won.key()]);
+        assert_eq!(lost, ["e2", "e3"]);
+    }
 }
@@ -0,0 +1,84 @@
+//! Collection of perpetual futures funding rates and open interest.
+//!
+//! Every `DERIVATIVES_INTERVAL_MS`, each pair in `DERIVATIVES_PAIRS` gets
+//! its open interest read into `open_interest`, and the funding rates
+//! settled since the latest one stored fetched into `funding_rates`. A pair
+//! without stored funding rates starts from the latest ones the exchange
+//! returns, up to [`MAX_FUNDING_RATES`], and catches up on longer outages
+//! that many a tick. Open interest is only read as it is now, so readings
+//! missed while the collector is down or the exchange fails are not made up
+//! for. Every replica collecting stores every reading, so only one should.
+
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+
+use crate::db;
+use crate::error::ApiError;
+use crate::exchange::{Exchange, ExchangeError, MAX_FUNDING_RATES};
+
+/// Why collecting a pair's funding rates failed.
+#[derive(thiserror::Error, Debug)]
+enum CollectError {
+    #[error(transparent)]
+    Exchange(#[from] ExchangeError),
+
+    #[error(transparent)]
+    Store(#[from] ApiError),
+}
+
+/// Collect `pairs`' funding rates and open interest every `interval` until
+/// `shutdown` turns true.
+pub fn start(
+    pool: PgPool,
+    exchange: Exchange,
+    pairs: Vec<String>,
+    interval: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.wait_for(|&closed| closed) => break,
+            }
+
+            for pair in &pairs {
+                match collect_funding_rates(&pool, &exchange, pair).await {
+                    Ok(count) => tracing::debug!(%pair, count, "Funding rates stored"),
+                    Err(e) => tracing::warn!(%pair, error = %e, "Failed to collect funding rates"),
+                }
+                match exchange.open_interest(pair).await {
+                    Ok(reading) => match db::insert_open_interest(&pool, pair, &reading).await {
+                        Ok(()) => {
+                            tracing::debug!(%pair, ts_ms = reading.ts_ms, "Open interest stored")
+                        }
+                        Err(e) => {
+                            tracing::warn!(%pair, error = %e, "Failed to store open interest")
+                        }
+                    },
+                    Err(e) => tracing::warn!(%pair, error = %e, "Failed to fetch open interest"),
+                }
+            }
+        }
+    });
+}
+
+/// Store `pair`'s funding rates settled after the latest one stored;
+/// returns how many there were.
+async fn collect_funding_rates(
+    pool: &PgPool,
+    exchange: &Exchange,
+    pair: &str,
+) -> Result<usize, CollectError> {
+    let after = db::get_latest_funding_ts(pool, pair).await?;
+    let rates = exchange
+        .funding_rates(pair, after.map(|ts_ms| ts_ms + 1), MAX_FUNDING_RATES)
+        .await?;
+    db::insert_funding_rates(pool, pair, &rates).await?;
+    Ok(rates.len())
+}
@@ -0,0 +1,83 @@
+//! Opt-in envelope around list responses.
+//!
+//! With `?envelope=true`, a JSON array (or a page of `items`) is returned as
+//! `{"data": [...], "count": ..., "generated_at": ..., "query_time_ms": ...}`
+//! plus `next_cursor` for paged listings. Without it, responses are
+//! unchanged, so existing clients keep working.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::timestamp;
+
+/// Query parameter requesting the envelope.
+#[derive(Debug, Default, Deserialize, IntoParams, ToSchema)]
+pub struct EnvelopeQuery {
+    /// Wrap the list in an envelope with count, freshness and paging info
+    pub envelope: Option<bool>,
+}
+
+/// A list response wrapped with `envelope=true`.
+#[derive(Serialize, ToSchema)]
+pub struct Envelope {
+    /// The list the endpoint returns without the envelope
+    pub data: Vec<Value>,
+    /// Items in `data`
+    pub count: usize,
+    /// When the response was produced (ISO-8601, UTC)
+    pub generated_at: String,
+    /// Time spent handling the request (ms)
+    pub query_time_ms: u64,
+    /// Cursor for the next page, for paged listings only; null on the last
+    /// page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<Value>,
+}
+
+/// Wrap a list body in an envelope. Returns `None` for bodies that are not
+/// lists, which are served unchanged.
+pub fn wrap(body: Value, query_time_ms: u64) -> Option<Value> {
+    let (data, next_cursor) = match body {
+        Value::Array(items) => (items, None),
+        Value::Object(mut page) if page.contains_key("next_cursor") => {
+            let Some(Value::Array(items)) = page.remove("items") else {
+                return None;
+            };
+            (items, page.remove("next_cursor"))
+        }
+        _ => return None,
+    };
+
+    Some(json!(Envelope {
+        count: data.len(),
+        data,
+        generated_at: timestamp::to_iso8601(timestamp::now_ms()),
+        query_time_ms,
+        next_cursor,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_arrays_and_pages() {
+        let wrapped = wrap(json!([{"pair": "BTCUSDT"}]), 3).unwrap();
+        assert_eq!(wrapped["count"], 1);
+        assert_eq!(wrapped["data"], json!([{"pair": "BTCUSDT"}]));
+        assert_eq!(wrapped["query_time_ms"], 3);
+        assert!(wrapped.get("next_cursor").is_none());
+
+        let wrapped = wrap(json!({"items": [], "next_cursor": null}), 0).unwrap();
+        assert_eq!(wrapped["count"], 0);
+        assert_eq!(wrapped["next_cursor"], Value::Null);
+    }
+
+    #[test]
+    fn leaves_other_bodies_alone() {
+        assert_eq!(wrap(json!({"pair": "BTCUSDT"}), 0), None);
+        assert_eq!(wrap(json!({"BTCUSDT": null}), 0), None);
+    }
+}
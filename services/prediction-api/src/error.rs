@@ -1,11 +1,17 @@
 //! Error types for the prediction API.
 
+use std::fmt;
+
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Content type of error responses (RFC 7807).
+pub const PROBLEM_JSON: &str = "application/problem+json";
 
 /// API error types with proper HTTP status codes.
 #[derive(thiserror::Error, Debug)]
@@ -13,47 +19,352 @@ pub enum ApiError {
     #[error("Prediction not found for pair: {0}")]
     NotFound(String),
 
+    #[error("Webhook not found: {0}")]
+    WebhookNotFound(String),
+
+    #[error("Pair alias not found: {0}")]
+    AliasNotFound(String),
+
+    #[error("Rejected prediction not found: {0}")]
+    RejectedNotFound(String),
+
+    #[error("Model not found: {0}")]
+    ModelNotFound(String),
+
+    #[error("Order book not found for pair: {0}")]
+    OrderBookNotFound(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
 
     #[error("Invalid request: {0}")]
     BadRequest(String),
 
+    #[error("Invalid request: {}", FieldError::summary(.0))]
+    InvalidFields(Vec<FieldError>),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
     #[error("Configuration error: {0}")]
     Config(String),
 
+    #[error("Service overloaded, retry after {0}s")]
+    Overloaded(u64),
+
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Rate limit exceeded")]
+    RateLimited,
+
     #[error("Internal server error")]
     Internal,
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        let (status, message) = match &self {
+/// Machine-readable error code. Codes are stable; clients should branch on
+/// them rather than on messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// No prediction matches the pair (and model filter)
+    PairNotFound,
+    /// No webhook has the given id
+    WebhookNotFound,
+    /// No pair alias has the given symbol
+    AliasNotFound,
+    /// No rejected prediction has the given id
+    RejectedNotFound,
+    /// No registered model has the given id
+    ModelNotFound,
+    /// No order book snapshot of the pair at or before the requested time
+    OrderBookNotFound,
+    /// The resource already exists
+    Conflict,
+    /// A parameter or the request body is invalid
+    ValidationFailed,
+    /// The admin API key is missing or wrong
+    Unauthorized,
+    /// Too many requests from this client; see `Retry-After`
+    RateLimited,
+    /// The database pool is saturated; see `Retry-After`
+    Overloaded,
+    /// The service cannot serve the request, e.g. in degraded mode
+    ServiceUnavailable,
+    /// A database query failed
+    DatabaseError,
+    /// The server is misconfigured
+    ConfigurationError,
+    /// Any other server error
+    InternalError,
+}
+
+impl ErrorCode {
+    /// Problem `type` URI for the code, e.g.
+    /// `urn:prediction-api:problem:pair-not-found`.
+    fn type_uri(self) -> String {
+        let code = serde_json::to_value(self)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        format!(
+            "urn:prediction-api:problem:{}",
+            code.to_lowercase().replace('_', "-")
+        )
+    }
+}
+
+/// A request body field that failed validation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct FieldError {
+    /// Name of the field
+    pub field: &'static str,
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+
+    /// All errors on one line, e.g. `ts_ms: must be positive; quantile: ...`.
+    pub fn summary(errors: &[FieldError]) -> String {
+        errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Error response body: RFC 7807 problem details plus a stable `code`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Problem {
+    /// URI identifying the problem type
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// Short summary of the problem type
+    pub title: &'static str,
+    /// HTTP status code
+    pub status: u16,
+    /// Explanation of this occurrence
+    pub detail: String,
+    /// Machine-readable error code
+    pub code: ErrorCode,
+    /// Same as `detail`; deprecated, kept for clients of the old error body
+    pub error: String,
+    /// Every field that failed validation, when the request body was checked
+    /// field by field
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl ApiError {
+    /// Status, code, title and client-facing detail of the error. Server-side
+    /// details are logged here and kept out of the response.
+    fn describe(&self) -> (StatusCode, ErrorCode, &'static str, String) {
+        match self {
             ApiError::NotFound(pair) => (
                 StatusCode::NOT_FOUND,
+                ErrorCode::PairNotFound,
+                "Prediction not found",
                 format!("Prediction not found for pair: {}", pair),
             ),
+            ApiError::WebhookNotFound(id) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::WebhookNotFound,
+                "Webhook not found",
+                format!("Webhook not found: {}", id),
+            ),
+            ApiError::AliasNotFound(alias) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::AliasNotFound,
+                "Pair alias not found",
+                format!("Pair alias not found: {}", alias),
+            ),
+            ApiError::RejectedNotFound(id) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::RejectedNotFound,
+                "Rejected prediction not found",
+                format!("Rejected prediction not found: {}", id),
+            ),
+            ApiError::ModelNotFound(id) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::ModelNotFound,
+                "Model not found",
+                format!("Model not found: {}", id),
+            ),
+            ApiError::OrderBookNotFound(pair) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::OrderBookNotFound,
+                "Order book not found",
+                format!("Order book not found for pair: {}", pair),
+            ),
+            ApiError::Conflict(msg) => (
+                StatusCode::CONFLICT,
+                ErrorCode::Conflict,
+                "Conflict",
+                msg.clone(),
+            ),
             ApiError::Database(e) => {
                 tracing::error!("Database error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::DatabaseError,
+                    "Database error",
                     "Database error".to_string(),
                 )
             }
-            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            ApiError::BadRequest(msg) => (
+                StatusCode::BAD_REQUEST,
+                ErrorCode::ValidationFailed,
+                "Invalid request",
+                msg.clone(),
+            ),
+            ApiError::InvalidFields(errors) => (
+                StatusCode::BAD_REQUEST,
+                ErrorCode::ValidationFailed,
+                "Invalid request",
+                FieldError::summary(errors),
+            ),
+            ApiError::Unauthorized(msg) => (
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
+                "Unauthorized",
+                msg.clone(),
+            ),
             ApiError::Config(msg) => {
                 tracing::error!("Config error: {}", msg);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::ConfigurationError,
+                    "Configuration error",
                     "Configuration error".to_string(),
                 )
             }
+            ApiError::Overloaded(_) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::Overloaded,
+                "Service overloaded",
+                "Service overloaded, retry later".to_string(),
+            ),
+            ApiError::Unavailable(reason) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::ServiceUnavailable,
+                "Service unavailable",
+                format!("Service unavailable: {}", reason),
+            ),
+            ApiError::RateLimited => (
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorCode::RateLimited,
+                "Rate limit exceeded",
+                "Too many requests, retry later".to_string(),
+            ),
             ApiError::Internal => (
                 StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::InternalError,
+                "Internal server error",
                 "Internal server error".to_string(),
             ),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, code, title, detail) = self.describe();
+        let problem = Problem {
+            problem_type: code.type_uri(),
+            title,
+            status: status.as_u16(),
+            error: detail.clone(),
+            detail,
+            code,
+            errors: match &self {
+                ApiError::InvalidFields(errors) => errors.clone(),
+                _ => Vec::new(),
+            },
         };
 
-        (status, Json(json!({ "error": message }))).into_response()
+        let mut response = (status, Json(problem)).into_response();
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        if let ApiError::Overloaded(retry_after_secs) = self {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn renders_problem_details() {
+        let response = ApiError::NotFound("BTCUSDT".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            payload,
+            json!({
+                "type": "urn:prediction-api:problem:pair-not-found",
+                "title": "Prediction not found",
+                "status": 404,
+                "detail": "Prediction not found for pair: BTCUSDT",
+                "code": "PAIR_NOT_FOUND",
+                "error": "Prediction not found for pair: BTCUSDT"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn lists_invalid_fields() {
+        let response = ApiError::InvalidFields(vec![
+            FieldError::new("ts_ms", "must be positive"),
+            FieldError::new("quantile", "must be between 0 and 1"),
+        ])
+        .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            payload["detail"],
+            "ts_ms: must be positive; quantile: must be between 0 and 1"
+        );
+        assert_eq!(
+            payload["errors"],
+            json!([
+                {"field": "ts_ms", "message": "must be positive"},
+                {"field": "quantile", "message": "must be between 0 and 1"}
+            ])
+        );
+    }
+
+    #[test]
+    fn overload_sets_retry_after() {
+        let response = ApiError::Overloaded(3).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
     }
 }
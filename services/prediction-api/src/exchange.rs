@@ -0,0 +1,310 @@
+//! Client for the market data endpoints of the Binance USDⓈ-M futures REST
+//! API, for the market data this service collects itself.
+//!
+//! Requests go out over HTTPS, trusting the Mozilla root certificates of
+//! `webpki-roots`. The base URL is configurable (`EXCHANGE_BASE_URL`), so a
+//! stand-in can take the exchange's place outside production.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::rt::TokioExecutor;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::routes::derivatives::{FundingRate, OpenInterest};
+use crate::routes::orderbook::OrderBook;
+use crate::webhooks::error_chain;
+
+/// How long one request may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most funding rates the exchange returns for one request.
+pub const MAX_FUNDING_RATES: u32 = 1000;
+
+/// Largest response body read; a 1000-level book is well below it.
+const MAX_BODY: usize = 4 * 1024 * 1024;
+
+/// `User-Agent` of exchange requests.
+const USER_AGENT: &str = concat!("prediction-api/", env!("CARGO_PKG_VERSION"));
+
+/// Why a request to the exchange failed.
+#[derive(thiserror::Error, Debug)]
+pub enum ExchangeError {
+    #[error("request failed: {0}")]
+    Request(String),
+
+    #[error("exchange answered {status}: {body}")]
+    Status { status: StatusCode, body: String },
+
+    #[error("invalid response: {0}")]
+    Invalid(String),
+}
+
+/// Handle to the exchange; cheap to clone.
+#[derive(Clone)]
+pub struct Exchange {
+    client: Client<HttpsConnector<HttpConnector>, Body>,
+    base_url: Arc<str>,
+}
+
+impl Exchange {
+    pub fn new(base_url: &str) -> Self {
+        let connector = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Self {
+            client: Client::builder(TokioExecutor::new()).build(connector),
+            base_url: base_url.trim_end_matches('/').into(),
+        }
+    }
+
+    /// The top `limit` levels of each side of `symbol`'s order book.
+    ///
+    /// `limit` must be one the exchange accepts: 5, 10, 20, 50, 100, 500 or
+    /// 1000.
+    pub async fn depth(&self, symbol: &str, limit: u32) -> Result<OrderBook, ExchangeError> {
+        let depth: Depth = self
+            .get(&format!("/fapi/v1/depth?symbol={symbol}&limit={limit}"))
+            .await?;
+        depth.into_order_book(symbol)
+    }
+
+    /// `symbol`'s funding rates from `start_time` (ms) on, oldest first; the
+    /// latest ones without it. At most `limit`, which may not exceed
+    /// [`MAX_FUNDING_RATES`].
+    pub async fn funding_rates(
+        &self,
+        symbol: &str,
+        start_time: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<FundingRate>, ExchangeError> {
+        let mut path = format!("/fapi/v1/fundingRate?symbol={symbol}&limit={limit}");
+        if let Some(start_time) = start_time {
+            path.push_str(&format!("&startTime={start_time}"));
+        }
+        let rates: Vec<RawFundingRate> = self.get(&path).await?;
+        rates.into_iter().map(RawFundingRate::parse).collect()
+    }
+
+    /// `symbol`'s open interest now.
+    pub async fn open_interest(&self, symbol: &str) -> Result<OpenInterest, ExchangeError> {
+        let reading: RawOpenInterest = self
+            .get(&format!("/fapi/v1/openInterest?symbol={symbol}"))
+            .await?;
+        reading.parse()
+    }
+
+    /// GET `path_and_query` and parse the JSON response.
+    async fn get<T: DeserializeOwned>(&self, path_and_query: &str) -> Result<T, ExchangeError> {
+        let request = Request::get(format!("{}{}", self.base_url, path_and_query))
+            .header(header::USER_AGENT, USER_AGENT)
+            .body(Body::empty())
+            .map_err(|e| ExchangeError::Request(format!("invalid request: {}", e)))?;
+
+        let response = tokio::time::timeout(REQUEST_TIMEOUT, self.client.request(request))
+            .await
+            .map_err(|_| ExchangeError::Request("timed out".to_string()))?
+            .map_err(|e| ExchangeError::Request(error_chain(&e)))?;
+        let status = response.status();
+        let body = to_bytes(Body::new(response.into_body()), MAX_BODY)
+            .await
+            .map_err(|e| ExchangeError::Request(error_chain(&e)))?;
+
+        if !status.is_success() {
+            return Err(ExchangeError::Status {
+                status,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+        serde_json::from_slice(&body).map_err(|e| ExchangeError::Invalid(e.to_string()))
+    }
+}
+
+/// Response of `GET /fapi/v1/depth`. Prices and quantities are decimal
+/// strings.
+#[derive(Debug, Deserialize)]
+struct Depth {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: i64,
+    /// Transaction time (ms)
+    #[serde(rename = "T")]
+    transaction_time: i64,
+    bids: Vec<[String; 2]>,
+    asks: Vec<[String; 2]>,
+}
+
+impl Depth {
+    fn into_order_book(self, pair: &str) -> Result<OrderBook, ExchangeError> {
+        Ok(OrderBook {
+            pair: pair.to_string(),
+            ts_ms: self.transaction_time,
+            last_update_id: self.last_update_id,
+            bids: levels(&self.bids)?,
+            asks: levels(&self.asks)?,
+        })
+    }
+}
+
+/// Element of the response of `GET /fapi/v1/fundingRate`.
+#[derive(Debug, Deserialize)]
+struct RawFundingRate {
+    #[serde(rename = "fundingRate")]
+    funding_rate: String,
+    #[serde(rename = "fundingTime")]
+    funding_time: i64,
+    /// Empty for funding times before the exchange recorded it
+    #[serde(rename = "markPrice", default)]
+    mark_price: String,
+}
+
+impl RawFundingRate {
+    fn parse(self) -> Result<FundingRate, ExchangeError> {
+        Ok(FundingRate {
+            ts_ms: self.funding_time,
+            funding_rate: decimal(&self.funding_rate)?,
+            mark_price: match self.mark_price.as_str() {
+                "" => None,
+                price => Some(decimal(price)?),
+            },
+        })
+    }
+}
+
+/// Response of `GET /fapi/v1/openInterest`.
+#[derive(Debug, Deserialize)]
+struct RawOpenInterest {
+    #[serde(rename = "openInterest")]
+    open_interest: String,
+    time: i64,
+}
+
+impl RawOpenInterest {
+    fn parse(self) -> Result<OpenInterest, ExchangeError> {
+        Ok(OpenInterest {
+            ts_ms: self.time,
+            open_interest: decimal(&self.open_interest)?,
+        })
+    }
+}
+
+/// `[price, quantity]` levels parsed from the exchange's decimal strings.
+fn levels(raw: &[[String; 2]]) -> Result<Vec<[f64; 2]>, ExchangeError> {
+    raw.iter()
+        .map(|[price, quantity]| Ok([decimal(price)?, decimal(quantity)?]))
+        .collect()
+}
+
+/// A finite number parsed from one of the exchange's decimal strings.
+fn decimal(s: &str) -> Result<f64, ExchangeError> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| ExchangeError::Invalid(format!("invalid decimal: {:?}", s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_depth() {
+        // Example response from the GET /fapi/v1/depth documentation
+        let depth: Depth = serde_json::from_str(
+            r#"{
+                "lastUpdateId": 1027024,
+                "E": 1589436922972,
+                "T": 1589436922959,
+                "bids": [["4.00000000", "431.00000000"]],
+                "asks": [["4.00000200", "12.00000000"]]
+            }"#,
+        )
+        .unwrap();
+
+        let book = depth.into_order_book("BTCUSDT").unwrap();
+        assert_eq!(book.pair, "BTCUSDT");
+        assert_eq!(book.ts_ms, 1_589_436_922_959);
+        assert_eq!(book.last_update_id, 1_027_024);
+        assert_eq!(book.bids, [[4.0, 431.0]]);
+        assert_eq!(book.asks, [[4.000002, 12.0]]);
+    }
+
+    #[test]
+    fn parses_funding_rates() {
+        // Example response from the GET /fapi/v1/fundingRate documentation,
+        // with an entry from before mark prices were recorded
+        let rates: Vec<RawFundingRate> = serde_json::from_str(
+            r#"[
+                {
+                    "symbol": "BTCUSDT",
+                    "fundingRate": "-0.03750000",
+                    "fundingTime": 1570608000000,
+                    "markPrice": "34287.54619963"
+                },
+                {
+                    "symbol": "BTCUSDT",
+                    "fundingRate": "0.00010000",
+                    "fundingTime": 1570636800000,
+                    "markPrice": ""
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        let rates: Vec<_> = rates
+            .into_iter()
+            .map(RawFundingRate::parse)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rates,
+            [
+                FundingRate {
+                    ts_ms: 1_570_608_000_000,
+                    funding_rate: -0.0375,
+                    mark_price: Some(34287.54619963),
+                },
+                FundingRate {
+                    ts_ms: 1_570_636_800_000,
+                    funding_rate: 0.0001,
+                    mark_price: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_open_interest() {
+        // Example response from the GET /fapi/v1/openInterest documentation
+        let reading: RawOpenInterest = serde_json::from_str(
+            r#"{
+                "openInterest": "10659.509",
+                "symbol": "BTCUSDT",
+                "time": 1589437530011
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            reading.parse().unwrap(),
+            OpenInterest {
+                ts_ms: 1_589_437_530_011,
+                open_interest: 10659.509,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unparseable_levels() {
+        let raw = [["4.0".to_string(), "NaN".to_string()]];
+        assert!(levels(&raw).is_err());
+        let raw = [["".to_string(), "1".to_string()]];
+        assert!(levels(&raw).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! A/B traffic splits between a pair's production version and a candidate.
+//!
+//! A staging version given a `traffic_pct` in the model registry is served
+//! instead of its pair's production version to that share of the requests
+//! to `GET /predictions`, `GET /predictions/latest` and
+//! `POST /predictions/batch` that name no model. Requests are assigned by hashing a subject, the
+//! client's API key or the pair (`AB_STICKY_BY`), with the candidate's id,
+//! so a subject sees the same variant on every request and replica for as
+//! long as the split is unchanged. Cacheable responses assigned by API key
+//! carry `Vary: authorization, x-api-key`, so a cache doesn't hand one
+//! client's variant to another.
+//!
+//! Every assignment is logged as a `Variant served` event with the variant,
+//! the version served and a hash of the subject, for offline comparison.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use axum::http::{header, HeaderMap};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::db::{self, ModelFilter};
+use crate::error::ApiError;
+use crate::routes::predictions::Prediction;
+use crate::routes::registry::RegisteredModel;
+use crate::state::AppState;
+
+/// What keeps a client on one variant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StickyBy {
+    /// The bearer token or `X-API-Key` the request carries; requests with
+    /// neither fall back to the pair
+    #[default]
+    ApiKey,
+    /// The pair, so every client sees the same variant of it
+    Pair,
+}
+
+impl FromStr for StickyBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "api_key" => Ok(Self::ApiKey),
+            "pair" => Ok(Self::Pair),
+            other => Err(format!("unknown sticky key: {}", other)),
+        }
+    }
+}
+
+/// Which side of a split a prediction was served from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    /// The pair's production version
+    A,
+    /// The candidate version
+    B,
+}
+
+/// Serve each prediction in `predictions` from the variant its request is
+/// assigned, for pairs with a candidate.
+///
+/// `predictions` must come from a lookup that named no model; `model`
+/// carries that lookup's other filters, `max_ts_ms` its cutoff and
+/// `max_interval_pct` its interval bound, so the candidate's prediction is
+/// chosen the same way. A candidate with no
+/// matching prediction leaves the production one in place. The candidates'
+/// predictions are fetched in one query, however many pairs are split.
+pub async fn apply(
+    state: &AppState,
+    headers: &HeaderMap,
+    predictions: &mut [Prediction],
+    model: ModelFilter<'_>,
+    max_ts_ms: Option<i64>,
+    max_interval_pct: Option<f64>,
+) -> Result<(), ApiError> {
+    if predictions.is_empty() {
+        return Ok(());
+    }
+    let candidates = db::get_candidates(&state.pool).await?;
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    let api_key = api_key(headers);
+    let assigned: Vec<_> = predictions
+        .iter()
+        .map(|prediction| {
+            let candidate = candidates.iter().find(|c| c.pair == prediction.pair)?;
+            let subject = match (state.config.ab_sticky_by, api_key) {
+                (StickyBy::ApiKey, Some(key)) => key,
+                _ => &candidate.pair,
+            };
+            Some((candidate, subject, assign(candidate, subject)))
+        })
+        .collect();
+
+    let b: Vec<&RegisteredModel> = assigned
+        .iter()
+        .flatten()
+        .filter(|(_, _, variant)| *variant == Variant::B)
+        .map(|(candidate, _, _)| *candidate)
+        .collect();
+    let mut served: HashMap<String, Prediction> =
+        db::get_latest_for_candidates(&state.pool, &b, model, max_ts_ms, max_interval_pct)
+            .await?
+            .into_iter()
+            .map(|p| (p.pair.clone(), p))
+            .collect();
+
+    for (prediction, assigned) in predictions.iter_mut().zip(assigned) {
+        let Some((candidate, subject, assigned)) = assigned else {
+            continue;
+        };
+        let mut variant = Variant::A;
+        if assigned == Variant::B {
+            if let Some(b) = served.remove(&candidate.pair) {
+                *prediction = b;
+                variant = Variant::B;
+            }
+        }
+        prediction.variant = Some(variant);
+
+        tracing::info!(
+            pair = %prediction.pair,
+            candidate = %candidate.id,
+            ?variant,
+            model_name = %prediction.model_name,
+            model_version = %prediction.model_version,
+            subject = %subject_hash(subject),
+            "Variant served"
+        );
+    }
+    Ok(())
+}
+
+/// `Vary` for a response built from `predictions`: the request headers that
+/// chose its variants, so caches keep one client's variant from another.
+/// `None` when no variant was chosen or every client gets the same one.
+pub fn vary(state: &AppState, predictions: &[Prediction]) -> Option<&'static str> {
+    let split = predictions.iter().any(|p| p.variant.is_some());
+    (split && state.config.ab_sticky_by == StickyBy::ApiKey).then_some("authorization, x-api-key")
+}
+
+/// The variant `subject` is assigned in `candidate`'s split.
+fn assign(candidate: &RegisteredModel, subject: &str) -> Variant {
+    if bucket(&candidate.id, subject) < candidate.traffic_pct {
+        Variant::B
+    } else {
+        Variant::A
+    }
+}
+
+/// Where `subject` falls in the split, from 0 to 99.
+///
+/// A stable hash, unlike `std`'s, so replicas and restarts agree. Salting
+/// with the candidate id reshuffles subjects for every new split.
+fn bucket(candidate_id: &str, subject: &str) -> i32 {
+    let digest = Sha256::new()
+        .chain_update(candidate_id)
+        .chain_update(b":")
+        .chain_update(subject)
+        .finalize();
+    let value = u64::from_be_bytes(digest[..8].try_into().expect("8 bytes"));
+    (value % 100) as i32
+}
+
+/// The client's API key: a bearer token or `X-API-Key`.
+fn api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .filter(|key| !key.is_empty())
+}
+
+/// Short hash identifying a subject in logs without revealing API keys.
+fn subject_hash(subject: &str) -> String {
+    hex::encode(&Sha256::digest(subject)[..8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::registry::ModelStatus;
+
+    fn candidate(traffic_pct: i32) -> RegisteredModel {
+        RegisteredModel {
+            id: "5f0c6e3a9b1d4c2e8a7f6b5d4c3e2a1f".to_string(),
+            pair: "BTCUSDT".to_string(),
+            model_name: "lgbm".to_string(),
+            model_version: "v4".to_string(),
+            artifact_uri: None,
+            features: Vec::new(),
+            training_from_ts_ms: None,
+            training_to_ts_ms: None,
+            status: ModelStatus::Staging,
+            traffic_pct,
+            promoted_ts_ms: None,
+            created_ts_ms: 0,
+            updated_ts_ms: 0,
+        }
+    }
+
+    #[test]
+    fn assigns_subjects_stably_in_proportion() {
+        let split = candidate(20);
+        let subjects: Vec<String> = (0..10_000).map(|i| format!("key-{i}")).collect();
+        let b = subjects
+            .iter()
+            .filter(|s| assign(&split, s) == Variant::B)
+            .count();
+        assert!((1_700..2_300).contains(&b), "{b} of 10000 assigned B");
+
+        for subject in &subjects[..100] {
+            assert_eq!(assign(&split, subject), assign(&split, subject));
+        }
+        assert!(subjects
+            .iter()
+            .all(|s| assign(&candidate(0), s) == Variant::A));
+        assert!(subjects
+            .iter()
+            .all(|s| assign(&candidate(100), s) == Variant::B));
+    }
+
+    #[test]
+    fn reads_api_keys() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(api_key(&headers), None);
+        headers.insert("x-api-key", "k1".parse().unwrap());
+        assert_eq!(api_key(&headers), Some("k1"));
+        headers.insert(header::AUTHORIZATION, "Bearer k2".parse().unwrap());
+        assert_eq!(api_key(&headers), Some("k2"));
+    }
+}
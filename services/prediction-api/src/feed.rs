@@ -0,0 +1,278 @@
+//! Live feed of newly written predictions.
+//!
+//! Every prediction write also appends the prediction to the
+//! `prediction_events` outbox, whether it comes through the ingestion
+//! endpoints or from the model workers. The event is written just before
+//! the prediction, and the outbox is read joined with the predictions, so
+//! an event is relayed once its prediction is stored, and never if the
+//! prediction is not. The feed polls the outbox for events not relayed yet
+//! and broadcasts them to subscribers. The outbox is only polled while
+//! someone is subscribed. The webhook dispatcher reads the outbox the same
+//! way, with a `Relay` of its own.
+//!
+//! Events are ordered by the time their writer stamped them, but become
+//! visible with their predictions, a little later. An event can therefore
+//! show up behind events already relayed; the feed reads the events of the
+//! last `LATE_WINDOW` again now and then to catch those.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::db::{self, Event};
+use crate::error::ApiError;
+use crate::routes::predictions::Prediction;
+use crate::timestamp;
+
+/// Predictions buffered per subscriber before a slow one is cut off.
+const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// Events read per poll query; a poll keeps reading until it catches up.
+const POLL_BATCH: usize = 500;
+
+/// Longest an event may take to become visible with its prediction after
+/// its writer stamped it, allowing for clock skew between writers. An event
+/// not visible by then belongs to a write that failed.
+const LATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// How often the events of the last `LATE_WINDOW` are read again.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Handle to the feed; cheap to clone.
+#[derive(Clone)]
+pub struct Feed {
+    sender: broadcast::Sender<Arc<Prediction>>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl Feed {
+    /// Start polling `pool` every `interval` until `shutdown` turns true.
+    pub fn start(pool: PgPool, interval: Duration, shutdown: watch::Receiver<bool>) -> Self {
+        let (sender, _) = broadcast::channel(SUBSCRIBER_BUFFER);
+        tokio::spawn(poll(pool, interval, sender.clone(), shutdown.clone()));
+        Self { sender, shutdown }
+    }
+
+    /// Receive predictions written from now on, oldest first.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Prediction>> {
+        self.sender.subscribe()
+    }
+
+    /// Resolves once the service starts shutting down, so open streams can
+    /// end instead of holding the shutdown up.
+    pub async fn closed(&self) {
+        let mut shutdown = self.shutdown.clone();
+        let _ = shutdown.wait_for(|&closed| closed).await;
+    }
+
+    /// Wait on `updates` for a prediction for `pair`. Returns false when
+    /// `deadline` passes or the service shuts down first.
+    ///
+    /// A receiver that fell behind may have missed one, so it counts as a
+    /// change too.
+    pub async fn changed(
+        &self,
+        updates: &mut broadcast::Receiver<Arc<Prediction>>,
+        pair: &str,
+        deadline: Instant,
+    ) -> bool {
+        let changed = async {
+            loop {
+                match updates.recv().await {
+                    Ok(prediction) if prediction.pair == pair => return true,
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(_)) => return true,
+                    Err(RecvError::Closed) => return false,
+                }
+            }
+        };
+
+        tokio::select! {
+            changed = changed => changed,
+            _ = tokio::time::sleep_until(deadline) => false,
+            _ = self.closed() => false,
+        }
+    }
+}
+
+async fn poll(
+    pool: PgPool,
+    interval: Duration,
+    sender: broadcast::Sender<Arc<Prediction>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // `None` until the first poll with subscribers, which starts with the
+    // events written from then on
+    let mut relay: Option<Relay> = None;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.wait_for(|&closed| closed) => break,
+        }
+
+        if sender.receiver_count() == 0 {
+            relay = None;
+            continue;
+        }
+
+        let relay = relay.get_or_insert_with(|| Relay::after(timestamp::now_ms()));
+        match relay.poll(&pool).await {
+            Ok(events) => {
+                for prediction in events.into_iter().filter_map(|event| event.prediction) {
+                    let _ = sender.send(Arc::new(prediction));
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "Prediction feed poll failed"),
+        }
+    }
+
+    tracing::debug!("Prediction feed stopped");
+}
+
+/// How far a reader has relayed the outbox: the feed, and the webhook
+/// dispatcher, which saves `settled_ms` to resume from after a restart.
+#[derive(Debug)]
+pub struct Relay {
+    /// Every event written at or before this was relayed or given up on
+    settled_ms: i64,
+    /// The last event read in order, where reading new events resumes
+    high: (i64, String),
+    /// Write times of the events relayed that were written after
+    /// `settled_ms`, by id
+    seen: HashMap<String, i64>,
+    /// When the events written after `settled_ms` were last read again
+    rescanned: Option<Instant>,
+}
+
+impl Relay {
+    /// Start with the events written after `after_ms`.
+    pub fn after(after_ms: i64) -> Self {
+        Self {
+            settled_ms: after_ms,
+            // Every id sorts after the empty one
+            high: (after_ms + 1, String::new()),
+            seen: HashMap::new(),
+            rescanned: None,
+        }
+    }
+
+    /// Read the events not relayed yet: new ones oldest first, then any
+    /// found late.
+    pub async fn poll(&mut self, pool: &PgPool) -> Result<Vec<Event>, ApiError> {
+        let started_ms = timestamp::now_ms();
+        let mut relayed = Vec::new();
+        loop {
+            let events = db::get_events_after(pool, self.high.0, &self.high.1, POLL_BATCH).await?;
+            let caught_up = events.len() < POLL_BATCH;
+            if let Some(last) = events.last() {
+                self.high = (last.created_ts_ms, last.id.clone());
+            }
+            relayed.extend(self.unseen(events));
+            if caught_up {
+                break;
+            }
+        }
+
+        if self
+            .rescanned
+            .is_none_or(|at| at.elapsed() >= RESCAN_INTERVAL)
+        {
+            let missing: Vec<String> = db::get_event_ids_since(pool, self.settled_ms)
+                .await?
+                .into_iter()
+                .filter(|(id, _)| !self.seen.contains_key(id))
+                .map(|(id, _)| id)
+                .collect();
+            if !missing.is_empty() {
+                let late = self.unseen(db::get_events(pool, &missing).await?);
+                tracing::debug!(count = late.len(), "Late prediction events relayed");
+                relayed.extend(late);
+            }
+            self.rescanned = Some(Instant::now());
+            self.settle(started_ms.saturating_sub(LATE_WINDOW.as_millis() as i64));
+        }
+        Ok(relayed)
+    }
+
+    /// Every event written at or before this was relayed or given up on.
+    pub fn settled_ms(&self) -> i64 {
+        self.settled_ms
+    }
+
+    /// The events among `events` not relayed before, marked relayed.
+    fn unseen(&mut self, events: Vec<Event>) -> Vec<Event> {
+        events
+            .into_iter()
+            .filter(|event| {
+                event.created_ts_ms > self.settled_ms
+                    && self
+                        .seen
+                        .insert(event.id.clone(), event.created_ts_ms)
+                        .is_none()
+            })
+            .collect()
+    }
+
+    /// Give up on events written at or before `settled_ms` that were not
+    /// relayed yet.
+    fn settle(&mut self, settled_ms: i64) {
+        self.settled_ms = self.settled_ms.max(settled_ms);
+        let settled_ms = self.settled_ms;
+        self.seen
+            .retain(|_, created_ts_ms| *created_ts_ms > settled_ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(ids: &[(&str, i64)]) -> Vec<Event> {
+        ids.iter()
+            .map(|&(id, created_ts_ms)| Event {
+                id: id.to_string(),
+                created_ts_ms,
+                prediction: None,
+            })
+            .collect()
+    }
+
+    fn ids(events: &[Event]) -> Vec<&str> {
+        events.iter().map(|event| event.id.as_str()).collect()
+    }
+
+    #[test]
+    fn relays_each_event_once() {
+        let mut relay = Relay::after(100);
+        let first = relay.unseen(events(&[("a", 101), ("b", 102)]));
+        assert_eq!(ids(&first), ["a", "b"]);
+
+        // Read again by a rescan, with one written late behind them
+        let again = relay.unseen(events(&[("a", 101), ("c", 101), ("b", 102)]));
+        assert_eq!(ids(&again), ["c"]);
+    }
+
+    #[test]
+    fn gives_up_on_settled_events() {
+        let mut relay = Relay::after(100);
+        relay.unseen(events(&[("a", 101), ("b", 105)]));
+        relay.settle(103);
+        assert_eq!(relay.settled_ms, 103);
+        assert_eq!(relay.seen.len(), 1);
+
+        let late = relay.unseen(events(&[("c", 102), ("d", 104), ("b", 105)]));
+        assert_eq!(ids(&late), ["d"]);
+
+        // Never moves back
+        relay.settle(90);
+        assert_eq!(relay.settled_ms, 103);
+    }
+}
@@ -0,0 +1,254 @@
+//! `Idempotency-Key` support for prediction writes.
+//!
+//! Model jobs retry writes on network errors, when they cannot tell whether
+//! the first attempt was stored. A write carrying an `Idempotency-Key` is
+//! processed once; the response is stored under the key, and retries with
+//! the same key and request get that response back instead of a 409. Keys
+//! are forgotten after `IDEMPOTENCY_KEY_TTL_MS`, and a key whose request
+//! never completed, because the replica handling it died, after
+//! `CLAIM_LEASE`.
+
+use std::time::Duration;
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{header, request::Parts, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::db;
+use crate::error::ApiError;
+use crate::routes::ingest::MAX_BULK_BODY_BYTES;
+use crate::routes::webhooks::new_id;
+use crate::state::AppState;
+use crate::timestamp;
+
+/// Request header carrying the key.
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Response header marking a replayed response.
+const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// Longest key accepted.
+const MAX_KEY_LEN: usize = 255;
+
+/// How long a request may hold a key without completing it. A retry after
+/// that claims the key again, so a crash doesn't block it for the whole TTL;
+/// writes must finish well within it.
+const CLAIM_LEASE: Duration = Duration::from_secs(60);
+
+/// What is stored under a key.
+#[derive(Debug)]
+pub struct IdempotencyRecord {
+    /// Hash of the request that claimed the key
+    pub request_hash: String,
+    /// Status of the response; `None` while the request is in progress
+    pub status: Option<i32>,
+    pub content_type: Option<String>,
+    pub location: Option<String>,
+    pub body: Option<Vec<u8>>,
+    /// Random token of the request holding the key, identifying its claim
+    pub claim: Option<String>,
+}
+
+/// Process a write at most once per `Idempotency-Key`; requests without the
+/// header pass through.
+///
+/// Server errors are not stored, so a retry after one runs the request
+/// again. A key reused for a different request is a 400, and a retry while
+/// the first request is still running a 409, for up to `CLAIM_LEASE`.
+pub async fn idempotent(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(key) = request.headers().get(&IDEMPOTENCY_KEY) else {
+        return next.run(request).await;
+    };
+    let key = match validate_key(key) {
+        Ok(key) => key.to_string(),
+        Err(e) => return e.into_response(),
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_BULK_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    let hash = request_hash(&parts, &body);
+
+    let claim = new_id();
+    let now_ms = timestamp::now_ms();
+    let ttl_ms = i64::try_from(state.config.idempotency_key_ttl_ms).unwrap_or(i64::MAX);
+    let lease_ms = CLAIM_LEASE.as_millis() as i64;
+    let claimed = db::claim_idempotency_key(
+        &state.pool,
+        &key,
+        &claim,
+        &hash,
+        now_ms,
+        now_ms.saturating_sub(ttl_ms),
+        now_ms.saturating_sub(lease_ms),
+    )
+    .await;
+    match claimed {
+        Ok(None) => {}
+        Ok(Some(record)) => {
+            return replay(record, &hash).unwrap_or_else(IntoResponse::into_response)
+        }
+        Err(e) => return e.into_response(),
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    store(&state.pool, &key, &claim, response).await
+}
+
+/// Check a key: 1 to 255 visible ASCII characters.
+fn validate_key(key: &HeaderValue) -> Result<&str, ApiError> {
+    key.to_str()
+        .ok()
+        .filter(|key| {
+            !key.is_empty() && key.len() <= MAX_KEY_LEN && key.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "Idempotency-Key must be 1 to {MAX_KEY_LEN} visible ASCII characters"
+            ))
+        })
+}
+
+/// Hex SHA-256 of the request's method, path, query and body.
+fn request_hash(parts: &Parts, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(parts.method.as_str());
+    hasher.update(b" ");
+    hasher.update(
+        parts
+            .uri
+            .path_and_query()
+            .map_or(parts.uri.path(), |pq| pq.as_str()),
+    );
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+/// The stored response for a key claimed by an earlier request.
+fn replay(record: IdempotencyRecord, hash: &str) -> Result<Response, ApiError> {
+    if record.request_hash != hash {
+        return Err(ApiError::BadRequest(
+            "Idempotency-Key was already used for a different request".to_string(),
+        ));
+    }
+    let Some(status) = record.status else {
+        return Err(ApiError::Conflict(
+            "a request with this Idempotency-Key is in progress".to_string(),
+        ));
+    };
+
+    let status = u16::try_from(status)
+        .ok()
+        .and_then(|s| StatusCode::from_u16(s).ok())
+        .ok_or(ApiError::Internal)?;
+    let mut response = (status, record.body.unwrap_or_default()).into_response();
+    let headers = response.headers_mut();
+    for (name, value) in [
+        (header::CONTENT_TYPE, record.content_type),
+        (header::LOCATION, record.location),
+    ] {
+        if let Some(value) = value.and_then(|v| HeaderValue::try_from(v).ok()) {
+            headers.insert(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    Ok(response)
+}
+
+/// Store `response` under `key`, held by `claim`, or release the key when
+/// it is a server error or cannot be stored.
+async fn store(pool: &PgPool, key: &str, claim: &str, response: Response) -> Response {
+    if response.status().is_server_error() {
+        release(pool, key, claim).await;
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body: Bytes = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to read response body");
+            release(pool, key, claim).await;
+            return ApiError::Internal.into_response();
+        }
+    };
+
+    let header = |name: HeaderName| parts.headers.get(name).and_then(|v| v.to_str().ok());
+    match db::complete_idempotency_key(
+        pool,
+        key,
+        claim,
+        parts.status.as_u16().into(),
+        header(header::CONTENT_TYPE),
+        header(header::LOCATION),
+        &body,
+    )
+    .await
+    {
+        Ok(true) => {}
+        Ok(false) => tracing::warn!(
+            key,
+            "Idempotency key claim outlived its lease; response not stored"
+        ),
+        Err(e) => {
+            tracing::warn!(key, error = %e, "Failed to store idempotent response");
+            release(pool, key, claim).await;
+        }
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+async fn release(pool: &PgPool, key: &str, claim: &str) {
+    if let Err(e) = db::release_idempotency_key(pool, key, claim).await {
+        tracing::warn!(key, error = %e, "Failed to release idempotency key");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_keys() {
+        let valid = HeaderValue::from_static("job-42:BTCUSDT");
+        assert_eq!(validate_key(&valid).unwrap(), "job-42:BTCUSDT");
+        assert!(validate_key(&HeaderValue::from_static("")).is_err());
+        assert!(validate_key(&HeaderValue::from_static("two words")).is_err());
+        let long = HeaderValue::try_from("k".repeat(MAX_KEY_LEN + 1)).unwrap();
+        assert!(validate_key(&long).is_err());
+    }
+
+    #[test]
+    fn replays_only_matching_completed_requests() {
+        let record = |hash: &str, status| IdempotencyRecord {
+            request_hash: hash.to_string(),
+            status,
+            content_type: Some("application/json".to_string()),
+            location: None,
+            body: Some(b"{}".to_vec()),
+            claim: Some("c".to_string()),
+        };
+
+        let response = replay(record("a", Some(201)), "a").unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[&IDEMPOTENT_REPLAYED], "true");
+        assert!(matches!(
+            replay(record("a", Some(201)), "b"),
+            Err(ApiError::BadRequest(_))
+        ));
+        assert!(matches!(
+            replay(record("a", None), "a"),
+            Err(ApiError::Conflict(_))
+        ));
+    }
+}
@@ -1,40 +1,285 @@
 //! Prediction API - REST API for ML price predictions.
 //!
 //! A modern Rust API built with Axum, featuring:
-//! - OpenAPI/Swagger documentation at /docs
-//! - Rate limiting (100 req/sec per IP)
+//! - OpenAPI/Swagger documentation at /docs (`swagger` feature, on by default)
+//! - Per-route-group rate limiting per IP
+//! - Load shedding when the database pool is saturated
 //! - Structured logging with tracing
 //! - Proper error handling
 //! - Graceful shutdown
 
-use axum::{routing::get, Router};
+use axum::{
+    extract::{DefaultBodyLimit, Request},
+    middleware::{from_fn, from_fn_with_state},
+    routing::{delete, get, post, put},
+    Router, ServiceExt,
+};
 use sqlx::postgres::PgPoolOptions;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Semaphore};
+use tower::Layer;
 use tower_governor::{governor::GovernorConfigBuilder, GovernorLayer};
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    cors::CorsLayer,
+    trace::TraceLayer,
+};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use utoipa::OpenApi;
+#[cfg(feature = "swagger")]
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+#[cfg(feature = "swagger")]
+use utoipa::{Modify, OpenApi};
+#[cfg(feature = "swagger")]
 use utoipa_swagger_ui::SwaggerUi;
 
+mod aliases;
+mod cache;
 mod config;
 mod db;
+mod derivatives;
+mod envelope;
 mod error;
+mod exchange;
+mod experiments;
+mod feed;
+mod idempotency;
+mod middleware;
+mod orderbook;
+mod pagination;
+mod projection;
+mod protobuf;
+mod purge;
+mod query;
 mod routes;
+mod state;
+mod timestamp;
+mod webhooks;
 
-use routes::health::HealthResponse;
-use routes::predictions::{Prediction, PredictionQuery};
+use aliases::Aliases;
+use config::{Compression, RateLimit};
+#[cfg(feature = "swagger")]
+use envelope::{Envelope, EnvelopeQuery};
+#[cfg(feature = "swagger")]
+use error::{ErrorCode, FieldError, Problem, PROBLEM_JSON};
+use exchange::Exchange;
+#[cfg(feature = "swagger")]
+use experiments::Variant;
+use feed::Feed;
+#[cfg(feature = "swagger")]
+use projection::ProfileQuery;
+#[cfg(feature = "swagger")]
+use routes::aggregates::{DownsampleQuery, PriceBucket, PriceStats, StatsQuery};
+#[cfg(feature = "swagger")]
+use routes::candles::{Candle, CandlesQuery};
+#[cfg(feature = "swagger")]
+use routes::derivatives::{DerivativesQuery, FundingRate, OpenInterest};
+#[cfg(feature = "swagger")]
+use routes::export::ExportQuery;
+#[cfg(feature = "swagger")]
+use routes::health::{HealthResponse, ReadyResponse};
+#[cfg(feature = "swagger")]
+use routes::history::{HistoryBatchRequest, HistoryQuery, RecentQuery};
+#[cfg(feature = "swagger")]
+use routes::index::IndexResponse;
+#[cfg(feature = "swagger")]
+use routes::ingest::{BulkRequest, BulkResponse, NewPrediction, OnConflict, RowResult, RowStatus};
+#[cfg(feature = "swagger")]
+use routes::metrics::{
+    AccuracyMetrics, AccuracyPoint, ErrorHistogram, EvaluatedPrediction, EvaluatedQuery,
+    HistogramBin, HistogramQuery, HitRate, HitRateQuery, IntervalCoverage, RollingAccuracyQuery,
+};
+#[cfg(feature = "swagger")]
+use routes::models::ModelSummary;
+#[cfg(feature = "swagger")]
+use routes::orderbook::{OrderBook, OrderBookQuery};
+#[cfg(feature = "swagger")]
+use routes::pairs::{AliasRequest, PairAlias, PairAliases, PairSummary};
+#[cfg(feature = "swagger")]
+use routes::predictions::{
+    CompareQuery, Direction, Fallback, LatestBatchRequest, LatestQuery, Prediction,
+    PredictionQuery, SortBy, SortOrder,
+};
+#[cfg(feature = "swagger")]
+use routes::prices::{PriceGap, PricePoint, PricesQuery};
+#[cfg(feature = "swagger")]
+use routes::ratelimit::{RateLimitGroupStatus, RateLimitResponse};
+#[cfg(feature = "swagger")]
+use routes::registry::{ModelRequest, ModelStatus, RegisteredModel, RegistryQuery};
+#[cfg(feature = "swagger")]
+use routes::rejected::{RejectedPrediction, RejectedQuery};
+#[cfg(feature = "swagger")]
+use routes::status::{DatabaseStatus, PoolStats, StatusResponse, SubsystemStatus};
+#[cfg(feature = "swagger")]
+use routes::stream::StreamQuery;
+#[cfg(feature = "swagger")]
+use routes::webhooks::{CreatedWebhook, DeadLetter, DeadLetterQuery, Webhook, WebhookRequest};
+use state::{AppState, RateLimitGroups};
+#[cfg(feature = "swagger")]
+use timestamp::TimestampQuery;
+use webhooks::{Delivery, Dispatcher};
 
+/// Mount point of the Swagger UI.
+const DOCS_PATH: &str = "/docs";
+
+/// Prefix of the versioned API. The same routes stay served unprefixed as
+/// deprecated aliases.
+const API_PREFIX: &str = "/v1";
+
+#[cfg(feature = "swagger")]
 #[derive(OpenApi)]
 #[openapi(
     paths(
         routes::health::health,
+        routes::health::ready,
+        routes::index::index,
         routes::predictions::get_prediction,
         routes::predictions::get_all_latest,
+        routes::predictions::get_latest_batch,
+        routes::predictions::compare_models,
+        routes::ingest::create_prediction,
+        routes::ingest::create_predictions,
+        routes::ingest::get_prediction_schema,
+        routes::ingest::get_prediction_proto,
+        routes::models::get_model_prediction,
+        routes::models::get_model_predictions,
+        routes::pairs::list_pairs,
+        routes::pairs::get_aliases,
+        routes::models::list_models,
+        routes::history::get_history,
+        routes::history::get_recent,
+        routes::stream::stream_predictions,
+        routes::history::get_history_batch,
+        routes::export::export_csv,
+        routes::export::export_ndjson,
+        routes::aggregates::get_downsample,
+        routes::aggregates::get_stats,
+        routes::prices::get_prices,
+        routes::prices::get_price_gaps,
+        routes::orderbook::get_orderbook,
+        routes::derivatives::get_funding_rates,
+        routes::derivatives::get_open_interest,
+        routes::candles::get_candles,
+        routes::metrics::get_accuracy,
+        routes::metrics::get_rolling_accuracy,
+        routes::metrics::get_hit_rates,
+        routes::metrics::get_error_histogram,
+        routes::metrics::get_evaluated,
+        routes::status::status,
+        routes::ratelimit::get_rate_limits,
+        routes::webhooks::list_webhooks,
+        routes::webhooks::create_webhook,
+        routes::webhooks::get_webhook,
+        routes::webhooks::update_webhook,
+        routes::webhooks::delete_webhook,
+        routes::pairs::list_aliases,
+        routes::pairs::put_alias,
+        routes::pairs::delete_alias,
+        routes::rejected::list_rejected,
+        routes::rejected::replay_rejected,
+        routes::rejected::delete_rejected,
+        routes::webhooks::get_dead_letters,
+        routes::registry::list_registered_models,
+        routes::registry::register_model,
+        routes::registry::get_registered_model,
+        routes::registry::update_registered_model,
+        routes::registry::delete_registered_model,
+        routes::registry::promote_model,
+        routes::registry::rollback_model,
     ),
-    components(schemas(HealthResponse, Prediction, PredictionQuery)),
+    components(schemas(
+        HealthResponse,
+        ReadyResponse,
+        Problem,
+        ErrorCode,
+        FieldError,
+        Prediction,
+        Direction,
+        Variant,
+        PredictionQuery,
+        NewPrediction,
+        BulkRequest,
+        BulkResponse,
+        RowResult,
+        RowStatus,
+        OnConflict,
+        RejectedPrediction,
+        RejectedQuery,
+        ProfileQuery,
+        EnvelopeQuery,
+        Envelope,
+        TimestampQuery,
+        LatestQuery,
+        LatestBatchRequest,
+        CompareQuery,
+        SortBy,
+        SortOrder,
+        Fallback,
+        HistoryQuery,
+        RecentQuery,
+        StreamQuery,
+        HistoryBatchRequest,
+        ExportQuery,
+        DownsampleQuery,
+        PriceBucket,
+        StatsQuery,
+        PriceStats,
+        PricesQuery,
+        PricePoint,
+        PriceGap,
+        OrderBookQuery,
+        OrderBook,
+        DerivativesQuery,
+        FundingRate,
+        OpenInterest,
+        CandlesQuery,
+        Candle,
+        AccuracyMetrics,
+        RollingAccuracyQuery,
+        AccuracyPoint,
+        HitRateQuery,
+        HitRate,
+        HistogramQuery,
+        HistogramBin,
+        IntervalCoverage,
+        ErrorHistogram,
+        EvaluatedQuery,
+        EvaluatedPrediction,
+        PairSummary,
+        PairAliases,
+        PairAlias,
+        AliasRequest,
+        ModelSummary,
+        IndexResponse,
+        StatusResponse,
+        DatabaseStatus,
+        PoolStats,
+        SubsystemStatus,
+        RateLimitResponse,
+        RateLimitGroupStatus,
+        Webhook,
+        WebhookRequest,
+        CreatedWebhook,
+        DeadLetter,
+        DeadLetterQuery,
+        RegisteredModel,
+        ModelStatus,
+        ModelRequest,
+        RegistryQuery
+    )),
+    modifiers(&BearerSecurity, &ProblemResponses),
+    servers((url = "/v1", description = "Current API version")),
     tags(
         (name = "health", description = "Health check endpoints"),
-        (name = "predictions", description = "ML Price Predictions API")
+        (name = "predictions", description = "ML Price Predictions API"),
+        (name = "prices", description = "Realized market prices, candles, order books, funding rates and open interest"),
+        (name = "metrics", description = "Prediction accuracy against realized prices"),
+        (name = "ingestion", description = "Prediction writes from model services (ingest API key required)"),
+        (name = "admin", description = "Operator endpoints (admin API key required)")
     ),
     info(
         title = "Prediction API",
@@ -44,6 +289,58 @@ use routes::predictions::{Prediction, PredictionQuery};
 )]
 struct ApiDoc;
 
+/// Registers the bearer schemes used by admin and ingestion endpoints.
+#[cfg(feature = "swagger")]
+struct BearerSecurity;
+
+#[cfg(feature = "swagger")]
+impl Modify for BearerSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            for scheme in ["admin_api_key", "ingest_api_key"] {
+                components.add_security_scheme(
+                    scheme,
+                    SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+                );
+            }
+        }
+    }
+}
+
+/// Documents the problem details body on every error response.
+#[cfg(feature = "swagger")]
+struct ProblemResponses;
+
+#[cfg(feature = "swagger")]
+impl Modify for ProblemResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        use utoipa::openapi::{Content, Ref, RefOr};
+
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.post,
+                &mut item.put,
+                &mut item.delete,
+            ];
+            for operation in operations.into_iter().flatten() {
+                for (status, response) in operation.responses.responses.iter_mut() {
+                    let RefOr::T(response) = response else {
+                        continue;
+                    };
+                    let is_error = status.parse::<u16>().is_ok_and(|s| s >= 400);
+                    if is_error && response.content.is_empty() {
+                        response.content.insert(
+                            PROBLEM_JSON.to_string(),
+                            Content::new(Some(Ref::from_schema_name("Problem"))),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load .env file if present
@@ -60,58 +357,437 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Load configuration
     let config = config::Config::from_env()?;
-    tracing::info!("Configuration loaded");
+    timestamp::init(config.timestamp_format);
+    db::init(config.non_finite_price);
 
     // Create database connection pool
     let pool = PgPoolOptions::new()
-        .max_connections(10)
+        .max_connections(config.pg_max_connections)
         .connect(&config.database_url())
         .await?;
 
     tracing::info!("Connected to database at {}:{}", config.pg_host, config.pg_port);
 
-    // Rate limiting: 100 requests per second, burst of 50
-    let governor_conf = Arc::new(
-        GovernorConfigBuilder::default()
-            .per_second(100)
-            .burst_size(50)
-            .finish()
-            .expect("Failed to create rate limiter config"),
+    // Start degraded rather than crash-looping if the schema is unusable
+    let degraded = match db::probe_schema(&pool).await {
+        Ok(missing) if missing.is_empty() => None,
+        Ok(missing) => Some(format!("schema is missing columns: {}", missing.join(", "))),
+        Err(e) => Some(format!("schema probe failed: {}", e)),
+    };
+    if let Some(reason) = &degraded {
+        tracing::error!(%reason, "Starting in degraded mode");
+    }
+
+    // Exercise the read queries so grant or schema problems show up now
+    match db::self_test(&pool).await {
+        Ok(pairs) => tracing::info!(pairs, "Startup self-test passed"),
+        Err(e) if config.startup_selftest_strict => {
+            tracing::error!(error = %e, "Startup self-test failed");
+            return Err(e.into());
+        }
+        Err(e) => tracing::warn!(error = %e, "Startup self-test failed, continuing"),
+    }
+
+    // Flipped on shutdown so long-lived streams end
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let feed = Feed::start(
+        pool.clone(),
+        Duration::from_millis(config.feed_poll_interval_ms),
+        shutdown_rx.clone(),
+    );
+    purge::start(
+        pool.clone(),
+        purge::PREDICTION_EVENTS,
+        Duration::from_millis(config.prediction_event_retention_ms),
+        shutdown_rx.clone(),
+    );
+    purge::start(
+        pool.clone(),
+        purge::IDEMPOTENCY_KEYS,
+        Duration::from_millis(config.idempotency_key_ttl_ms),
+        shutdown_rx.clone(),
+    );
+    purge::start(
+        pool.clone(),
+        purge::REJECTED_PREDICTIONS,
+        Duration::from_millis(config.rejected_prediction_retention_ms),
+        shutdown_rx.clone(),
+    );
+    purge::start(
+        pool.clone(),
+        purge::WEBHOOK_DEAD_LETTERS,
+        Duration::from_millis(config.dead_letter_retention_ms),
+        shutdown_rx.clone(),
+    );
+    let aliases = Aliases::start(pool.clone(), shutdown_rx.clone());
+    if !config.orderbook_pairs.is_empty() {
+        orderbook::start(
+            pool.clone(),
+            Exchange::new(&config.exchange_base_url),
+            config.orderbook_pairs.clone(),
+            config.orderbook_depth,
+            Duration::from_millis(config.orderbook_interval_ms),
+            shutdown_rx.clone(),
+        );
+    }
+    if !config.derivatives_pairs.is_empty() {
+        derivatives::start(
+            pool.clone(),
+            Exchange::new(&config.exchange_base_url),
+            config.derivatives_pairs.clone(),
+            Duration::from_millis(config.derivatives_interval_ms),
+            shutdown_rx.clone(),
+        );
+    }
+
+    let state = AppState {
+        pool: pool.clone(),
+        config: Arc::new(config.clone()),
+        started_at: Instant::now(),
+        degraded: degraded.map(Into::into),
+        rate_limits: RateLimitGroups::default(),
+        webhooks: Dispatcher::start(
+            pool.clone(),
+            Duration::from_millis(config.feed_poll_interval_ms),
+            Delivery {
+                timeout: Duration::from_millis(config.webhook_timeout_ms),
+                max_attempts: config.webhook_max_attempts,
+                retry_base: Duration::from_millis(config.webhook_retry_base_ms),
+                concurrency: config.webhook_concurrency,
+                queue_size: config.webhook_queue_size,
+            },
+            config.webhook_dispatcher_enabled,
+            shutdown_rx.clone(),
+        ),
+        feed,
+        aliases,
+        exports: Arc::new(Semaphore::new(config.export_concurrency)),
+    };
+
+    // Shed prediction requests when the pool is saturated; /health stays served
+    let load_shedder = middleware::LoadShedder::new(
+        pool.clone(),
+        config.load_shed_threshold,
+        config.load_shed_retry_after_secs,
     );
 
-    // Build router with all layers
-    let app = Router::new()
-        // API routes
-        .route("/health", get(routes::health::health))
+    let ingest_queue = middleware::IngestQueue::new(
+        config.ingest_concurrency,
+        config.ingest_queue_size,
+        Duration::from_millis(config.ingest_queue_timeout_ms),
+        config.load_shed_retry_after_secs,
+    );
+
+    // Cheap single-row reads
+    let read_routes = Router::new()
+        .route("/", get(routes::index::index))
         .route("/predictions", get(routes::predictions::get_prediction))
         .route(
             "/predictions/latest",
             get(routes::predictions::get_all_latest),
         )
-        // Swagger UI
-        .merge(SwaggerUi::new("/docs").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .route("/predictions/recent", get(routes::history::get_recent))
+        .route(
+            "/predictions/stream",
+            get(routes::stream::stream_predictions),
+        )
+        .route(
+            "/predictions/batch",
+            post(routes::predictions::get_latest_batch),
+        )
+        .route(
+            "/predictions/compare",
+            get(routes::predictions::compare_models),
+        )
+        .route(
+            "/models/{model_name}/predictions",
+            get(routes::models::get_model_predictions),
+        )
+        .route(
+            "/models/{model_name}/predictions/{pair}",
+            get(routes::models::get_model_prediction),
+        )
+        .route("/pairs/{pair}/aliases", get(routes::pairs::get_aliases))
+        .route("/orderbook", get(routes::orderbook::get_orderbook))
+        .route(
+            "/funding-rates",
+            get(routes::derivatives::get_funding_rates),
+        )
+        .route(
+            "/open-interest",
+            get(routes::derivatives::get_open_interest),
+        )
+        .route(
+            "/predictions/schema",
+            get(routes::ingest::get_prediction_schema),
+        )
+        .route(
+            "/predictions/schema.proto",
+            get(routes::ingest::get_prediction_proto),
+        )
+        .route_layer(from_fn(middleware::envelope))
+        .route_layer(from_fn(middleware::timestamp_format))
+        .route_layer(from_fn_with_state(
+            load_shedder.clone(),
+            middleware::load_shed,
+        ))
+        .route_layer(from_fn_with_state(
+            state.clone(),
+            middleware::reject_if_degraded,
+        ));
+
+    // Heavy scans over many rows
+    let heavy_routes = Router::new()
+        .route("/pairs", get(routes::pairs::list_pairs))
+        .route("/models", get(routes::models::list_models))
+        .route("/predictions/history", get(routes::history::get_history))
+        .route(
+            "/predictions/downsample",
+            get(routes::aggregates::get_downsample),
+        )
+        .route("/predictions/stats", get(routes::aggregates::get_stats))
+        .route("/prices", get(routes::prices::get_prices))
+        .route("/prices/gaps", get(routes::prices::get_price_gaps))
+        .route("/candles", get(routes::candles::get_candles))
+        .route("/metrics/accuracy", get(routes::metrics::get_accuracy))
+        .route(
+            "/metrics/accuracy/rolling",
+            get(routes::metrics::get_rolling_accuracy),
+        )
+        .route("/metrics/hit-rate", get(routes::metrics::get_hit_rates))
+        .route(
+            "/metrics/errors/histogram",
+            get(routes::metrics::get_error_histogram),
+        )
+        .route(
+            "/predictions/evaluated",
+            get(routes::metrics::get_evaluated),
+        )
+        .route(
+            "/predictions/history/batch",
+            post(routes::history::get_history_batch),
+        )
+        .route("/predictions/export.csv", get(routes::export::export_csv))
+        .route(
+            "/predictions/export.ndjson",
+            get(routes::export::export_ndjson),
+        )
+        .route_layer(from_fn(middleware::envelope))
+        .route_layer(from_fn(middleware::timestamp_format))
+        .route_layer(from_fn_with_state(load_shedder, middleware::load_shed))
+        .route_layer(from_fn_with_state(
+            state.clone(),
+            middleware::reject_if_degraded,
+        ));
+
+    // Operator endpoints behind the admin API key
+    let admin_routes = Router::new()
+        .route("/status", get(routes::status::status))
+        .route("/admin/ratelimit", get(routes::ratelimit::get_rate_limits))
+        .route(
+            "/admin/webhooks",
+            get(routes::webhooks::list_webhooks).post(routes::webhooks::create_webhook),
+        )
+        .route(
+            "/admin/webhooks/{id}",
+            get(routes::webhooks::get_webhook)
+                .put(routes::webhooks::update_webhook)
+                .delete(routes::webhooks::delete_webhook),
+        )
+        .route(
+            "/admin/webhooks/{id}/dead-letters",
+            get(routes::webhooks::get_dead_letters),
+        )
+        .route(
+            "/admin/rejected-predictions",
+            get(routes::rejected::list_rejected),
+        )
+        .route(
+            "/admin/rejected-predictions/{id}",
+            delete(routes::rejected::delete_rejected),
+        )
+        .route(
+            "/admin/rejected-predictions/{id}/replay",
+            post(routes::rejected::replay_rejected),
+        )
+        .route("/admin/pair-aliases", get(routes::pairs::list_aliases))
+        .route(
+            "/admin/pair-aliases/{alias}",
+            put(routes::pairs::put_alias).delete(routes::pairs::delete_alias),
+        )
+        .route(
+            "/admin/models",
+            get(routes::registry::list_registered_models).post(routes::registry::register_model),
+        )
+        .route(
+            "/admin/models/{id}",
+            get(routes::registry::get_registered_model)
+                .put(routes::registry::update_registered_model)
+                .delete(routes::registry::delete_registered_model),
+        )
+        .route(
+            "/admin/models/{id}/promote",
+            post(routes::registry::promote_model),
+        )
+        .route(
+            "/admin/models/{id}/rollback",
+            post(routes::registry::rollback_model),
+        )
+        .route_layer(from_fn_with_state(state.clone(), middleware::require_admin));
+
+    // Prediction writes from model services, behind the ingest API key
+    let write_routes = Router::new()
+        .route("/predictions", post(routes::ingest::create_prediction))
+        .route(
+            "/predictions/bulk",
+            post(routes::ingest::create_predictions)
+                .layer(DefaultBodyLimit::max(routes::ingest::MAX_BULK_BODY_BYTES)),
+        )
+        .route_layer(from_fn_with_state(state.clone(), idempotency::idempotent))
+        .route_layer(from_fn_with_state(ingest_queue, middleware::ingest_queue))
+        .route_layer(from_fn_with_state(
+            state.clone(),
+            middleware::require_ingest,
+        ))
+        .route_layer(from_fn_with_state(
+            state.clone(),
+            middleware::reject_if_degraded,
+        ));
+
+    // Probes are never rate limited
+    let probes = Router::new()
+        .route("/health", get(routes::health::health))
+        .route("/ready", get(routes::health::ready));
+
+    // API routes, each group with its own rate limit
+    let routes = Router::new()
+        .merge(rate_limited(
+            read_routes,
+            config.rate_limit_enabled,
+            &config.rate_limit_read,
+            &state.rate_limits.read,
+        ))
+        .merge(rate_limited(
+            heavy_routes,
+            config.rate_limit_enabled,
+            &config.rate_limit_heavy,
+            &state.rate_limits.heavy,
+        ))
+        .merge(rate_limited(
+            write_routes,
+            config.rate_limit_enabled,
+            &config.rate_limit_write,
+            &state.rate_limits.write,
+        ))
+        .merge(rate_limited(
+            admin_routes,
+            config.rate_limit_enabled,
+            &config.rate_limit_admin,
+            &state.rate_limits.admin,
+        ));
+
+    // Build router with all layers. Unprefixed probes are not deprecated,
+    // since orchestrators are configured with them.
+    let api = Router::new()
+        .nest(API_PREFIX, probes.clone().merge(routes.clone()))
+        .merge(probes)
+        .merge(routes.layer(from_fn_with_state(
+            state.clone(),
+            middleware::deprecated_alias,
+        )));
+
+    // Swagger UI, unless compiled out with --no-default-features
+    #[cfg(feature = "swagger")]
+    let api = api.merge(SwaggerUi::new(DOCS_PATH).url("/api-docs/openapi.json", ApiDoc::openapi()));
+
+    // Compress larger responses with whichever enabled algorithm the client
+    // accepts; streamed exports have no known size and are always compressed
+    let compression = CompressionLayer::new()
+        .gzip(config.compression.contains(&Compression::Gzip))
+        .deflate(config.compression.contains(&Compression::Deflate))
+        .compress_when(
+            SizeAbove::new(config.compression_min_size)
+                .and(NotForContentType::GRPC)
+                .and(NotForContentType::IMAGES)
+                .and(NotForContentType::SSE),
+        );
+
+    let app = api
         // Middleware layers
-        .layer(GovernorLayer::new(governor_conf))
+        .layer(compression)
+        .layer(from_fn_with_state(state.clone(), middleware::cache_control))
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
         // Shared state
-        .with_state(pool);
+        .with_state(state);
+
+    // Treat `/predictions/` like `/predictions`; this has to run before routing
+    let app = from_fn(middleware::trim_trailing_slash).layer(app);
 
     // Start server
-    let addr = format!("0.0.0.0:{}", config.api_port);
-    tracing::info!("Starting server on {}", addr);
-    tracing::info!("Swagger UI available at http://{}/docs", addr);
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", config.api_port)).await?;
+    let addr = listener.local_addr()?;
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    tracing::info!(
+        version = env!("CARGO_PKG_VERSION"),
+        %addr,
+        config = ?config,
+        rate_limit = config.rate_limit_enabled,
+        auth = !config.admin_api_key.is_empty(),
+        ingest = !config.ingest_api_key.is_empty(),
+        "startup"
+    );
+    #[cfg(feature = "swagger")]
+    tracing::info!("Swagger UI available at http://{}{}", addr, DOCS_PATH);
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // Rate limiting keys on the peer IP, which requires connect info
+    axum::serve(
+        listener,
+        ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        // End open streams, which would otherwise hold the shutdown up
+        let _ = shutdown_tx.send(true);
+    })
+    .await?;
 
     tracing::info!("Server stopped");
     Ok(())
 }
 
+/// Apply a per-IP rate limit to every route in a group, recording its
+/// rejections in `stats`.
+fn rate_limited<S>(
+    router: Router<S>,
+    enabled: bool,
+    limit: &RateLimit,
+    stats: &Arc<middleware::RateLimitStats>,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    if !enabled {
+        return router;
+    }
+
+    let governor_conf = GovernorConfigBuilder::default()
+        .period(limit.period())
+        .burst_size(limit.burst)
+        .finish()
+        .expect("Failed to create rate limiter config");
+
+    let limiter = governor_conf.limiter().clone();
+    stats.track_keys(move || limiter.len());
+
+    router
+        .route_layer(GovernorLayer::new(governor_conf))
+        .route_layer(from_fn_with_state(
+            stats.clone(),
+            middleware::count_rate_limited,
+        ))
+}
+
 /// Handle graceful shutdown on SIGINT (Ctrl+C).
 async fn shutdown_signal() {
     tokio::signal::ctrl_c()
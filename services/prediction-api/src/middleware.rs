@@ -0,0 +1,428 @@
+//! HTTP middleware for the prediction API.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{MatchedPath, Query, Request, State},
+    http::{header, uri::PathAndQuery, HeaderName, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sqlx::PgPool;
+use tokio::sync::Semaphore;
+
+use crate::envelope::{self, EnvelopeQuery};
+use crate::error::ApiError;
+use crate::state::AppState;
+use crate::timestamp::{self, TimestampQuery};
+use crate::{API_PREFIX, DOCS_PATH};
+
+/// Load-shedding state: the pool to watch and when to start rejecting.
+#[derive(Clone)]
+pub struct LoadShedder {
+    pool: PgPool,
+    threshold: f64,
+    retry_after_secs: u64,
+}
+
+impl LoadShedder {
+    pub fn new(pool: PgPool, threshold: f64, retry_after_secs: u64) -> Self {
+        Self {
+            pool,
+            threshold,
+            retry_after_secs,
+        }
+    }
+}
+
+/// Fraction of the pool's maximum connections currently checked out.
+pub fn pool_saturation(pool: &PgPool) -> f64 {
+    let max = pool.options().get_max_connections();
+    if max == 0 {
+        return 1.0;
+    }
+    let in_use = (pool.size() as usize).saturating_sub(pool.num_idle());
+    in_use as f64 / max as f64
+}
+
+/// Reject requests with 503 while the database pool is saturated.
+///
+/// Queuing more work behind a busy pool only deepens the queue, so requests
+/// are turned away immediately with a `Retry-After` hint instead.
+pub async fn load_shed(
+    State(shedder): State<LoadShedder>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let saturation = pool_saturation(&shedder.pool);
+    if saturation >= shedder.threshold {
+        tracing::warn!(
+            saturation,
+            threshold = shedder.threshold,
+            path = %request.uri().path(),
+            "Pool saturated, shedding request"
+        );
+        return ApiError::Overloaded(shedder.retry_after_secs).into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Bounded queue in front of prediction writes: at most `concurrency`
+/// writes run at once and at most `capacity` wait for a turn.
+#[derive(Clone)]
+pub struct IngestQueue {
+    permits: Arc<Semaphore>,
+    waiting: Arc<AtomicUsize>,
+    capacity: usize,
+    timeout: Duration,
+    retry_after_secs: u64,
+}
+
+impl IngestQueue {
+    pub fn new(
+        concurrency: usize,
+        capacity: usize,
+        timeout: Duration,
+        retry_after_secs: u64,
+    ) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(concurrency)),
+            waiting: Arc::new(AtomicUsize::new(0)),
+            capacity,
+            timeout,
+            retry_after_secs,
+        }
+    }
+
+    /// Take a place in the queue, unless it is full.
+    fn enter(&self) -> Option<Waiting<'_>> {
+        self.waiting
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |waiting| {
+                (waiting < self.capacity).then_some(waiting + 1)
+            })
+            .ok()
+            .map(|_| Waiting(&self.waiting))
+    }
+}
+
+/// A place in the ingest queue, given up when dropped.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Run prediction writes through the ingest queue, rejecting them with 503
+/// when the queue is full or a write waits too long for its turn.
+///
+/// Without it, a burst of writes the database cannot keep up with would
+/// each hold a connection or wait for one, starving reads of the pool.
+pub async fn ingest_queue(
+    State(queue): State<IngestQueue>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let permit = match queue.permits.clone().try_acquire_owned() {
+        Ok(permit) => permit,
+        Err(_) => {
+            let Some(_waiting) = queue.enter() else {
+                tracing::warn!(
+                    capacity = queue.capacity,
+                    path = %request.uri().path(),
+                    "Ingest queue full, rejecting write"
+                );
+                return ApiError::Overloaded(queue.retry_after_secs).into_response();
+            };
+            match tokio::time::timeout(queue.timeout, queue.permits.clone().acquire_owned()).await {
+                Ok(Ok(permit)) => permit,
+                Ok(Err(_)) => return ApiError::Internal.into_response(),
+                Err(_) => {
+                    tracing::warn!(
+                        path = %request.uri().path(),
+                        "Write waited too long in the ingest queue, rejecting"
+                    );
+                    return ApiError::Overloaded(queue.retry_after_secs).into_response();
+                }
+            }
+        }
+    };
+
+    let response = next.run(request).await;
+    drop(permit);
+    response
+}
+
+/// What one route group's rate limiter has been doing, for `/admin/ratelimit`.
+#[derive(Default)]
+pub struct RateLimitStats {
+    rejected: AtomicU64,
+    tracked_keys: OnceLock<Box<dyn Fn() -> usize + Send + Sync>>,
+}
+
+impl RateLimitStats {
+    /// Requests rejected with 429 since startup.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Register how to read the limiter's number of tracked client IPs.
+    pub fn track_keys(&self, len: impl Fn() -> usize + Send + Sync + 'static) {
+        let _ = self.tracked_keys.set(Box::new(len));
+    }
+
+    /// Client IPs the limiter currently holds a bucket for; `None` when
+    /// rate limiting is disabled.
+    pub fn tracked_keys(&self) -> Option<usize> {
+        self.tracked_keys.get().map(|len| len())
+    }
+}
+
+/// Count responses rejected by the rate limiter this middleware wraps, and
+/// replace the limiter's plain-text body with problem details.
+pub async fn count_rate_limited(
+    State(stats): State<Arc<RateLimitStats>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return response;
+    }
+    stats.rejected.fetch_add(1, Ordering::Relaxed);
+
+    // Keep the limiter's Retry-After and x-ratelimit-* headers
+    let mut problem = ApiError::RateLimited.into_response();
+    for (name, value) in response.headers() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            problem.headers_mut().append(name, value.clone());
+        }
+    }
+    problem
+}
+
+/// Reject requests with 503 while the service runs in degraded mode.
+pub async fn reject_if_degraded(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(reason) = &state.degraded {
+        return ApiError::Unavailable(reason.to_string()).into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Wrap successful JSON list responses in an envelope when the request asks
+/// for one with `envelope=true`.
+pub async fn envelope(request: Request, next: Next) -> Response {
+    let Ok(Query(query)) = Query::<EnvelopeQuery>::try_from_uri(request.uri()) else {
+        return ApiError::BadRequest("envelope must be true or false".to_string()).into_response();
+    };
+    if query.envelope != Some(true) {
+        return next.run(request).await;
+    }
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if response.status() != StatusCode::OK || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return ApiError::Internal.into_response();
+    };
+    let query_time_ms = started.elapsed().as_millis() as u64;
+
+    match serde_json::from_slice(&bytes)
+        .ok()
+        .and_then(|body| envelope::wrap(body, query_time_ms))
+    {
+        Some(wrapped) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(wrapped.to_string()))
+        }
+        None => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
+
+/// Apply a request's `ts_format` to the timestamps it gets back.
+pub async fn timestamp_format(request: Request, next: Next) -> Response {
+    let format = Query::<TimestampQuery>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(query)| query.ts_format)
+        .map(|format| format.parse());
+
+    match format {
+        None => next.run(request).await,
+        Some(Ok(format)) => timestamp::with_format(format, next.run(request)).await,
+        Some(Err(_)) => {
+            ApiError::BadRequest("ts_format must be one of ms, iso8601 or both".to_string())
+                .into_response()
+        }
+    }
+}
+
+/// Set `Cache-Control` on successful reads from `CACHE_MAX_AGE`, unless the
+/// handler already chose one. Errors are never marked cacheable.
+pub async fn cache_control(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Versioned and legacy routes share a policy
+    let policy = request.extensions().get::<MatchedPath>().and_then(|route| {
+        let route = match route.as_str().strip_prefix(API_PREFIX) {
+            Some("") => "/",
+            Some(route) => route,
+            None => route.as_str(),
+        };
+        state.config.cache_max_age.header(route)
+    });
+    let cacheable = request.method() == Method::GET || request.method() == Method::HEAD;
+
+    let mut response = next.run(request).await;
+    let status = response.status();
+    if let Some(policy) = policy.filter(|_| cacheable) {
+        if status.is_success() || status == StatusCode::NOT_MODIFIED {
+            response
+                .headers_mut()
+                .entry(header::CACHE_CONTROL)
+                .or_insert(policy);
+        }
+    }
+    response
+}
+
+/// When the unprefixed API routes were deprecated (2026-10-15), as an
+/// RFC 9745 `Deprecation` value.
+const LEGACY_DEPRECATED_AT: &str = "@1792022400";
+
+/// Mark a response from an unprefixed alias of a `/v1` route as deprecated,
+/// with a `Link` to the versioned route and, when `LEGACY_SUNSET_MS` is set,
+/// a `Sunset` date (RFC 8594) after which the alias may be removed.
+pub async fn deprecated_alias(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let successor = match request.uri().path() {
+        "/" => API_PREFIX.to_string(),
+        path => format!("{}{}", API_PREFIX, path),
+    };
+    let mut response = next.run(request).await;
+
+    let headers = response.headers_mut();
+    headers.insert(
+        HeaderName::from_static("deprecation"),
+        HeaderValue::from_static(LEGACY_DEPRECATED_AT),
+    );
+    if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor))
+    {
+        headers.append(header::LINK, link);
+    }
+    if let Some(sunset_ms) = state.config.legacy_sunset_ms {
+        let sunset = UNIX_EPOCH + Duration::from_millis(sunset_ms);
+        if let Ok(sunset) = HeaderValue::from_str(&httpdate::fmt_http_date(sunset)) {
+            headers.insert(HeaderName::from_static("sunset"), sunset);
+        }
+    }
+    response
+}
+
+/// Require `Authorization: Bearer <ADMIN_API_KEY>` on admin endpoints.
+///
+/// When no key is configured, admin endpoints are disabled entirely.
+pub async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match authorize(&request, &state.config.admin_api_key, "admin") {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Require `Authorization: Bearer <INGEST_API_KEY>` on ingestion endpoints.
+///
+/// When no key is configured, ingestion is disabled entirely.
+pub async fn require_ingest(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match authorize(&request, &state.config.ingest_api_key, "ingest") {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Check the request's bearer token against `key`, the `kind` API key.
+fn authorize(request: &Request, key: &str, kind: &str) -> Result<(), ApiError> {
+    let expected = key.as_bytes();
+    if expected.is_empty() {
+        return Err(ApiError::Unauthorized(format!("{kind} API is disabled")));
+    }
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default()
+        .as_bytes();
+
+    if !constant_time_eq(provided, expected) {
+        tracing::warn!(path = %request.uri().path(), kind, "Rejected unauthorized request");
+        return Err(ApiError::Unauthorized(format!(
+            "invalid or missing {kind} API key"
+        )));
+    }
+    Ok(())
+}
+
+/// Compare two byte strings without short-circuiting on the first mismatch.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Strip trailing slashes so `/predictions/` routes like `/predictions`.
+///
+/// Must wrap the whole router, since routing happens before router layers
+/// run. Swagger UI paths are left alone: it serves `/docs/` and redirects
+/// `/docs` there, so trimming would loop.
+pub async fn trim_trailing_slash(mut request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    if path.len() > 1 && path.ends_with('/') && !path.starts_with(DOCS_PATH) {
+        let trimmed = match path.trim_end_matches('/') {
+            "" => "/",
+            trimmed => trimmed,
+        };
+        let path_and_query = match request.uri().query() {
+            Some(query) => format!("{}?{}", trimmed, query),
+            None => trimmed.to_string(),
+        };
+
+        let mut parts = request.uri().clone().into_parts();
+        if let Ok(path_and_query) = PathAndQuery::try_from(path_and_query) {
+            parts.path_and_query = Some(path_and_query);
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *request.uri_mut() = uri;
+            }
+        }
+    }
+
+    next.run(request).await
+}
@@ -0,0 +1,57 @@
+//! Collection of L2 order book snapshots.
+//!
+//! Every `ORDERBOOK_INTERVAL_MS`, the top `ORDERBOOK_DEPTH` levels of each
+//! pair in `ORDERBOOK_PAIRS` are fetched from the exchange and stored in
+//! `orderbook_snapshots`, stamped with the exchange's transaction time, so
+//! the inputs of book features can be looked up next to the predictions
+//! made from them. Every replica collecting stores every snapshot, so only
+//! one should. A failed fetch is logged, and the pair is fetched again on
+//! the next tick; the snapshots missed are not made up for.
+
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+
+use crate::db;
+use crate::exchange::Exchange;
+
+/// Order book depths the exchange serves.
+pub const DEPTHS: [u32; 7] = [5, 10, 20, 50, 100, 500, 1000];
+
+/// Snapshot `pairs`' books, `depth` levels a side, every `interval` until
+/// `shutdown` turns true.
+pub fn start(
+    pool: PgPool,
+    exchange: Exchange,
+    pairs: Vec<String>,
+    depth: u32,
+    interval: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.wait_for(|&closed| closed) => break,
+            }
+
+            for pair in &pairs {
+                let book = match exchange.depth(pair, depth).await {
+                    Ok(book) => book,
+                    Err(e) => {
+                        tracing::warn!(%pair, error = %e, "Failed to fetch order book");
+                        continue;
+                    }
+                };
+                match db::insert_orderbook(&pool, &book).await {
+                    Ok(()) => tracing::debug!(%pair, ts_ms = book.ts_ms, "Order book stored"),
+                    Err(e) => tracing::warn!(%pair, error = %e, "Failed to store order book"),
+                }
+            }
+        }
+    });
+}
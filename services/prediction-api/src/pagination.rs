@@ -0,0 +1,152 @@
+//! Keyset pagination for prediction listings.
+//!
+//! Pages are ordered by `(ts_ms, pair, model_name)`, the table's primary key
+//! in time order, and each page resumes strictly after the last row of the
+//! previous one. Unlike offsets this stays cheap as the table grows and
+//! never skips or repeats rows when new predictions arrive mid-scan.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+use crate::routes::predictions::Prediction;
+
+/// Page size when the request does not give one.
+pub const DEFAULT_PAGE_SIZE: usize = 1_000;
+
+/// Largest page a request may ask for.
+pub const MAX_PAGE_SIZE: usize = 10_000;
+
+/// Position after the last row of a page.
+///
+/// Rendered as `<ts_ms>:<pair>:<model_name>`; clients should treat it as
+/// opaque and only pass back what they received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub ts_ms: i64,
+    pub pair: String,
+    pub model_name: String,
+}
+
+impl Cursor {
+    /// Position of `prediction`, so a scan resumes right after it.
+    pub fn after(prediction: &Prediction) -> Self {
+        Self {
+            ts_ms: prediction.ts_ms,
+            pair: prediction.pair.clone(),
+            model_name: prediction.model_name.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.ts_ms, self.pair, self.model_name)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ApiError::BadRequest("invalid cursor".to_string());
+        let mut parts = s.splitn(3, ':');
+        let ts_ms = parts
+            .next()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(invalid)?;
+        let pair = parts.next().ok_or_else(invalid)?;
+        let model_name = parts.next().ok_or_else(invalid)?;
+
+        Ok(Self {
+            ts_ms,
+            pair: pair.to_string(),
+            model_name: model_name.to_string(),
+        })
+    }
+}
+
+/// Resolve a requested page size, applying the default and the cap.
+pub fn page_size(limit: Option<usize>) -> Result<usize, ApiError> {
+    match limit.unwrap_or(DEFAULT_PAGE_SIZE) {
+        0 => Err(ApiError::BadRequest("limit must be positive".to_string())),
+        limit if limit > MAX_PAGE_SIZE => Err(ApiError::BadRequest(format!(
+            "limit cannot exceed {MAX_PAGE_SIZE}"
+        ))),
+        limit => Ok(limit),
+    }
+}
+
+/// One page of a listing.
+#[derive(Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor for the next page; null on the last page
+    pub next_cursor: Option<String>,
+}
+
+impl Page<Prediction> {
+    /// Build a page from up to `limit + 1` rows fetched after the cursor; the
+    /// extra row only signals that another page exists.
+    pub fn from_rows(mut rows: Vec<Prediction>, limit: usize) -> Self {
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|last| Cursor::after(last).to_string())
+        } else {
+            None
+        };
+
+        Self {
+            items: rows,
+            next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prediction(ts_ms: i64) -> Prediction {
+        Prediction {
+            model_name: "lgbm.v2".to_string(),
+            model_version: "v2".to_string(),
+            ..Prediction::sample("BTCUSDT", ts_ms)
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = Cursor::after(&prediction(1_700_000_000_000));
+        assert_eq!(cursor.to_string(), "1700000000000:BTCUSDT:lgbm.v2");
+        assert_eq!(cursor.to_string().parse::<Cursor>().unwrap(), cursor);
+    }
+
+    #[test]
+    fn rejects_malformed_cursor() {
+        assert!("".parse::<Cursor>().is_err());
+        assert!("abc:BTCUSDT:lgbm".parse::<Cursor>().is_err());
+        assert!("1700000000000:BTCUSDT".parse::<Cursor>().is_err());
+    }
+
+    #[test]
+    fn next_cursor_only_when_rows_remain() {
+        let page = Page::from_rows(vec![prediction(1), prediction(2), prediction(3)], 2);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.next_cursor.as_deref(), Some("2:BTCUSDT:lgbm.v2"));
+
+        let page = Page::from_rows(vec![prediction(1), prediction(2)], 2);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn page_size_bounds() {
+        assert_eq!(page_size(None).unwrap(), DEFAULT_PAGE_SIZE);
+        assert!(page_size(Some(0)).is_err());
+        assert!(page_size(Some(MAX_PAGE_SIZE + 1)).is_err());
+    }
+}
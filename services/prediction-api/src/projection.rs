@@ -0,0 +1,243 @@
+//! Field subsets for prediction responses, either named ("profiles") or
+//! listed per request with `fields`.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::ApiError;
+
+/// Fields of a serialized `Prediction` that projections may select.
+const PREDICTION_FIELDS: &[&str] = &[
+    "pair",
+    "predicted_price",
+    "ts_ms",
+    "ts_iso",
+    "predicted_ts_ms",
+    "predicted_ts_iso",
+    "horizon_ms",
+    "lower_bound",
+    "upper_bound",
+    "quantile",
+    "current_price",
+    "delta_abs",
+    "delta_pct",
+    "direction",
+    "model_name",
+    "model_version",
+    "fallback",
+    "variant",
+    "valid",
+];
+
+/// Profile that always returns every field.
+const FULL: &str = "full";
+
+/// Default `PREDICTION_PROFILES`.
+pub const DEFAULT_PROFILES: &str = "minimal=pair,predicted_price,ts_ms,ts_iso";
+
+/// Query parameters selecting a profile or a field list.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct ProfileQuery {
+    /// Response profile, e.g. "minimal"; defaults to "full"
+    pub profile: Option<String>,
+    /// Comma-separated fields to return, e.g. "pair,predicted_price,ts_ms"
+    pub fields: Option<String>,
+}
+
+/// Profiles configured with `PREDICTION_PROFILES`, plus the built-in `full`.
+#[derive(Debug, Clone)]
+pub struct ProjectionProfiles(BTreeMap<String, Vec<&'static str>>);
+
+impl ProjectionProfiles {
+    /// Fields selected by `profile`; `None` means every field.
+    pub fn fields(&self, profile: Option<&str>) -> Result<Option<Vec<&'static str>>, ApiError> {
+        match profile {
+            None | Some(FULL) => Ok(None),
+            Some(name) => self
+                .0
+                .get(name)
+                .map(|fields| Some(fields.clone()))
+                .ok_or_else(|| ApiError::BadRequest(format!("unknown profile: {}", name))),
+        }
+    }
+
+    /// Fields selected by `profile` or by a comma-separated `fields` list;
+    /// `None` means every field.
+    pub fn select(
+        &self,
+        profile: Option<&str>,
+        fields: Option<&str>,
+    ) -> Result<Option<Vec<&'static str>>, ApiError> {
+        match (profile, fields) {
+            (Some(_), Some(_)) => Err(ApiError::BadRequest(
+                "profile and fields cannot be combined".to_string(),
+            )),
+            (_, Some(fields)) => fields
+                .split(',')
+                .map(|field| {
+                    parse_field(field.trim())
+                        .ok_or_else(|| ApiError::BadRequest(format!("unknown field: {}", field)))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Some),
+            (profile, None) => self.fields(profile),
+        }
+    }
+}
+
+/// The `PREDICTION_FIELDS` entry named `field`.
+fn parse_field(field: &str) -> Option<&'static str> {
+    PREDICTION_FIELDS
+        .iter()
+        .find(|known| **known == field)
+        .copied()
+}
+
+/// Whether a projection keeps any of `names`; `None` keeps every field.
+pub fn keeps_any(fields: Option<&[&'static str]>, names: &[&str]) -> bool {
+    fields.is_none_or(|fields| names.iter().any(|name| fields.contains(name)))
+}
+
+/// Parses `name=field,field;name=field,...`.
+impl FromStr for ProjectionProfiles {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut profiles = BTreeMap::new();
+
+        for entry in s.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, fields) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("profile without fields: {}", entry))?;
+            let name = name.trim();
+            if name.is_empty() || name == FULL {
+                return Err(format!("invalid profile name: {:?}", name));
+            }
+
+            let fields = fields
+                .split(',')
+                .map(str::trim)
+                .map(|field| {
+                    parse_field(field)
+                        .ok_or_else(|| format!("unknown field in profile {}: {}", name, field))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            profiles.insert(name.to_string(), fields);
+        }
+
+        Ok(Self(profiles))
+    }
+}
+
+/// JSON response restricted to a projection's fields.
+pub struct Projected<T> {
+    pub body: T,
+    pub fields: Option<Vec<&'static str>>,
+}
+
+impl<T: Serialize> IntoResponse for Projected<T> {
+    fn into_response(self) -> Response {
+        let Some(fields) = self.fields else {
+            return Json(self.body).into_response();
+        };
+
+        match serde_json::to_value(&self.body) {
+            Ok(value) => Json(project(value, &fields)).into_response(),
+            Err(_) => ApiError::Internal.into_response(),
+        }
+    }
+}
+
+/// Keep only `fields` in an object, or in each object of an array.
+fn project(value: Value, fields: &[&str]) -> Value {
+    match value {
+        Value::Object(mut object) => {
+            object.retain(|key, _| fields.contains(&key.as_str()));
+            Value::Object(object)
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| project(item, fields))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_profiles() {
+        let profiles: ProjectionProfiles = "minimal=pair,predicted_price; ids = pair , model_name"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            profiles.fields(Some("ids")).unwrap(),
+            Some(vec!["pair", "model_name"])
+        );
+        assert_eq!(profiles.fields(Some("full")).unwrap(), None);
+        assert_eq!(profiles.fields(None).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_reserved_names() {
+        assert!("minimal=pair,price".parse::<ProjectionProfiles>().is_err());
+        assert!("full=pair".parse::<ProjectionProfiles>().is_err());
+        assert!("minimal".parse::<ProjectionProfiles>().is_err());
+    }
+
+    #[test]
+    fn unknown_profile_is_bad_request() {
+        let profiles: ProjectionProfiles = DEFAULT_PROFILES.parse().unwrap();
+        assert!(matches!(
+            profiles.fields(Some("tiny")),
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn selects_field_lists() {
+        let profiles: ProjectionProfiles = DEFAULT_PROFILES.parse().unwrap();
+        assert_eq!(
+            profiles.select(None, Some("pair, ts_ms")).unwrap(),
+            Some(vec!["pair", "ts_ms"])
+        );
+        assert_eq!(
+            profiles.select(Some("minimal"), None).unwrap(),
+            Some(vec!["pair", "predicted_price", "ts_ms", "ts_iso"])
+        );
+        assert!(profiles.select(None, Some("pair,price")).is_err());
+        assert!(profiles.select(Some("minimal"), Some("pair")).is_err());
+
+        assert!(keeps_any(None, &["current_price"]));
+        assert!(!keeps_any(Some(&["pair"]), &["current_price"]));
+    }
+
+    #[test]
+    fn projects_objects_and_arrays() {
+        let prediction = json!({"pair": "BTCUSDT", "predicted_price": 1.0, "model_name": "lgbm"});
+        let fields = ["pair", "predicted_price"];
+
+        assert_eq!(
+            project(prediction.clone(), &fields),
+            json!({"pair": "BTCUSDT", "predicted_price": 1.0})
+        );
+        assert_eq!(
+            project(json!([prediction]), &fields),
+            json!([{"pair": "BTCUSDT", "predicted_price": 1.0}])
+        );
+    }
+}
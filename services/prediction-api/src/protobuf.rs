@@ -0,0 +1,405 @@
+//! Protobuf request bodies for prediction writes.
+//!
+//! Model runners that would rather not produce JSON at high rates can send
+//! the messages of `proto/prediction.proto` instead, with `Content-Type:
+//! application/x-protobuf`. The messages are flat, so they are decoded here
+//! directly from the wire format rather than through generated code, and
+//! tested against messages encoded by Google's protobuf runtime
+//! (`proto/testdata`).
+
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+use crate::error::ApiError;
+use crate::routes::ingest::{BulkRequest, NewPrediction};
+
+/// Content type of protobuf bodies.
+const PROTOBUF: &str = "application/x-protobuf";
+
+/// The `.proto` schema of the accepted messages.
+pub const PREDICTION_PROTO: &str = include_str!("../proto/prediction.proto");
+
+/// Why a protobuf body could not be decoded.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct DecodeError(&'static str);
+
+/// A message of `proto/prediction.proto`.
+pub trait Message: Sized {
+    fn decode(buf: &[u8]) -> Result<Self, DecodeError>;
+}
+
+/// Request body sent as JSON or, with `Content-Type:
+/// application/x-protobuf`, as protobuf.
+pub struct JsonOrProtobuf<T>(pub T);
+
+impl<S, T> FromRequest<S> for JsonOrProtobuf<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Message,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_protobuf(request.headers()) {
+            let Json(value) = Json::<T>::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(Self(value));
+        }
+
+        let body = Bytes::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        T::decode(&body).map(Self).map_err(|e| {
+            ApiError::BadRequest(format!("invalid protobuf body: {e}")).into_response()
+        })
+    }
+}
+
+/// Whether the request body is protobuf. `application/protobuf` is
+/// accepted too.
+fn is_protobuf(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| {
+            let mime = mime.trim();
+            mime.eq_ignore_ascii_case(PROTOBUF) || mime.eq_ignore_ascii_case("application/protobuf")
+        })
+}
+
+impl Message for NewPrediction {
+    fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut prediction = NewPrediction {
+            pair: String::new(),
+            model_name: String::new(),
+            model_version: String::new(),
+            ts_ms: 0,
+            predicted_ts_ms: None,
+            predicted_price: 0.0,
+            lower_bound: None,
+            upper_bound: None,
+            quantile: None,
+        };
+        let mut reader = Reader { buf };
+        while let Some((field, wire)) = reader.key()? {
+            match field {
+                1 => prediction.pair = reader.string(wire)?,
+                2 => prediction.model_name = reader.string(wire)?,
+                3 => prediction.model_version = reader.string(wire)?,
+                4 => prediction.ts_ms = reader.int64(wire)?,
+                5 => prediction.predicted_ts_ms = Some(reader.int64(wire)?),
+                6 => prediction.predicted_price = reader.double(wire)?,
+                7 => prediction.lower_bound = Some(reader.double(wire)?),
+                8 => prediction.upper_bound = Some(reader.double(wire)?),
+                9 => prediction.quantile = Some(reader.double(wire)?),
+                _ => reader.skip(wire)?,
+            }
+        }
+        Ok(prediction)
+    }
+}
+
+impl Message for BulkRequest {
+    fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut predictions = Vec::new();
+        let mut reader = Reader { buf };
+        while let Some((field, wire)) = reader.key()? {
+            match field {
+                1 => predictions.push(NewPrediction::decode(reader.bytes(wire)?)?),
+                _ => reader.skip(wire)?,
+            }
+        }
+        Ok(BulkRequest { predictions })
+    }
+}
+
+/// How a field's value is encoded on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wire {
+    Varint,
+    Fixed64,
+    Len,
+    Fixed32,
+}
+
+const TRUNCATED: DecodeError = DecodeError("message is truncated");
+const WRONG_WIRE_TYPE: DecodeError = DecodeError("field has an unexpected wire type");
+
+/// Reads fields off the front of an encoded message.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Number and wire type of the next field; `None` at the end.
+    fn key(&mut self) -> Result<Option<(u32, Wire)>, DecodeError> {
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let wire = match key & 7 {
+            0 => Wire::Varint,
+            1 => Wire::Fixed64,
+            2 => Wire::Len,
+            5 => Wire::Fixed32,
+            _ => return Err(DecodeError("unsupported wire type")),
+        };
+        match u32::try_from(key >> 3) {
+            Ok(field) if field > 0 => Ok(Some((field, wire))),
+            _ => Err(DecodeError("invalid field number")),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.buf.len() {
+            return Err(TRUNCATED);
+        }
+        let (taken, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError("varint is too long"))
+    }
+
+    fn int64(&mut self, wire: Wire) -> Result<i64, DecodeError> {
+        if wire != Wire::Varint {
+            return Err(WRONG_WIRE_TYPE);
+        }
+        // Negative values are sent as their 64-bit two's complement
+        Ok(self.varint()? as i64)
+    }
+
+    fn double(&mut self, wire: Wire) -> Result<f64, DecodeError> {
+        if wire != Wire::Fixed64 {
+            return Err(WRONG_WIRE_TYPE);
+        }
+        let bytes = self.take(8)?.try_into().map_err(|_| TRUNCATED)?;
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    fn bytes(&mut self, wire: Wire) -> Result<&'a [u8], DecodeError> {
+        if wire != Wire::Len {
+            return Err(WRONG_WIRE_TYPE);
+        }
+        let len = usize::try_from(self.varint()?).map_err(|_| TRUNCATED)?;
+        self.take(len)
+    }
+
+    fn string(&mut self, wire: Wire) -> Result<String, DecodeError> {
+        let bytes = self.bytes(wire)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError("string is not valid UTF-8"))
+    }
+
+    /// Skip a field this service does not know, e.g. one added to the
+    /// schema later.
+    fn skip(&mut self, wire: Wire) -> Result<(), DecodeError> {
+        match wire {
+            Wire::Varint => self.varint().map(drop),
+            Wire::Fixed64 => self.take(8).map(drop),
+            Wire::Len => self.bytes(wire).map(drop),
+            Wire::Fixed32 => self.take(4).map(drop),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn string(field: u64, value: &str, out: &mut Vec<u8>) {
+        varint(field << 3 | 2, out);
+        varint(value.len() as u64, out);
+        out.extend_from_slice(value.as_bytes());
+    }
+
+    fn int64(field: u64, value: i64, out: &mut Vec<u8>) {
+        varint(field << 3, out);
+        varint(value as u64, out);
+    }
+
+    fn double(field: u64, value: f64, out: &mut Vec<u8>) {
+        varint(field << 3 | 1, out);
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn encoded_prediction() -> Vec<u8> {
+        let mut buf = Vec::new();
+        string(1, "BTCUSDT", &mut buf);
+        string(2, "lgbm", &mut buf);
+        string(3, "v1", &mut buf);
+        int64(4, 1_700_000_000_000, &mut buf);
+        double(6, 65000.5, &mut buf);
+        double(9, 0.5, &mut buf);
+        buf
+    }
+
+    #[test]
+    fn decodes_predictions() {
+        let prediction = NewPrediction::decode(&encoded_prediction()).unwrap();
+        assert_eq!(prediction.pair, "BTCUSDT");
+        assert_eq!(prediction.model_version, "v1");
+        assert_eq!(prediction.ts_ms, 1_700_000_000_000);
+        assert_eq!(prediction.predicted_ts_ms, None);
+        assert_eq!(prediction.predicted_price, 65000.5);
+        assert_eq!(prediction.quantile, Some(0.5));
+
+        let mut buf = Vec::new();
+        for _ in 0..2 {
+            let prediction = encoded_prediction();
+            varint(1 << 3 | 2, &mut buf);
+            varint(prediction.len() as u64, &mut buf);
+            buf.extend_from_slice(&prediction);
+        }
+        assert_eq!(BulkRequest::decode(&buf).unwrap().predictions.len(), 2);
+    }
+
+    #[test]
+    fn skips_unknown_fields_and_keeps_negative_values() {
+        let mut buf = encoded_prediction();
+        string(15, "added later", &mut buf);
+        int64(16, 7, &mut buf);
+        int64(4, -1, &mut buf);
+        let prediction = NewPrediction::decode(&buf).unwrap();
+        assert_eq!(prediction.ts_ms, -1);
+        assert!(prediction.validate(1_700_000_000_000).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let buf = encoded_prediction();
+        assert!(NewPrediction::decode(&buf[..buf.len() - 1]).is_err());
+
+        let mut wrong_type = Vec::new();
+        int64(1, 5, &mut wrong_type);
+        assert!(NewPrediction::decode(&wrong_type).is_err());
+        assert!(NewPrediction::decode(&[0x80]).is_err());
+    }
+
+    // Encoded by Google's protobuf runtime; see proto/testdata/generate.py
+    const GOLDEN_PREDICTION: &[u8] = include_bytes!("../proto/testdata/new_prediction.pb");
+    const GOLDEN_MINIMAL: &[u8] = include_bytes!("../proto/testdata/new_prediction_minimal.pb");
+    const GOLDEN_NEGATIVE: &[u8] = include_bytes!("../proto/testdata/new_prediction_negative.pb");
+    const GOLDEN_UNKNOWN_FIELDS: &[u8] =
+        include_bytes!("../proto/testdata/new_prediction_unknown_fields.pb");
+    const GOLDEN_BULK: &[u8] = include_bytes!("../proto/testdata/bulk_request.pb");
+    const GOLDEN_BULK_UNKNOWN_FIELDS: &[u8] =
+        include_bytes!("../proto/testdata/bulk_request_unknown_fields.pb");
+
+    fn assert_golden_prediction(prediction: &NewPrediction) {
+        assert_eq!(prediction.pair, "BTCUSDT");
+        assert_eq!(prediction.model_name, "lgbm");
+        assert_eq!(prediction.model_version, "v1");
+        assert_eq!(prediction.ts_ms, 1_700_000_000_000);
+        assert_eq!(prediction.predicted_ts_ms, Some(1_700_000_060_000));
+        assert_eq!(prediction.predicted_price, 65000.5);
+        assert_eq!(prediction.lower_bound, Some(64000.25));
+        assert_eq!(prediction.upper_bound, Some(66000.75));
+        assert_eq!(prediction.quantile, Some(0.5));
+    }
+
+    fn assert_golden_minimal(prediction: &NewPrediction) {
+        assert_eq!(prediction.pair, "ETHUSDT");
+        assert_eq!(prediction.model_name, "arima");
+        assert_eq!(prediction.model_version, "");
+        assert_eq!(prediction.ts_ms, 0);
+        assert_eq!(prediction.predicted_ts_ms, None);
+        assert_eq!(prediction.predicted_price, 3100.0);
+        assert_eq!(prediction.lower_bound, None);
+        assert_eq!(prediction.upper_bound, None);
+        assert_eq!(prediction.quantile, None);
+    }
+
+    fn assert_golden_negative(prediction: &NewPrediction) {
+        assert_eq!(prediction.ts_ms, -1);
+        assert_eq!(prediction.predicted_ts_ms, Some(-1_700_000_000_000));
+        assert_eq!(prediction.predicted_price, -0.5);
+        // Optionals explicitly set to zero are present
+        assert_eq!(prediction.lower_bound, Some(0.0));
+        assert_eq!(prediction.upper_bound, None);
+        assert_eq!(prediction.quantile, Some(0.0));
+    }
+
+    #[test]
+    fn decodes_golden_predictions() {
+        assert_golden_prediction(&NewPrediction::decode(GOLDEN_PREDICTION).unwrap());
+        assert_golden_minimal(&NewPrediction::decode(GOLDEN_MINIMAL).unwrap());
+        assert_golden_negative(&NewPrediction::decode(GOLDEN_NEGATIVE).unwrap());
+    }
+
+    #[test]
+    fn decodes_golden_bulk_requests() {
+        let bulk = BulkRequest::decode(GOLDEN_BULK).unwrap();
+        assert_eq!(bulk.predictions.len(), 3);
+        assert_golden_prediction(&bulk.predictions[0]);
+        assert_golden_minimal(&bulk.predictions[1]);
+        assert_golden_negative(&bulk.predictions[2]);
+    }
+
+    #[test]
+    fn skips_golden_unknown_fields() {
+        // A newer producer's message, with a string, packed repeated int64,
+        // fixed32, sint64 and nested message this schema does not have
+        let expected = NewPrediction {
+            pair: "SOLUSDT".to_string(),
+            model_name: "tft".to_string(),
+            model_version: "v3".to_string(),
+            ts_ms: 1_700_000_000_000,
+            predicted_ts_ms: None,
+            predicted_price: 150.125,
+            lower_bound: None,
+            upper_bound: Some(155.0),
+            quantile: None,
+        };
+        let prediction = NewPrediction::decode(GOLDEN_UNKNOWN_FIELDS).unwrap();
+        assert_eq!(format!("{prediction:?}"), format!("{expected:?}"));
+
+        let bulk = BulkRequest::decode(GOLDEN_BULK_UNKNOWN_FIELDS).unwrap();
+        assert_eq!(bulk.predictions.len(), 1);
+        assert_eq!(
+            format!("{:?}", bulk.predictions[0]),
+            format!("{expected:?}")
+        );
+    }
+
+    #[test]
+    fn detects_protobuf_bodies() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_protobuf(&headers));
+        headers.insert(
+            header::CONTENT_TYPE,
+            "application/x-protobuf; messageType=NewPrediction"
+                .parse()
+                .unwrap(),
+        );
+        assert!(is_protobuf(&headers));
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        assert!(!is_protobuf(&headers));
+    }
+}
@@ -0,0 +1,146 @@
+//! Deletion of expired rows from tables that would otherwise grow without
+//! bound.
+//!
+//! Rows are deleted `BATCH` at a time, each statement waiting at most
+//! `LOCK_TIMEOUT` for the locks it needs, so a purge never holds the writes
+//! to the table it trims up for long. A batch that times out is retried
+//! after a backoff, and the contention is logged.
+
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::sync::watch;
+
+use crate::db;
+use crate::error::ApiError;
+use crate::timestamp;
+
+/// How often expired rows are deleted.
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Rows deleted per statement.
+const BATCH: i64 = 1000;
+
+/// Longest a batch waits for locks before giving way to writers.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Batches retried in a row after lock timeouts before the purge waits for
+/// its next run.
+const MAX_RETRIES: u32 = 5;
+
+/// Delay before the first retry; doubles with each further one.
+const RETRY_BASE: Duration = Duration::from_secs(1);
+
+/// SQLSTATE of a statement that timed out waiting for a lock.
+const LOCK_NOT_AVAILABLE: &str = "55P03";
+
+/// A table whose rows expire.
+#[derive(Debug, Clone, Copy)]
+pub struct Expiring {
+    pub table: &'static str,
+    /// Primary key column
+    pub key: &'static str,
+    /// When the row was written, in ms since the epoch
+    pub written: &'static str,
+}
+
+/// The prediction outbox.
+pub const PREDICTION_EVENTS: Expiring = Expiring {
+    table: "prediction_events",
+    key: "id",
+    written: "created_ts_ms",
+};
+
+/// Stored idempotent responses.
+pub const IDEMPOTENCY_KEYS: Expiring = Expiring {
+    table: "idempotency_keys",
+    key: "key",
+    written: "created_ts_ms",
+};
+
+/// Ingested predictions that failed validation.
+pub const REJECTED_PREDICTIONS: Expiring = Expiring {
+    table: "rejected_predictions",
+    key: "id",
+    written: "rejected_ts_ms",
+};
+
+/// Webhook deliveries that failed every attempt.
+pub const WEBHOOK_DEAD_LETTERS: Expiring = Expiring {
+    table: "webhook_dead_letters",
+    key: "id",
+    written: "failed_ts_ms",
+};
+
+/// Delete the rows of `expiring` older than `retention` every hour until
+/// `shutdown` turns true.
+pub fn start(
+    pool: PgPool,
+    expiring: Expiring,
+    retention: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.wait_for(|&closed| closed) => break,
+            }
+
+            let before_ms = timestamp::now_ms()
+                .saturating_sub(i64::try_from(retention.as_millis()).unwrap_or(i64::MAX));
+            match purge(&pool, expiring, before_ms).await {
+                Ok(0) => {}
+                Ok(deleted) => {
+                    tracing::debug!(table = expiring.table, deleted, "Expired rows deleted")
+                }
+                Err(e) => tracing::warn!(
+                    table = expiring.table,
+                    error = %e,
+                    "Failed to delete expired rows"
+                ),
+            }
+        }
+    });
+}
+
+/// Delete the rows of `expiring` written before `before_ms`, batch by
+/// batch. Returns how many were deleted.
+async fn purge(pool: &PgPool, expiring: Expiring, before_ms: i64) -> Result<u64, ApiError> {
+    let mut deleted = 0;
+    let mut retries = 0;
+    loop {
+        match db::delete_expired(pool, expiring, before_ms, BATCH, LOCK_TIMEOUT).await {
+            Ok(batch) => {
+                deleted += batch;
+                retries = 0;
+                if batch < BATCH as u64 {
+                    return Ok(deleted);
+                }
+            }
+            Err(ApiError::Database(sqlx::Error::Database(e)))
+                if e.code().as_deref() == Some(LOCK_NOT_AVAILABLE) =>
+            {
+                retries += 1;
+                if retries > MAX_RETRIES {
+                    tracing::warn!(
+                        table = expiring.table,
+                        deleted,
+                        "Purge gave way to writers; the rest waits for the next run"
+                    );
+                    return Ok(deleted);
+                }
+                let delay = RETRY_BASE.saturating_mul(1 << (retries - 1));
+                tracing::info!(
+                    table = expiring.table,
+                    retries,
+                    retry_in_ms = delay.as_millis() as u64,
+                    "Purge batch timed out waiting for locks, retrying"
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Builder for SELECTs with optional filters.
+//!
+//! Conditions and their bound parameters are pushed together, so placeholder
+//! numbering always matches bind order and values never end up in the SQL.
+
+use sqlx::{Encode, Postgres, QueryBuilder, Type};
+
+/// Accumulates `WHERE` conditions over a base `SELECT ... FROM ...`.
+///
+/// Column names and operators are `&'static str` so only literals from the
+/// code base, never request input, are spliced into the SQL.
+pub struct FilteredSelect<'args> {
+    builder: QueryBuilder<'args, Postgres>,
+    has_where: bool,
+}
+
+impl<'args> FilteredSelect<'args> {
+    pub fn new(select: &'static str) -> Self {
+        Self {
+            builder: QueryBuilder::new(select),
+            has_where: false,
+        }
+    }
+
+    /// Start from a SELECT that binds its own parameters, such as computed
+    /// columns; filters are numbered after them.
+    pub fn from_builder(builder: QueryBuilder<'args, Postgres>) -> Self {
+        Self {
+            builder,
+            has_where: false,
+        }
+    }
+
+    /// Add `column <op> value`.
+    pub fn filter<T>(mut self, column: &'static str, op: &'static str, value: T) -> Self
+    where
+        T: 'args + Encode<'args, Postgres> + Type<Postgres> + Send,
+    {
+        self.builder
+            .push(if self.has_where { " AND " } else { " WHERE " })
+            .push(column)
+            .push(" ")
+            .push(op)
+            .push(" ")
+            .push_bind(value);
+        self.has_where = true;
+        self
+    }
+
+    /// Add a condition written by `push`, which must bind every value it uses.
+    pub fn filter_with(mut self, push: impl FnOnce(&mut QueryBuilder<'args, Postgres>)) -> Self {
+        self.builder
+            .push(if self.has_where { " AND " } else { " WHERE " });
+        push(&mut self.builder);
+        self.has_where = true;
+        self
+    }
+
+    /// Add `column <op> value` when `value` is set; skip it otherwise.
+    pub fn filter_opt<T>(self, column: &'static str, op: &'static str, value: Option<T>) -> Self
+    where
+        T: 'args + Encode<'args, Postgres> + Type<Postgres> + Send,
+    {
+        match value {
+            Some(value) => self.filter(column, op, value),
+            None => self,
+        }
+    }
+
+    /// Append a trailing clause such as `ORDER BY`.
+    pub fn then(mut self, clause: &'static str) -> Self {
+        self.builder.push(" ").push(clause);
+        self
+    }
+
+    /// Append `LIMIT value`.
+    pub fn limit(mut self, value: i64) -> Self {
+        self.builder.push(" LIMIT ").push_bind(value);
+        self
+    }
+
+    /// Finish building; run with `.build().fetch_*(pool)`.
+    pub fn into_builder(self) -> QueryBuilder<'args, Postgres> {
+        self.builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::{Arguments, Execute};
+
+    fn bound(select: FilteredSelect<'_>) -> (String, usize) {
+        let mut builder = select.into_builder();
+        let mut query = builder.build();
+        let sql = query.sql().to_string();
+        let args = query.take_arguments().unwrap().map_or(0, |a| a.len());
+        (sql, args)
+    }
+
+    #[test]
+    fn no_filters_has_no_where() {
+        let (sql, args) = bound(FilteredSelect::new("SELECT * FROM t").then("ORDER BY a"));
+        assert_eq!(sql, "SELECT * FROM t ORDER BY a");
+        assert_eq!(args, 0);
+    }
+
+    #[test]
+    fn filters_are_numbered_in_bind_order() {
+        let (sql, args) = bound(
+            FilteredSelect::new("SELECT * FROM t")
+                .filter("pair", "=", "BTCUSDT")
+                .filter_opt("model_name", "=", Some("lgbm"))
+                .filter_opt("ts_ms", "<=", Some(5_i64))
+                .then("ORDER BY ts_ms DESC")
+                .limit(1),
+        );
+        assert_eq!(
+            sql,
+            "SELECT * FROM t WHERE pair = $1 AND model_name = $2 AND ts_ms <= $3 \
+             ORDER BY ts_ms DESC LIMIT $4"
+        );
+        assert_eq!(args, 4);
+    }
+
+    #[test]
+    fn skipped_filters_do_not_shift_placeholders() {
+        let (sql, args) = bound(
+            FilteredSelect::new("SELECT * FROM t")
+                .filter_opt("model_name", "=", None::<&str>)
+                .filter_opt("ts_ms", "<=", Some(5_i64)),
+        );
+        assert_eq!(sql, "SELECT * FROM t WHERE ts_ms <= $1");
+        assert_eq!(args, 1);
+    }
+
+    #[test]
+    fn custom_conditions_share_numbering() {
+        let (sql, args) = bound(
+            FilteredSelect::new("SELECT * FROM t")
+                .filter("pair", "=", "BTCUSDT")
+                .filter_with(|q| {
+                    q.push("(ts_ms, pair) > (")
+                        .push_bind(5_i64)
+                        .push(", ")
+                        .push_bind("ETHUSDT")
+                        .push(")");
+                })
+                .limit(10),
+        );
+        assert_eq!(
+            sql,
+            "SELECT * FROM t WHERE pair = $1 AND (ts_ms, pair) > ($2, $3) LIMIT $4"
+        );
+        assert_eq!(args, 4);
+    }
+
+    #[test]
+    fn values_are_bound_not_inlined() {
+        let (sql, args) = bound(FilteredSelect::new("SELECT * FROM t").filter(
+            "pair",
+            "=",
+            "x'; DROP TABLE t; --",
+        ));
+        assert_eq!(sql, "SELECT * FROM t WHERE pair = $1");
+        assert_eq!(args, 1);
+    }
+}
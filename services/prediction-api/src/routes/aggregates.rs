@@ -0,0 +1,244 @@
+//! Aggregated views over prediction history.
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db::{self, ModelFilter};
+use crate::envelope::EnvelopeQuery;
+use crate::error::ApiError;
+use crate::routes::history::validate_range;
+use crate::routes::predictions::{
+    parse_horizon, validate_model_name, validate_model_version, validate_pair,
+};
+use crate::state::AppState;
+use crate::timestamp;
+
+/// Most buckets a single downsample request may produce.
+const MAX_BUCKETS: i64 = 10_000;
+
+/// Query parameters for downsampling a pair's predictions.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct DownsampleQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Bucket width, e.g. "1m", "5m", "1h"
+    pub bucket: String,
+    /// Start of the range, inclusive (ms)
+    pub from_ts_ms: i64,
+    /// End of the range, inclusive (ms)
+    pub to_ts_ms: i64,
+    /// Only include predictions from this model
+    pub model_name: Option<String>,
+    /// Only include predictions from this model version
+    pub model_version: Option<String>,
+    /// Only include predictions for this horizon, e.g. "4h"
+    pub horizon: Option<String>,
+}
+
+impl DownsampleQuery {
+    /// Validate the query parameters, returning the bucket width in ms.
+    pub fn validate(&self, max_range_ms: i64) -> Result<i64, ApiError> {
+        validate_pair(&self.pair)?;
+        if let Some(model_name) = &self.model_name {
+            validate_model_name(model_name)?;
+        }
+        if let Some(model_version) = &self.model_version {
+            validate_model_version(model_version)?;
+        }
+        parse_horizon(self.horizon.as_deref())?;
+        validate_range(self.from_ts_ms, self.to_ts_ms, max_range_ms)?;
+
+        let bucket_ms = timestamp::parse_duration_ms(&self.bucket).ok_or_else(|| {
+            ApiError::BadRequest("bucket must be a positive duration like 1m, 5m or 1h".to_string())
+        })?;
+        if (self.to_ts_ms - self.from_ts_ms) / bucket_ms >= MAX_BUCKETS {
+            return Err(ApiError::BadRequest(format!(
+                "range spans more than {MAX_BUCKETS} buckets; widen the bucket"
+            )));
+        }
+        Ok(bucket_ms)
+    }
+}
+
+/// Predicted prices aggregated over one time bucket.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PriceBucket {
+    /// Start of the bucket, aligned to the bucket width (ms)
+    pub bucket_start_ms: i64,
+    /// Predictions in the bucket
+    pub count: i64,
+    /// Mean predicted price
+    pub avg_price: f64,
+    /// Predicted price of the earliest prediction in the bucket
+    pub first_price: f64,
+    /// Predicted price of the latest prediction in the bucket
+    pub last_price: f64,
+}
+
+/// Downsample a pair's predictions into fixed time buckets.
+///
+/// Buckets are aligned to multiples of the bucket width since the epoch and
+/// returned oldest first; empty buckets are omitted. Predictions with a
+/// non-finite price are left out of every aggregate.
+#[utoipa::path(
+    get,
+    path = "/predictions/downsample",
+    params(DownsampleQuery, EnvelopeQuery),
+    responses(
+        (status = 200, description = "Buckets in the range, oldest first", body = Vec<PriceBucket>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_downsample(
+    State(state): State<AppState>,
+    Query(params): Query<DownsampleQuery>,
+) -> Result<Json<Vec<PriceBucket>>, ApiError> {
+    let bucket_ms = params.validate(state.config.max_history_range_ms)?;
+    let model = ModelFilter {
+        name: params.model_name.as_deref(),
+        version: params.model_version.as_deref(),
+        horizon_ms: parse_horizon(params.horizon.as_deref())?,
+    };
+
+    tracing::info!(pair = %params.pair, bucket_ms, "Downsampling predictions");
+
+    let buckets = db::get_downsampled(
+        &state.pool,
+        &params.pair,
+        model,
+        params.from_ts_ms,
+        params.to_ts_ms,
+        bucket_ms,
+    )
+    .await?;
+
+    tracing::debug!(count = buckets.len(), "Buckets fetched");
+
+    Ok(Json(buckets))
+}
+
+/// Query parameters for statistics over a trailing window.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct StatsQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Trailing window ending now, e.g. "1h", "24h", "7d"
+    pub window: String,
+    /// Only include predictions from this model
+    pub model_name: Option<String>,
+    /// Only include predictions from this model version
+    pub model_version: Option<String>,
+    /// Only include predictions for this horizon, e.g. "4h"
+    pub horizon: Option<String>,
+}
+
+impl StatsQuery {
+    /// Validate the query parameters, returning the window in ms.
+    pub fn validate(&self, max_range_ms: i64) -> Result<i64, ApiError> {
+        validate_pair(&self.pair)?;
+        if let Some(model_name) = &self.model_name {
+            validate_model_name(model_name)?;
+        }
+        if let Some(model_version) = &self.model_version {
+            validate_model_version(model_version)?;
+        }
+        parse_horizon(self.horizon.as_deref())?;
+
+        let window_ms = timestamp::parse_duration_ms(&self.window).ok_or_else(|| {
+            ApiError::BadRequest(
+                "window must be a positive duration like 1h, 24h or 7d".to_string(),
+            )
+        })?;
+        validate_range(0, window_ms, max_range_ms)?;
+        Ok(window_ms)
+    }
+}
+
+/// Summary statistics of predicted prices; all null when the window holds
+/// no predictions.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PriceStats {
+    pub pair: String,
+    /// Window the statistics cover, ending at request time (ms)
+    pub window_ms: i64,
+    /// Predictions in the window
+    pub count: i64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    /// Sample standard deviation; null for fewer than two predictions
+    pub stddev: Option<f64>,
+}
+
+/// Summary statistics of a pair's predicted prices over a trailing window.
+///
+/// Predictions with a non-finite price are left out.
+#[utoipa::path(
+    get,
+    path = "/predictions/stats",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Price statistics", body = PriceStats),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_stats(
+    State(state): State<AppState>,
+    Query(params): Query<StatsQuery>,
+) -> Result<Json<PriceStats>, ApiError> {
+    let window_ms = params.validate(state.config.max_history_range_ms)?;
+    let model = ModelFilter {
+        name: params.model_name.as_deref(),
+        version: params.model_version.as_deref(),
+        horizon_ms: parse_horizon(params.horizon.as_deref())?,
+    };
+
+    tracing::info!(pair = %params.pair, window_ms, "Computing prediction stats");
+
+    let stats = db::get_price_stats(&state.pool, &params.pair, model, window_ms).await?;
+
+    Ok(Json(stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(bucket: &str, to_ts_ms: i64) -> DownsampleQuery {
+        DownsampleQuery {
+            pair: "BTCUSDT".to_string(),
+            bucket: bucket.to_string(),
+            from_ts_ms: 0,
+            to_ts_ms,
+            model_name: None,
+            model_version: None,
+            horizon: None,
+        }
+    }
+
+    #[test]
+    fn parses_bucket_width() {
+        assert_eq!(query("5m", 3_600_000).validate(i64::MAX).unwrap(), 300_000);
+    }
+
+    #[test]
+    fn rejects_too_many_buckets() {
+        assert!(query("1s", 10_000_000).validate(i64::MAX).is_err());
+        assert!(query("1s", 9_999_999).validate(i64::MAX).is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_bucket() {
+        assert!(query("0m", 3_600_000).validate(i64::MAX).is_err());
+        assert!(query("week", 3_600_000).validate(i64::MAX).is_err());
+    }
+}
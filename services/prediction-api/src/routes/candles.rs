@@ -0,0 +1,156 @@
+//! OHLCV candles of realized market prices.
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db;
+use crate::envelope::EnvelopeQuery;
+use crate::error::ApiError;
+use crate::routes::history::validate_range;
+use crate::routes::predictions::validate_pair;
+use crate::state::AppState;
+use crate::timestamp;
+
+/// Width of the stored candles every interval is built from.
+pub const BASE_CANDLE_MS: i64 = 60_000;
+
+/// Most candles a single request may return.
+const MAX_CANDLES: i64 = 10_000;
+
+/// Query parameters for a pair's candles.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct CandlesQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Candle width, a whole number of minutes, e.g. "1m", "5m", "1h"
+    pub interval: String,
+    /// Start of the range, inclusive (ms)
+    pub from_ts_ms: i64,
+    /// End of the range, inclusive (ms)
+    pub to_ts_ms: i64,
+}
+
+impl CandlesQuery {
+    /// Validate the query parameters, returning the candle width in ms.
+    pub fn validate(&self, max_range_ms: i64) -> Result<i64, ApiError> {
+        validate_pair(&self.pair)?;
+        validate_range(self.from_ts_ms, self.to_ts_ms, max_range_ms)?;
+
+        let interval_ms = timestamp::parse_duration_ms(&self.interval)
+            .filter(|ms| ms % BASE_CANDLE_MS == 0)
+            .ok_or_else(|| {
+                ApiError::BadRequest(
+                    "interval must be a whole number of minutes like 1m, 5m or 1h".to_string(),
+                )
+            })?;
+        if (self.to_ts_ms - self.from_ts_ms) / interval_ms >= MAX_CANDLES {
+            return Err(ApiError::BadRequest(format!(
+                "range spans more than {MAX_CANDLES} candles; widen the interval"
+            )));
+        }
+        Ok(interval_ms)
+    }
+}
+
+/// Open, high, low, close and volume of a pair over one interval.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Candle {
+    /// Start of the interval, aligned to the interval width (ms)
+    pub open_ts_ms: i64,
+    /// End of the interval, exclusive (ms)
+    pub close_ts_ms: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Traded volume, in the base asset
+    pub volume: f64,
+    /// 1m candles the candle was built from; fewer than the interval has
+    /// minutes when trading data is missing or the interval is still open
+    pub count: i64,
+}
+
+/// Get a pair's OHLCV candles within a time range.
+///
+/// Candles are built from the 1m candles the candles service writes, so
+/// any whole number of minutes works as an interval. They are aligned to
+/// multiples of the interval since the epoch and cover every interval that
+/// overlaps the range, oldest first; intervals without trades are omitted.
+/// Late trades, which the candles service folds into the 1m candles they
+/// belong to, show up as soon as those candles are rewritten.
+#[utoipa::path(
+    get,
+    path = "/candles",
+    params(CandlesQuery, EnvelopeQuery),
+    responses(
+        (status = 200, description = "Candles in the range, oldest first", body = Vec<Candle>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "prices"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_candles(
+    State(state): State<AppState>,
+    Query(params): Query<CandlesQuery>,
+) -> Result<Json<Vec<Candle>>, ApiError> {
+    let interval_ms = params.validate(state.config.max_history_range_ms)?;
+    let (from_ms, until_ms) = covering_range(params.from_ts_ms, params.to_ts_ms, interval_ms);
+
+    tracing::info!(pair = %params.pair, interval_ms, "Fetching candles");
+
+    let candles =
+        db::get_candles(&state.pool, &params.pair, from_ms, until_ms, interval_ms).await?;
+
+    tracing::debug!(count = candles.len(), "Candles fetched");
+
+    Ok(Json(candles))
+}
+
+/// The range `[from, until)` of whole intervals overlapping
+/// `[from_ts_ms, to_ts_ms]`.
+fn covering_range(from_ts_ms: i64, to_ts_ms: i64, interval_ms: i64) -> (i64, i64) {
+    let from = from_ts_ms.div_euclid(interval_ms) * interval_ms;
+    let until = (to_ts_ms.div_euclid(interval_ms) + 1).saturating_mul(interval_ms);
+    (from, until)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(interval: &str, to_ts_ms: i64) -> CandlesQuery {
+        CandlesQuery {
+            pair: "BTCUSDT".to_string(),
+            interval: interval.to_string(),
+            from_ts_ms: 0,
+            to_ts_ms,
+        }
+    }
+
+    #[test]
+    fn accepts_whole_minute_intervals() {
+        assert_eq!(query("5m", 3_600_000).validate(i64::MAX).unwrap(), 300_000);
+        assert_eq!(
+            query("1h", 3_600_000).validate(i64::MAX).unwrap(),
+            3_600_000
+        );
+        assert!(query("30s", 3_600_000).validate(i64::MAX).is_err());
+        assert!(query("90s", 3_600_000).validate(i64::MAX).is_err());
+        assert!(query("1m", 60_000 * MAX_CANDLES)
+            .validate(i64::MAX)
+            .is_err());
+    }
+
+    #[test]
+    fn covers_whole_intervals() {
+        assert_eq!(covering_range(130_000, 430_000, 300_000), (0, 600_000));
+        assert_eq!(
+            covering_range(300_000, 599_999, 300_000),
+            (300_000, 600_000)
+        );
+    }
+}
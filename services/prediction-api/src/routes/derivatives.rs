@@ -0,0 +1,139 @@
+//! Perpetual futures funding rates and open interest.
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db;
+use crate::envelope::EnvelopeQuery;
+use crate::error::ApiError;
+use crate::routes::history::validate_range;
+use crate::routes::predictions::validate_pair;
+use crate::state::AppState;
+
+/// Most rows a single request may return.
+const MAX_DERIVATIVES_ROWS: usize = 50_000;
+
+/// Query parameters for a pair's funding rates or open interest.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct DerivativesQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Start of the range, inclusive (ms)
+    pub from_ts_ms: i64,
+    /// End of the range, inclusive (ms)
+    pub to_ts_ms: i64,
+}
+
+/// Funding rate a perpetual paid at one funding time.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FundingRate {
+    /// Funding time (ms)
+    pub ts_ms: i64,
+    /// Rate paid by longs to shorts, as a fraction (negative when shorts pay)
+    pub funding_rate: f64,
+    /// Mark price at funding time; absent where the exchange has none
+    pub mark_price: Option<f64>,
+}
+
+/// Open interest of a perpetual at one point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct OpenInterest {
+    /// Exchange time of the reading (ms)
+    pub ts_ms: i64,
+    /// Open contracts, in the base asset
+    pub open_interest: f64,
+}
+
+/// Get a pair's funding rates within a time range.
+///
+/// Collected for the pairs in `DERIVATIVES_PAIRS`, one row per funding
+/// time, oldest first.
+#[utoipa::path(
+    get,
+    path = "/funding-rates",
+    params(DerivativesQuery, EnvelopeQuery),
+    responses(
+        (status = 200, description = "Funding rates in the range, oldest first", body = Vec<FundingRate>),
+        (status = 400, description = "Invalid request or too many rows")
+    ),
+    tag = "prices"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_funding_rates(
+    State(state): State<AppState>,
+    Query(params): Query<DerivativesQuery>,
+) -> Result<Json<Vec<FundingRate>>, ApiError> {
+    validate_query(&params, state.config.max_history_range_ms)?;
+
+    tracing::info!(pair = %params.pair, "Fetching funding rates");
+
+    let rates = db::get_funding_rates(
+        &state.pool,
+        &params.pair,
+        params.from_ts_ms,
+        params.to_ts_ms,
+        MAX_DERIVATIVES_ROWS as i64 + 1,
+    )
+    .await?;
+    check_rows(rates.len())?;
+
+    tracing::debug!(count = rates.len(), "Funding rates fetched");
+
+    Ok(Json(rates))
+}
+
+/// Get a pair's open interest within a time range.
+///
+/// Read every `DERIVATIVES_INTERVAL_MS` for the pairs in
+/// `DERIVATIVES_PAIRS`, oldest first.
+#[utoipa::path(
+    get,
+    path = "/open-interest",
+    params(DerivativesQuery, EnvelopeQuery),
+    responses(
+        (status = 200, description = "Open interest in the range, oldest first", body = Vec<OpenInterest>),
+        (status = 400, description = "Invalid request or too many rows")
+    ),
+    tag = "prices"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_open_interest(
+    State(state): State<AppState>,
+    Query(params): Query<DerivativesQuery>,
+) -> Result<Json<Vec<OpenInterest>>, ApiError> {
+    validate_query(&params, state.config.max_history_range_ms)?;
+
+    tracing::info!(pair = %params.pair, "Fetching open interest");
+
+    let readings = db::get_open_interest(
+        &state.pool,
+        &params.pair,
+        params.from_ts_ms,
+        params.to_ts_ms,
+        MAX_DERIVATIVES_ROWS as i64 + 1,
+    )
+    .await?;
+    check_rows(readings.len())?;
+
+    tracing::debug!(count = readings.len(), "Open interest fetched");
+
+    Ok(Json(readings))
+}
+
+fn validate_query(params: &DerivativesQuery, max_range_ms: i64) -> Result<(), ApiError> {
+    validate_pair(&params.pair)?;
+    validate_range(params.from_ts_ms, params.to_ts_ms, max_range_ms)
+}
+
+fn check_rows(count: usize) -> Result<(), ApiError> {
+    if count > MAX_DERIVATIVES_ROWS {
+        return Err(ApiError::BadRequest(format!(
+            "result exceeds {MAX_DERIVATIVES_ROWS} rows; narrow the range"
+        )));
+    }
+    Ok(())
+}
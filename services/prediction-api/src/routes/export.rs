@@ -0,0 +1,293 @@
+//! Bulk prediction exports, streamed from the database as they are read.
+//!
+//! Exports are ordered by `(ts_ms, pair, model_name)` like history pages, so
+//! an interrupted download can be resumed with `after` set to the last
+//! complete row instead of starting over.
+//!
+//! Each export holds a database connection for as long as its client takes
+//! to download it, so at most `EXPORT_CONCURRENCY` run at once; more are
+//! answered 503 rather than left to drain the pool other requests need.
+
+use std::borrow::Cow;
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures_util::{stream, StreamExt};
+use serde::Deserialize;
+use tokio::sync::OwnedSemaphorePermit;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db::{self, ModelFilter};
+use crate::error::ApiError;
+use crate::pagination::Cursor;
+use crate::routes::history::validate_range;
+use crate::routes::predictions::{
+    parse_horizon, validate_model_name, validate_model_version, validate_pair, Prediction,
+};
+use crate::state::AppState;
+use crate::timestamp::{self, TimestampQuery};
+
+/// Query parameters for a prediction export.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct ExportQuery {
+    /// Trading pair (e.g., "BTCUSDT"); all pairs when omitted
+    pub pair: Option<String>,
+    /// Only include predictions from this model
+    pub model_name: Option<String>,
+    /// Only include predictions from this model version
+    pub model_version: Option<String>,
+    /// Only include predictions for this horizon, e.g. "4h"
+    pub horizon: Option<String>,
+    /// Start of the range, inclusive (ms)
+    pub from_ts_ms: i64,
+    /// End of the range, inclusive (ms)
+    pub to_ts_ms: i64,
+    /// Resume after this row, given as `<ts_ms>:<pair>:<model_name>` of the
+    /// last complete row received
+    pub after: Option<String>,
+}
+
+impl ExportQuery {
+    /// Validate the query parameters.
+    pub fn validate(&self, max_range_ms: i64) -> Result<(), ApiError> {
+        if let Some(pair) = &self.pair {
+            validate_pair(pair)?;
+        }
+        if let Some(model_name) = &self.model_name {
+            validate_model_name(model_name)?;
+        }
+        if let Some(model_version) = &self.model_version {
+            validate_model_version(model_version)?;
+        }
+        parse_horizon(self.horizon.as_deref())?;
+        self.after()?;
+        validate_range(self.from_ts_ms, self.to_ts_ms, max_range_ms)
+    }
+
+    /// The row to resume after, if any.
+    pub fn after(&self) -> Result<Option<Cursor>, ApiError> {
+        self.after.as_deref().map(str::parse).transpose()
+    }
+
+    /// The model filter selected by the query.
+    pub fn model(&self) -> Result<ModelFilter<'_>, ApiError> {
+        Ok(ModelFilter {
+            name: self.model_name.as_deref(),
+            version: self.model_version.as_deref(),
+            horizon_ms: parse_horizon(self.horizon.as_deref())?,
+        })
+    }
+
+    /// Download file name, e.g. `predictions-BTCUSDT-1700000000000-1700086400000.csv`.
+    fn filename(&self, extension: &str) -> String {
+        match &self.pair {
+            Some(pair) => format!(
+                "predictions-{}-{}-{}.{}",
+                pair, self.from_ts_ms, self.to_ts_ms, extension
+            ),
+            None => format!(
+                "predictions-{}-{}.{}",
+                self.from_ts_ms, self.to_ts_ms, extension
+            ),
+        }
+    }
+}
+
+/// Content type of an NDJSON export.
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Column names of a CSV export.
+const CSV_HEADER: &str = "pair,model_name,model_version,ts_ms,ts_iso,predicted_ts_ms,\
+                          predicted_ts_iso,horizon_ms,predicted_price,lower_bound,upper_bound,\
+                          quantile\n";
+
+/// One CSV line for a prediction. Missing values, and the price of an
+/// invalid prediction, are left empty.
+fn csv_row(p: &Prediction) -> String {
+    fn opt<T: ToString>(value: Option<T>) -> String {
+        value.map(|v| v.to_string()).unwrap_or_default()
+    }
+
+    [
+        csv_field(&p.pair),
+        csv_field(&p.model_name),
+        csv_field(&p.model_version),
+        p.ts_ms.to_string().into(),
+        timestamp::to_iso8601(p.ts_ms).into(),
+        opt(p.predicted_ts_ms).into(),
+        opt(p.predicted_ts_ms.map(timestamp::to_iso8601)).into(),
+        opt(p.horizon_ms).into(),
+        opt(p.valid.then_some(p.predicted_price)).into(),
+        opt(p.lower_bound).into(),
+        opt(p.upper_bound).into(),
+        opt(p.quantile).into(),
+    ]
+    .join(",")
+        + "\n"
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+/// Take a turn to export, or answer 503 when `EXPORT_CONCURRENCY` exports
+/// are running.
+fn export_permit(state: &AppState) -> Result<OwnedSemaphorePermit, ApiError> {
+    state.exports.clone().try_acquire_owned().map_err(|_| {
+        tracing::warn!("Too many exports running, rejecting");
+        ApiError::Overloaded(state.config.load_shed_retry_after_secs)
+    })
+}
+
+/// Export predictions in a time range as CSV.
+///
+/// Rows are streamed oldest first as they are read, so exports of any size
+/// run in constant memory. Timestamps are given both as epoch milliseconds
+/// and as ISO-8601 (UTC). If the database fails mid-export the connection
+/// is closed without the final chunk, so a truncated file is never mistaken
+/// for a complete one.
+///
+/// A resumed export (`after`) omits the header line, so it can be appended
+/// to the partial file as is.
+#[utoipa::path(
+    get,
+    path = "/predictions/export.csv",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Predictions in the range, oldest first", content_type = "text/csv", body = String,
+            headers(("Content-Disposition" = String, description = "Attachment file name"))),
+        (status = 400, description = "Invalid request"),
+        (status = 503, description = "Too many exports running; retry after Retry-After seconds")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn export_csv(
+    State(state): State<AppState>,
+    Query(params): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    params.validate(state.config.max_history_range_ms)?;
+    let permit = export_permit(&state)?;
+
+    tracing::info!(
+        pair = ?params.pair,
+        from_ts_ms = params.from_ts_ms,
+        to_ts_ms = params.to_ts_ms,
+        after = ?params.after,
+        "Exporting predictions as CSV"
+    );
+
+    let filename = params.filename("csv");
+    let header = params.after.is_none().then(|| Ok(CSV_HEADER.to_string()));
+    let rows =
+        db::stream_history(state.pool.clone(), params, permit).map(|row| row.map(|p| csv_row(&p)));
+    let body = Body::from_stream(stream::iter(header).chain(rows));
+
+    Ok(attachment("text/csv; charset=utf-8", &filename, body))
+}
+
+/// Export predictions in a time range as newline-delimited JSON.
+///
+/// Each line is one prediction, serialized as the other prediction
+/// endpoints serialize it (`ts_format` applies). Rows are streamed oldest
+/// first as they are read, so exports of any size run in constant memory;
+/// a database failure mid-export closes the connection early, as for CSV.
+/// `after` resumes an interrupted export, as for CSV.
+#[utoipa::path(
+    get,
+    path = "/predictions/export.ndjson",
+    params(ExportQuery, TimestampQuery),
+    responses(
+        (status = 200, description = "Predictions in the range, oldest first, one JSON object per line",
+            content_type = "application/x-ndjson", body = Prediction,
+            headers(("Content-Disposition" = String, description = "Attachment file name"))),
+        (status = 400, description = "Invalid request"),
+        (status = 503, description = "Too many exports running; retry after Retry-After seconds")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn export_ndjson(
+    State(state): State<AppState>,
+    Query(params): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    params.validate(state.config.max_history_range_ms)?;
+    let permit = export_permit(&state)?;
+
+    tracing::info!(
+        pair = ?params.pair,
+        from_ts_ms = params.from_ts_ms,
+        to_ts_ms = params.to_ts_ms,
+        after = ?params.after,
+        "Exporting predictions as NDJSON"
+    );
+
+    // The body is serialized after the handler returns, outside the
+    // request's timestamp format scope
+    let format = timestamp::current();
+    let filename = params.filename("ndjson");
+    let rows = db::stream_history(state.pool.clone(), params, permit).map(move |row| {
+        row.and_then(|p| {
+            timestamp::in_format(format, || serde_json::to_string(&p))
+                .map(|line| line + "\n")
+                .map_err(|_| ApiError::Internal)
+        })
+    });
+
+    Ok(attachment(
+        NDJSON_CONTENT_TYPE,
+        &filename,
+        Body::from_stream(rows),
+    ))
+}
+
+/// A streamed download response.
+fn attachment(content_type: &'static str, filename: &str, body: Body) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        assert_eq!(csv_field("lgbm"), "lgbm");
+        assert_eq!(csv_field("v1,rc"), "\"v1,rc\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn formats_csv_rows() {
+        let prediction = Prediction {
+            predicted_price: 65000.5,
+            quantile: Some(0.5),
+            ..Prediction::sample("BTCUSDT", 1_700_000_000_000)
+        };
+
+        assert_eq!(CSV_HEADER.split(',').count(), 12);
+        assert_eq!(
+            csv_row(&prediction),
+            "BTCUSDT,lgbm,v1,1700000000000,2023-11-14T22:13:20.000Z,,,,65000.5,,,0.5\n"
+        );
+    }
+}
@@ -1,9 +1,11 @@
 //! Health check endpoint.
 
-use axum::Json;
+use axum::{extract::State, http::StatusCode, Json};
 use serde::Serialize;
 use utoipa::ToSchema;
 
+use crate::state::AppState;
+
 /// Health check response.
 #[derive(Serialize, ToSchema)]
 pub struct HealthResponse {
@@ -26,3 +28,54 @@ pub async fn health() -> Json<HealthResponse> {
         status: "healthy".to_string(),
     })
 }
+
+/// Readiness check response.
+#[derive(Serialize, ToSchema)]
+pub struct ReadyResponse {
+    pub status: String,
+    /// Why the service is not ready
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Readiness check endpoint.
+///
+/// Returns 503 while the service runs in degraded mode (e.g. the
+/// `predictions` or `models` table is missing columns) or the database is
+/// unreachable.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    responses(
+        (status = 200, description = "Service is ready", body = ReadyResponse),
+        (status = 503, description = "Service is not ready", body = ReadyResponse)
+    ),
+    tag = "health"
+)]
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<ReadyResponse>) {
+    let reason = match &state.degraded {
+        Some(reason) => Some(reason.to_string()),
+        None => sqlx::query("SELECT 1")
+            .execute(&state.pool)
+            .await
+            .err()
+            .map(|e| format!("database unreachable: {}", e)),
+    };
+
+    match reason {
+        None => (
+            StatusCode::OK,
+            Json(ReadyResponse {
+                status: "ready".to_string(),
+                reason: None,
+            }),
+        ),
+        Some(reason) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ReadyResponse {
+                status: "degraded".to_string(),
+                reason: Some(reason),
+            }),
+        ),
+    }
+}
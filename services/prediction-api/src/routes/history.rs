@@ -0,0 +1,321 @@
+//! Prediction history endpoints.
+
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db::{self, ModelFilter};
+use crate::envelope::EnvelopeQuery;
+use crate::error::ApiError;
+use crate::pagination::{page_size, Page};
+use crate::routes::predictions::{
+    parse_horizon, validate_max_interval_pct, validate_model_name, validate_model_version,
+    validate_pair, Prediction,
+};
+use crate::state::AppState;
+use crate::timestamp::TimestampQuery;
+
+/// Most pairs a single batch request may ask for.
+pub const MAX_BATCH_PAIRS: usize = 50;
+
+/// Most rows a single batch request may return across all pairs.
+const MAX_BATCH_ROWS: usize = 50_000;
+
+/// Query parameters for paging through prediction history.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct HistoryQuery {
+    /// Trading pair (e.g., "BTCUSDT"); all pairs when omitted
+    pub pair: Option<String>,
+    /// Only include predictions from this model
+    pub model_name: Option<String>,
+    /// Only include predictions from this model version
+    pub model_version: Option<String>,
+    /// Only include predictions for this horizon, e.g. "4h"
+    pub horizon: Option<String>,
+    /// Start of the range, inclusive (ms)
+    pub from_ts_ms: i64,
+    /// End of the range, inclusive (ms)
+    pub to_ts_ms: i64,
+    /// Page size (default 1000, max 10000)
+    pub limit: Option<usize>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+    /// Only include predictions whose interval (`upper_bound -
+    /// lower_bound`) is at most this percentage of `predicted_price`
+    pub max_interval_pct: Option<f64>,
+}
+
+impl HistoryQuery {
+    /// Validate the query parameters.
+    pub fn validate(&self, max_range_ms: i64) -> Result<(), ApiError> {
+        if let Some(pair) = &self.pair {
+            validate_pair(pair)?;
+        }
+        if let Some(model_name) = &self.model_name {
+            validate_model_name(model_name)?;
+        }
+        if let Some(model_version) = &self.model_version {
+            validate_model_version(model_version)?;
+        }
+        parse_horizon(self.horizon.as_deref())?;
+        validate_max_interval_pct(self.max_interval_pct)?;
+        validate_range(self.from_ts_ms, self.to_ts_ms, max_range_ms)
+    }
+}
+
+/// Default number of predictions for `/predictions/recent`.
+const DEFAULT_RECENT: u32 = 50;
+
+/// Query parameters for a pair's most recent predictions.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct RecentQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// How many predictions to return (default 50, capped by
+    /// `MAX_RECENT_PREDICTIONS`)
+    pub n: Option<u32>,
+    /// Only include predictions whose interval (`upper_bound -
+    /// lower_bound`) is at most this percentage of `predicted_price`
+    pub max_interval_pct: Option<f64>,
+}
+
+impl RecentQuery {
+    /// Validate the query parameters.
+    pub fn validate(&self, max_n: u32) -> Result<(), ApiError> {
+        validate_pair(&self.pair)?;
+        validate_max_interval_pct(self.max_interval_pct)?;
+        match self.n {
+            Some(0) => Err(ApiError::BadRequest("n must be positive".to_string())),
+            Some(n) if n > max_n => Err(ApiError::BadRequest(format!("n cannot exceed {max_n}"))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Request body for fetching history of several pairs at once.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct HistoryBatchRequest {
+    /// Trading pairs (e.g., ["BTCUSDT", "ETHUSDT"])
+    pub pairs: Vec<String>,
+    /// Start of the range, inclusive (ms)
+    pub from_ts_ms: i64,
+    /// End of the range, inclusive (ms)
+    pub to_ts_ms: i64,
+}
+
+impl HistoryBatchRequest {
+    /// Validate the request body.
+    pub fn validate(&self, max_range_ms: i64) -> Result<(), ApiError> {
+        if self.pairs.is_empty() {
+            return Err(ApiError::BadRequest("pairs cannot be empty".to_string()));
+        }
+        if self.pairs.len() > MAX_BATCH_PAIRS {
+            return Err(ApiError::BadRequest(format!(
+                "at most {MAX_BATCH_PAIRS} pairs per request"
+            )));
+        }
+        for pair in &self.pairs {
+            validate_pair(pair)?;
+        }
+        validate_range(self.from_ts_ms, self.to_ts_ms, max_range_ms)
+    }
+}
+
+/// Validate a `[from_ts_ms, to_ts_ms]` history range.
+///
+/// Ranges wider than `max_range_ms` are rejected rather than truncated, so
+/// clients never mistake a partial result for the full one.
+pub fn validate_range(from_ts_ms: i64, to_ts_ms: i64, max_range_ms: i64) -> Result<(), ApiError> {
+    if from_ts_ms > to_ts_ms {
+        return Err(ApiError::BadRequest(
+            "from_ts_ms must not be after to_ts_ms".to_string(),
+        ));
+    }
+    let requested_ms = to_ts_ms.saturating_sub(from_ts_ms);
+    if requested_ms > max_range_ms {
+        return Err(ApiError::BadRequest(format!(
+            "requested range of {requested_ms} ms exceeds the maximum of {max_range_ms} ms"
+        )));
+    }
+    Ok(())
+}
+
+/// Page through prediction history.
+///
+/// Returns predictions in the range ordered by `ts_ms`, then pair and model
+/// name, for one pair or all of them, optionally from one model, model
+/// version and/or horizon, and with intervals within `max_interval_pct`. While `next_cursor` is non-null, pass
+/// it back as `cursor` to fetch the next page.
+#[utoipa::path(
+    get,
+    path = "/predictions/history",
+    params(HistoryQuery, EnvelopeQuery, TimestampQuery),
+    responses(
+        (status = 200, description = "One page of predictions", body = Page<Prediction>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_history(
+    State(state): State<AppState>,
+    Query(params): Query<HistoryQuery>,
+) -> Result<Json<Page<Prediction>>, ApiError> {
+    params.validate(state.config.max_history_range_ms)?;
+    let limit = page_size(params.limit)?;
+    let after = params.cursor.as_deref().map(str::parse).transpose()?;
+
+    tracing::info!(pair = ?params.pair, "Fetching history page");
+
+    let page = db::get_history_page(
+        &state.pool,
+        params.pair.as_deref(),
+        ModelFilter {
+            name: params.model_name.as_deref(),
+            version: params.model_version.as_deref(),
+            horizon_ms: parse_horizon(params.horizon.as_deref())?,
+        },
+        params.max_interval_pct,
+        params.from_ts_ms..=params.to_ts_ms,
+        after,
+        limit,
+    )
+    .await?;
+
+    tracing::debug!(count = page.items.len(), "History page fetched");
+
+    Ok(Json(page))
+}
+
+/// Get a pair's most recent predictions.
+///
+/// Returns the `n` newest predictions, oldest first, ready to plot as a
+/// sparkline without choosing a time range.
+#[utoipa::path(
+    get,
+    path = "/predictions/recent",
+    params(RecentQuery, EnvelopeQuery, TimestampQuery),
+    responses(
+        (status = 200, description = "Most recent predictions, oldest first", body = Vec<Prediction>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_recent(
+    State(state): State<AppState>,
+    Query(params): Query<RecentQuery>,
+) -> Result<Json<Vec<Prediction>>, ApiError> {
+    let max_n = state.config.max_recent_predictions;
+    params.validate(max_n)?;
+    let n = params.n.unwrap_or(DEFAULT_RECENT).min(max_n);
+
+    tracing::info!(pair = %params.pair, n, "Fetching recent predictions");
+
+    let predictions =
+        db::get_recent_predictions(&state.pool, &params.pair, params.max_interval_pct, n.into())
+            .await?;
+
+    tracing::debug!(count = predictions.len(), "Recent predictions fetched");
+
+    Ok(Json(predictions))
+}
+
+/// Get prediction history for several trading pairs.
+///
+/// Returns a map of pair to its predictions in the range, oldest first.
+/// Requested pairs without predictions map to an empty array. Requests whose
+/// result would exceed the row cap are rejected rather than truncated.
+#[utoipa::path(
+    post,
+    path = "/predictions/history/batch",
+    request_body = HistoryBatchRequest,
+    responses(
+        (status = 200, description = "History per pair", body = BTreeMap<String, Vec<Prediction>>),
+        (status = 400, description = "Invalid request or result too large")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_history_batch(
+    State(state): State<AppState>,
+    Json(request): Json<HistoryBatchRequest>,
+) -> Result<Json<BTreeMap<String, Vec<Prediction>>>, ApiError> {
+    request.validate(state.config.max_history_range_ms)?;
+
+    tracing::info!(pairs = request.pairs.len(), "Fetching batch history");
+
+    let rows = db::get_history_for_pairs(
+        &state.pool,
+        &request.pairs,
+        request.from_ts_ms,
+        request.to_ts_ms,
+        MAX_BATCH_ROWS as i64 + 1,
+    )
+    .await?;
+
+    if rows.len() > MAX_BATCH_ROWS {
+        return Err(ApiError::BadRequest(format!(
+            "result exceeds {MAX_BATCH_ROWS} rows; narrow the range or request fewer pairs"
+        )));
+    }
+
+    let mut history: BTreeMap<String, Vec<Prediction>> = request
+        .pairs
+        .into_iter()
+        .map(|pair| (pair, Vec::new()))
+        .collect();
+    for prediction in rows {
+        history
+            .entry(prediction.pair.clone())
+            .or_default()
+            .push(prediction);
+    }
+
+    tracing::debug!(pairs = history.len(), "Batch history fetched");
+
+    Ok(Json(history))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::to_bytes, http::StatusCode, response::IntoResponse};
+
+    use super::*;
+
+    const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+    #[test]
+    fn accepts_range_at_the_limit() {
+        assert!(validate_range(0, 90 * DAY_MS, 90 * DAY_MS).is_ok());
+    }
+
+    #[test]
+    fn rejects_inverted_range() {
+        assert!(matches!(
+            validate_range(10, 0, 90 * DAY_MS),
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn too_wide_range_reports_max_and_requested() {
+        let err = validate_range(0, 91 * DAY_MS, 90 * DAY_MS).unwrap_err();
+
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(payload["code"], "VALIDATION_FAILED");
+        assert_eq!(
+            payload["detail"],
+            "requested range of 7862400000 ms exceeds the maximum of 7776000000 ms"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Root endpoint.
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::{self, ModelFilter};
+use crate::error::ApiError;
+use crate::routes::predictions::{attach_current_prices, Prediction};
+use crate::state::AppState;
+
+/// Endpoints listed by `GET /` when no default pair is configured.
+const ENDPOINTS: &[&str] = &[
+    "/v1/health",
+    "/v1/ready",
+    "/v1/pairs",
+    "/v1/pairs/{pair}/aliases",
+    "/v1/predictions?pair={pair}",
+    "/v1/predictions/latest",
+    "/v1/predictions/batch",
+    "/v1/predictions/compare?pair={pair}&models={model},{model}",
+    "/v1/predictions/recent?pair={pair}&n={n}",
+    "/v1/predictions/stream?pair={pair}",
+    "/v1/predictions/schema",
+    "/v1/predictions/schema.proto",
+    "/v1/models",
+    "/v1/models/{model_name}/predictions",
+    "/v1/models/{model_name}/predictions/{pair}",
+    "/v1/predictions/history?from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/predictions/history/batch",
+    "/v1/predictions/export.csv?from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/predictions/export.ndjson?from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/predictions/stats?pair={pair}&window={window}",
+    "/v1/predictions/downsample?pair={pair}&bucket={bucket}&from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/predictions/evaluated?pair={pair}&from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/prices?pair={pair}&from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/prices/gaps?pair={pair}&from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/orderbook?pair={pair}",
+    "/v1/funding-rates?pair={pair}&from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/open-interest?pair={pair}&from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/candles?pair={pair}&interval={interval}&from_ts_ms={from}&to_ts_ms={to}",
+    "/v1/metrics/accuracy?pair={pair}&window={window}",
+    "/v1/metrics/accuracy/rolling?pair={pair}&window={window}&step={step}",
+    "/v1/metrics/hit-rate?window={window}",
+    "/v1/metrics/errors/histogram?pair={pair}&window={window}",
+    #[cfg(feature = "swagger")]
+    "/docs",
+];
+
+/// Index of available endpoints.
+#[derive(Serialize, ToSchema)]
+pub struct IndexResponse {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub endpoints: &'static [&'static str],
+}
+
+/// Latest prediction for `DEFAULT_PAIR`, or an endpoint index when unset.
+#[utoipa::path(
+    get,
+    path = "/",
+    responses(
+        (status = 200, description = "Default pair's prediction, or the endpoint index", body = IndexResponse),
+        (status = 404, description = "No prediction for the default pair")
+    ),
+    tag = "predictions"
+)]
+pub async fn index(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let Some(pair) = state.config.default_pair.as_deref() else {
+        return Ok(Json(IndexResponse {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            endpoints: ENDPOINTS,
+        })
+        .into_response());
+    };
+
+    match db::get_latest_prediction(&state.pool, pair, ModelFilter::default(), None, None).await? {
+        Some(mut p) => {
+            attach_current_prices(&state, std::slice::from_mut(&mut p)).await;
+            Ok(Json::<Prediction>(p).into_response())
+        }
+        None => Err(ApiError::NotFound(pair.to_string())),
+    }
+}
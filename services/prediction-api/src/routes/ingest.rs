@@ -0,0 +1,802 @@
+//! Prediction ingestion for model services.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use utoipa::{IntoParams, PartialSchema, ToSchema};
+
+use crate::db;
+use crate::error::{ApiError, FieldError};
+use crate::protobuf::{JsonOrProtobuf, PREDICTION_PROTO};
+use crate::routes::pairs::canonical_pair;
+use crate::routes::predictions::{
+    validate_model_name, validate_model_version, validate_pair, Prediction,
+};
+use crate::routes::rejected::RejectedPrediction;
+use crate::routes::webhooks::new_id;
+use crate::state::AppState;
+use crate::timestamp;
+use crate::API_PREFIX;
+
+/// How far ahead of the server clock `ts_ms` may be, to allow for clock
+/// skew between model hosts and this service.
+const MAX_CLOCK_SKEW_MS: i64 = 60_000;
+
+/// Most predictions accepted per bulk request.
+pub const MAX_BULK_PREDICTIONS: usize = 50_000;
+
+/// Largest bulk request body accepted, sized for `MAX_BULK_PREDICTIONS`
+/// fully populated rows.
+pub const MAX_BULK_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// JSON Schema dialect of the published prediction schema.
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// A prediction submitted by a model service.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewPrediction {
+    /// Trading pair (e.g., "BTCUSDT"), or an exchange-native symbol for it
+    #[schema(min_length = 1)]
+    pub pair: String,
+    /// Model name
+    #[schema(min_length = 1, max_length = 64, pattern = r"^[A-Za-z0-9_.-]+$")]
+    pub model_name: String,
+    /// Model version
+    #[schema(min_length = 1, max_length = 64, pattern = r"^[A-Za-z0-9_.-]+$")]
+    pub model_version: String,
+    /// When the prediction was made (ms); at most a minute ahead of the
+    /// server clock
+    #[schema(minimum = 1)]
+    pub ts_ms: i64,
+    /// Timestamp for which the price is predicted (ms), after `ts_ms`; omit
+    /// for a current fair value
+    #[schema(minimum = 1)]
+    pub predicted_ts_ms: Option<i64>,
+    /// Predicted price; within `lower_bound` and `upper_bound` when given
+    #[schema(exclusive_minimum = 0.0)]
+    pub predicted_price: f64,
+    /// Lower end of the prediction interval, for probabilistic models
+    #[schema(exclusive_minimum = 0.0)]
+    pub lower_bound: Option<f64>,
+    /// Upper end of the prediction interval, for probabilistic models; not
+    /// below `lower_bound`
+    #[schema(exclusive_minimum = 0.0)]
+    pub upper_bound: Option<f64>,
+    /// Quantile level `predicted_price` represents, e.g. 0.5 for a median
+    #[schema(exclusive_minimum = 0.0, exclusive_maximum = 1.0)]
+    pub quantile: Option<f64>,
+}
+
+/// What a write does when it collides with stored predictions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OnConflict {
+    /// Keep the stored prediction and reject the new one
+    #[default]
+    Reject,
+    /// Replace the stored predictions: any with the same key, and earlier
+    /// runs of the same model version for the same pair and target time
+    Replace,
+}
+
+/// Query parameters for prediction writes.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct WriteQuery {
+    /// What to do with stored predictions the write collides with (default
+    /// `reject`)
+    pub on_conflict: Option<OnConflict>,
+}
+
+impl WriteQuery {
+    /// Whether stored predictions are replaced.
+    pub fn replace(&self) -> bool {
+        self.on_conflict == Some(OnConflict::Replace)
+    }
+}
+
+/// Request body for bulk ingestion.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BulkRequest {
+    /// Predictions to store (at most 50000)
+    pub predictions: Vec<NewPrediction>,
+}
+
+/// What happened to one row of a bulk request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    /// The prediction was stored
+    Created,
+    /// The prediction was stored in place of earlier ones
+    /// (`on_conflict=replace`)
+    Replaced,
+    /// The prediction failed validation
+    Invalid,
+    /// A prediction with the same key exists or appears earlier in the
+    /// request, or with `on_conflict=replace` a later run for the same
+    /// target does
+    Duplicate,
+}
+
+/// Outcome of one row of a bulk request.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RowResult {
+    pub status: RowStatus,
+    /// Why the row was not stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Every field that failed validation, for `invalid` rows
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+/// Response of a bulk request.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkResponse {
+    /// Rows stored, including replacements
+    pub created: usize,
+    /// Rows stored in place of earlier ones
+    pub replaced: usize,
+    /// Rows not stored
+    pub failed: usize,
+    /// One result per submitted prediction, in request order
+    pub results: Vec<RowResult>,
+}
+
+/// Key a prediction is stored under.
+pub type PredictionKey = (String, i64, String);
+
+impl NewPrediction {
+    /// Key the prediction is stored under.
+    pub fn key(&self) -> PredictionKey {
+        (self.pair.clone(), self.ts_ms, self.model_name.clone())
+    }
+
+    /// What identifies a run of the model for a target time, which a
+    /// replacing write supersedes; `None` for current fair values.
+    pub fn target(&self) -> Option<(&str, &str, &str, i64)> {
+        let predicted_ts_ms = self.predicted_ts_ms?;
+        Some((
+            &self.pair,
+            &self.model_name,
+            &self.model_version,
+            predicted_ts_ms,
+        ))
+    }
+
+    /// Validate the prediction against `now_ms`, reporting every field
+    /// that fails rather than only the first.
+    pub fn validate(&self, now_ms: i64) -> Result<(), ApiError> {
+        let mut errors: Vec<FieldError> = [
+            ("pair", validate_pair(&self.pair)),
+            ("model_name", validate_model_name(&self.model_name)),
+            ("model_version", validate_model_version(&self.model_version)),
+        ]
+        .into_iter()
+        .filter_map(|(field, result)| field_error(field, result))
+        .collect();
+
+        if self.ts_ms <= 0 {
+            errors.push(FieldError::new("ts_ms", "must be positive"));
+        } else if self.ts_ms > now_ms.saturating_add(MAX_CLOCK_SKEW_MS) {
+            errors.push(FieldError::new("ts_ms", "cannot be in the future"));
+        }
+        if self
+            .predicted_ts_ms
+            .is_some_and(|target| target <= self.ts_ms)
+        {
+            errors.push(FieldError::new("predicted_ts_ms", "must be after ts_ms"));
+        }
+
+        let mut prices_valid = true;
+        for (field, value) in [
+            ("predicted_price", Some(self.predicted_price)),
+            ("lower_bound", self.lower_bound),
+            ("upper_bound", self.upper_bound),
+        ] {
+            if value.is_some_and(|v| !v.is_finite() || v <= 0.0) {
+                errors.push(FieldError::new(field, "must be a positive number"));
+                prices_valid = false;
+            }
+        }
+        if prices_valid {
+            if let (Some(lower), Some(upper)) = (self.lower_bound, self.upper_bound) {
+                if lower > upper {
+                    errors.push(FieldError::new("lower_bound", "cannot exceed upper_bound"));
+                }
+            }
+            if self
+                .lower_bound
+                .is_some_and(|lower| self.predicted_price < lower)
+                || self
+                    .upper_bound
+                    .is_some_and(|upper| self.predicted_price > upper)
+            {
+                errors.push(FieldError::new(
+                    "predicted_price",
+                    "must lie within lower_bound and upper_bound",
+                ));
+            }
+        }
+        if self.quantile.is_some_and(|q| !(q > 0.0 && q < 1.0)) {
+            errors.push(FieldError::new("quantile", "must be between 0 and 1"));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::InvalidFields(errors))
+        }
+    }
+}
+
+/// The error of a shared field validator as a field error, without the
+/// field name its messages start with.
+fn field_error(field: &'static str, result: Result<(), ApiError>) -> Option<FieldError> {
+    match result {
+        Ok(()) => None,
+        Err(ApiError::BadRequest(msg)) => {
+            let message = msg
+                .strip_prefix(field)
+                .map_or(msg.as_str(), str::trim_start);
+            Some(FieldError::new(field, message))
+        }
+        Err(e) => Some(FieldError::new(field, e.to_string())),
+    }
+}
+
+/// JSON Schema of a prediction, as the ingestion endpoints accept it.
+///
+/// Describes every field with its type and range, so model services can
+/// check rows before sending them. Rules that relate fields to each other
+/// or to the server clock (`predicted_ts_ms` after `ts_ms`, the interval
+/// containing `predicted_price`, `ts_ms` not in the future) are given in
+/// the field descriptions and enforced on ingestion. Predictions failing
+/// validation are answered with a 400 listing every invalid field under
+/// `errors`.
+#[utoipa::path(
+    get,
+    path = "/predictions/schema",
+    responses(
+        (status = 200, description = "JSON Schema (draft 2020-12) of a prediction", body = Object)
+    ),
+    tag = "ingestion"
+)]
+pub async fn get_prediction_schema() -> Json<serde_json::Value> {
+    Json(prediction_schema())
+}
+
+/// Protobuf schema of the ingestion endpoints' bodies.
+///
+/// Bodies sent as `application/x-protobuf` are the `NewPrediction` and
+/// `BulkRequest` messages of this file. They are validated like JSON
+/// bodies, and responses are JSON either way. Unknown fields are ignored.
+#[utoipa::path(
+    get,
+    path = "/predictions/schema.proto",
+    responses(
+        (status = 200, description = "The .proto file", body = String, content_type = "text/plain")
+    ),
+    tag = "ingestion"
+)]
+pub async fn get_prediction_proto() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        PREDICTION_PROTO,
+    )
+}
+
+fn prediction_schema() -> serde_json::Value {
+    let mut schema = serde_json::to_value(NewPrediction::schema()).unwrap_or_default();
+    if let Some(schema) = schema.as_object_mut() {
+        schema.insert("$schema".to_string(), json!(JSON_SCHEMA_DIALECT));
+        schema.insert("title".to_string(), json!("NewPrediction"));
+    }
+    schema
+}
+
+/// Store a prediction.
+///
+/// Model services submit predictions here instead of writing to the
+/// database, so every row is validated on the way in. Predictions are keyed
+/// by `(pair, ts_ms, model_name)`; submitting the same key twice is a 409.
+/// Requires `Authorization: Bearer <INGEST_API_KEY>`.
+///
+/// `pair` may be an exchange-native symbol such as `BTC-USDT` or `XBT/USD`:
+/// it is normalized and mapped through the pair aliases before validation,
+/// and the prediction is stored under the canonical pair.
+///
+/// With `on_conflict=replace`, the prediction replaces any with the same
+/// key, and earlier runs of the same model version for the same pair and
+/// `predicted_ts_ms`, so re-running a model job does not duplicate rows.
+/// The answer is then 200 instead of 201 when something was replaced. When
+/// runs for the same target are written concurrently, the one with the
+/// latest `ts_ms` is kept and the others get a 409, as does a run written
+/// after a later one.
+///
+/// Predictions that fail validation are kept for inspection and replay
+/// under `/admin/rejected-predictions`.
+///
+/// Send an `Idempotency-Key` to retry safely: a retry with the same key and
+/// body gets the original response back, marked `Idempotent-Replayed: true`.
+///
+/// The body may also be the protobuf `NewPrediction` message of
+/// `GET /predictions/schema.proto`, sent as `application/x-protobuf`.
+#[utoipa::path(
+    post,
+    path = "/predictions",
+    params(
+        WriteQuery,
+        ("Idempotency-Key" = Option<String>, Header,
+            description = "Retries with the same key get the first response back instead of being processed again")
+    ),
+    request_body(content(
+        (NewPrediction = "application/json"),
+        (NewPrediction = "application/x-protobuf")
+    )),
+    responses(
+        (status = 201, description = "Prediction stored", body = Prediction,
+            headers(("Location" = String, description = "Where the model's latest prediction for the pair is served"))),
+        (status = 200, description = "Prediction stored in place of earlier ones", body = Prediction,
+            headers(("Location" = String, description = "Where the model's latest prediction for the pair is served"))),
+        (status = 400, description = "Invalid prediction"),
+        (status = 401, description = "Missing or invalid ingest API key"),
+        (status = 409, description = "A prediction with the same pair, ts_ms and model_name exists, or with on_conflict=replace a later run for the same target")
+    ),
+    security(("ingest_api_key" = [])),
+    tag = "ingestion"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn create_prediction(
+    State(state): State<AppState>,
+    Query(params): Query<WriteQuery>,
+    JsonOrProtobuf(prediction): JsonOrProtobuf<NewPrediction>,
+) -> Result<Response, ApiError> {
+    ingest(&state, prediction, params.replace(), true).await
+}
+
+/// Map, validate and store one prediction, answering as `POST /predictions`
+/// does. With `keep_rejected`, a prediction that fails validation is kept
+/// as a rejected prediction.
+pub async fn ingest(
+    state: &AppState,
+    mut prediction: NewPrediction,
+    replace: bool,
+    keep_rejected: bool,
+) -> Result<Response, ApiError> {
+    let aliases = state.aliases.get().await?;
+    prediction.pair = canonical_pair(&aliases, &prediction.pair);
+    if let Err(e) = prediction.validate(timestamp::now_ms()) {
+        if keep_rejected {
+            keep_rejected_predictions(&state.pool, &[(&prediction, rejection_reason(&e))]).await;
+        }
+        return Err(e);
+    }
+
+    let Some((stored, replaced)) = db::insert_prediction(&state.pool, &prediction, replace).await?
+    else {
+        return Err(ApiError::Conflict(match prediction.predicted_ts_ms {
+            Some(predicted_ts_ms) if replace => format!(
+                "a later run of {} for {} at {} is stored",
+                prediction.model_name, prediction.pair, predicted_ts_ms
+            ),
+            _ => format!(
+                "prediction exists for {} at {} from {}",
+                prediction.pair, prediction.ts_ms, prediction.model_name
+            ),
+        }));
+    };
+
+    tracing::info!(
+        pair = %stored.pair,
+        ts_ms = stored.ts_ms,
+        model_name = %stored.model_name,
+        model_version = %stored.model_version,
+        replaced,
+        "Prediction ingested"
+    );
+    let location = format!(
+        "{}/models/{}/predictions/{}",
+        API_PREFIX, stored.model_name, stored.pair
+    );
+    let status = if replaced {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
+    Ok((status, [(header::LOCATION, location)], Json(stored)).into_response())
+}
+
+/// Store many predictions at once.
+///
+/// For batch jobs: the rows are validated one by one and the valid ones are
+/// inserted with multi-row `INSERT`s of 5000 rows. Invalid rows and
+/// duplicates of existing keys (or of earlier rows in the request) are
+/// skipped and reported in `results`, which lines up with `predictions`.
+/// With `on_conflict=replace`, only the run with the latest `ts_ms` of
+/// each target in the request is written, wherever it appears.
+/// Only a database failure fails the request as a whole. The rows inserted
+/// before it stay stored, as RisingWave has no transactions to roll them
+/// back, so a retry reports them as duplicates, or replaces them again with
+/// `on_conflict=replace`. Like `POST /predictions`, takes `on_conflict` and
+/// an `Idempotency-Key`.
+///
+/// The body may also be the protobuf `BulkRequest` message of
+/// `GET /predictions/schema.proto`, sent as `application/x-protobuf`.
+///
+/// Served at `/predictions/bulk`, as `POST /predictions/batch` reads the
+/// latest predictions for several pairs.
+#[utoipa::path(
+    post,
+    path = "/predictions/bulk",
+    params(
+        WriteQuery,
+        ("Idempotency-Key" = Option<String>, Header,
+            description = "Retries with the same key get the first response back instead of being processed again")
+    ),
+    request_body(content(
+        (BulkRequest = "application/json"),
+        (BulkRequest = "application/x-protobuf")
+    )),
+    responses(
+        (status = 200, description = "Per-row results", body = BulkResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid ingest API key"),
+        (status = 413, description = "Request body too large")
+    ),
+    security(("ingest_api_key" = [])),
+    tag = "ingestion"
+)]
+#[tracing::instrument(skip_all, fields(rows = request.predictions.len()))]
+pub async fn create_predictions(
+    State(state): State<AppState>,
+    Query(params): Query<WriteQuery>,
+    JsonOrProtobuf(mut request): JsonOrProtobuf<BulkRequest>,
+) -> Result<Json<BulkResponse>, ApiError> {
+    if request.predictions.is_empty() || request.predictions.len() > MAX_BULK_PREDICTIONS {
+        return Err(ApiError::BadRequest(format!(
+            "predictions must have between 1 and {MAX_BULK_PREDICTIONS} entries"
+        )));
+    }
+
+    let aliases = state.aliases.get().await?;
+    for prediction in &mut request.predictions {
+        prediction.pair = canonical_pair(&aliases, &prediction.pair);
+    }
+
+    let replace = params.replace();
+    let (mut results, accepted) = screen(&request.predictions, timestamp::now_ms(), replace);
+    let invalid: Vec<(&NewPrediction, String)> = results
+        .iter()
+        .zip(&request.predictions)
+        .filter(|(result, _)| result.status == RowStatus::Invalid)
+        .map(|(result, prediction)| (prediction, result.error.clone().unwrap_or_default()))
+        .collect();
+    keep_rejected_predictions(&state.pool, &invalid).await;
+
+    let inserted = db::insert_predictions(&state.pool, &accepted, replace).await?;
+    for (result, prediction) in results.iter_mut().zip(&request.predictions) {
+        if result.status != RowStatus::Created {
+            continue;
+        }
+        let key = prediction.key();
+        if !inserted.created.contains(&key) {
+            result.status = RowStatus::Duplicate;
+            result.error = Some(if replace {
+                "a later run is stored".to_string()
+            } else {
+                "prediction exists".to_string()
+            });
+        } else if inserted.replaced.contains(&key) {
+            result.status = RowStatus::Replaced;
+        }
+    }
+
+    let response = BulkResponse {
+        created: inserted.created.len(),
+        replaced: inserted.replaced.len(),
+        failed: results.len() - inserted.created.len(),
+        results,
+    };
+    tracing::info!(
+        created = response.created,
+        replaced = response.replaced,
+        failed = response.failed,
+        "Predictions ingested"
+    );
+    Ok(Json(response))
+}
+
+/// Why a prediction failed validation, without the error's prefix.
+fn rejection_reason(error: &ApiError) -> String {
+    match error {
+        ApiError::BadRequest(msg) => msg.clone(),
+        ApiError::InvalidFields(errors) => FieldError::summary(errors),
+        other => other.to_string(),
+    }
+}
+
+/// Keep predictions that failed validation, with the reasons. Failing to
+/// is logged rather than failing the request.
+async fn keep_rejected_predictions(pool: &PgPool, rejected: &[(&NewPrediction, String)]) {
+    if rejected.is_empty() {
+        return;
+    }
+    let rejected_ts_ms = timestamp::now_ms();
+    let rows: Vec<RejectedPrediction> = rejected
+        .iter()
+        .map(|(prediction, reason)| RejectedPrediction {
+            id: new_id(),
+            payload: serde_json::to_value(prediction).unwrap_or_default(),
+            reason: reason.clone(),
+            rejected_ts_ms,
+        })
+        .collect();
+    if let Err(e) = db::insert_rejected_predictions(pool, &rows).await {
+        tracing::error!(count = rows.len(), error = %e, "Failed to keep rejected predictions");
+    }
+}
+
+/// Validate every row and drop repeated keys, and with `replace` every
+/// run of a repeated target but the one with the latest `ts_ms` (the first
+/// of those on a tie). Returns a result per row, in which rows still to be
+/// inserted are `Created`, and those rows.
+fn screen(
+    predictions: &[NewPrediction],
+    now_ms: i64,
+    replace: bool,
+) -> (Vec<RowResult>, Vec<&NewPrediction>) {
+    let mut seen = HashSet::new();
+    let mut results: Vec<RowResult> = predictions
+        .iter()
+        .map(|prediction| {
+            let (status, error, errors) = match prediction.validate(now_ms) {
+                Err(ApiError::InvalidFields(errors)) => (
+                    RowStatus::Invalid,
+                    Some(FieldError::summary(&errors)),
+                    errors,
+                ),
+                Err(e) => (RowStatus::Invalid, Some(rejection_reason(&e)), Vec::new()),
+                Ok(()) if !seen.insert(prediction.key()) => (
+                    RowStatus::Duplicate,
+                    Some("repeats an earlier row".to_string()),
+                    Vec::new(),
+                ),
+                Ok(()) => (RowStatus::Created, None, Vec::new()),
+            };
+            RowResult {
+                status,
+                error,
+                errors,
+            }
+        })
+        .collect();
+
+    if replace {
+        // Latest run of each target
+        let mut latest: HashMap<_, usize> = HashMap::new();
+        for (i, prediction) in predictions.iter().enumerate() {
+            if results[i].status != RowStatus::Created {
+                continue;
+            }
+            if let Some(target) = prediction.target() {
+                latest
+                    .entry(target)
+                    .and_modify(|best| {
+                        if prediction.ts_ms > predictions[*best].ts_ms {
+                            *best = i;
+                        }
+                    })
+                    .or_insert(i);
+            }
+        }
+        for (i, prediction) in predictions.iter().enumerate() {
+            let superseded = prediction
+                .target()
+                .and_then(|target| latest.get(&target))
+                .is_some_and(|&best| best != i);
+            if results[i].status == RowStatus::Created && superseded {
+                results[i].status = RowStatus::Duplicate;
+                results[i].error =
+                    Some("a later run for the same target is in the request".to_string());
+            }
+        }
+    }
+
+    let accepted = predictions
+        .iter()
+        .zip(&results)
+        .filter(|(_, result)| result.status == RowStatus::Created)
+        .map(|(prediction, _)| prediction)
+        .collect();
+    (results, accepted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW_MS: i64 = 1_700_000_000_000;
+
+    fn prediction() -> NewPrediction {
+        NewPrediction {
+            pair: "BTCUSDT".to_string(),
+            model_name: "lgbm".to_string(),
+            model_version: "v1".to_string(),
+            ts_ms: NOW_MS,
+            predicted_ts_ms: Some(NOW_MS + 300_000),
+            predicted_price: 65000.5,
+            lower_bound: Some(64000.0),
+            upper_bound: Some(66000.0),
+            quantile: Some(0.5),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_predictions() {
+        assert!(prediction().validate(NOW_MS).is_ok());
+        assert!(NewPrediction {
+            predicted_ts_ms: None,
+            lower_bound: None,
+            upper_bound: None,
+            quantile: None,
+            ..prediction()
+        }
+        .validate(NOW_MS)
+        .is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_predictions() {
+        let invalid = [
+            NewPrediction {
+                predicted_price: f64::NAN,
+                ..prediction()
+            },
+            NewPrediction {
+                ts_ms: NOW_MS + MAX_CLOCK_SKEW_MS + 1,
+                ..prediction()
+            },
+            NewPrediction {
+                predicted_ts_ms: Some(NOW_MS),
+                ..prediction()
+            },
+            NewPrediction {
+                predicted_price: 66500.0,
+                ..prediction()
+            },
+            NewPrediction {
+                lower_bound: Some(67000.0),
+                ..prediction()
+            },
+            NewPrediction {
+                quantile: Some(1.0),
+                ..prediction()
+            },
+            NewPrediction {
+                pair: "BTC/USDT".to_string(),
+                ..prediction()
+            },
+        ];
+        for p in invalid {
+            assert!(p.validate(NOW_MS).is_err(), "{p:?}");
+        }
+    }
+
+    #[test]
+    fn reports_every_invalid_field() {
+        let invalid = NewPrediction {
+            pair: "BTC/USDT".to_string(),
+            ts_ms: 0,
+            quantile: Some(1.5),
+            ..prediction()
+        };
+        let Err(ApiError::InvalidFields(errors)) = invalid.validate(NOW_MS) else {
+            panic!("expected field errors");
+        };
+        assert_eq!(
+            errors,
+            [
+                FieldError::new("pair", "must be ASCII alphanumeric"),
+                FieldError::new("ts_ms", "must be positive"),
+                FieldError::new("quantile", "must be between 0 and 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn publishes_prediction_schema() {
+        let schema = prediction_schema();
+        assert_eq!(schema["$schema"], JSON_SCHEMA_DIALECT);
+        assert_eq!(schema["properties"]["ts_ms"]["minimum"], 1);
+        assert_eq!(schema["properties"]["quantile"]["exclusiveMaximum"], 1.0);
+        assert!(schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("predicted_price")));
+    }
+
+    #[test]
+    fn screens_bulk_rows() {
+        let rows = [
+            prediction(),
+            NewPrediction {
+                quantile: Some(2.0),
+                ..prediction()
+            },
+            prediction(),
+            NewPrediction {
+                pair: "ETHUSDT".to_string(),
+                ..prediction()
+            },
+        ];
+        let (results, accepted) = screen(&rows, NOW_MS, false);
+        let statuses: Vec<RowStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                RowStatus::Created,
+                RowStatus::Invalid,
+                RowStatus::Duplicate,
+                RowStatus::Created
+            ]
+        );
+        assert_eq!(results[1].errors[0].field, "quantile");
+        assert_eq!(accepted.len(), 2);
+    }
+
+    #[test]
+    fn replacing_screens_repeated_targets() {
+        let rows = [
+            prediction(),
+            NewPrediction {
+                ts_ms: NOW_MS - 60_000,
+                ..prediction()
+            },
+        ];
+        let (_, accepted) = screen(&rows, NOW_MS, false);
+        assert_eq!(accepted.len(), 2);
+
+        let (results, accepted) = screen(&rows, NOW_MS, true);
+        assert_eq!(results[1].status, RowStatus::Duplicate);
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(accepted[0].ts_ms, NOW_MS);
+
+        // The latest run is kept whatever the order
+        let rows = [
+            NewPrediction {
+                ts_ms: NOW_MS - 60_000,
+                ..prediction()
+            },
+            prediction(),
+        ];
+        let (results, accepted) = screen(&rows, NOW_MS, true);
+        assert_eq!(results[0].status, RowStatus::Duplicate);
+        assert_eq!(results[1].status, RowStatus::Created);
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(accepted[0].ts_ms, NOW_MS);
+    }
+
+    #[test]
+    fn rejected_payloads_replay_as_predictions() {
+        let payload = serde_json::to_value(prediction()).unwrap();
+        let replayed: NewPrediction = serde_json::from_value(payload).unwrap();
+        assert_eq!(replayed.key(), prediction().key());
+        assert_eq!(
+            rejection_reason(&ApiError::BadRequest(
+                "quantile must be between 0 and 1".to_string()
+            )),
+            "quantile must be between 0 and 1"
+        );
+    }
+}
@@ -0,0 +1,760 @@
+//! Prediction accuracy against realized prices.
+
+use std::ops::{AddAssign, SubAssign};
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db::{self, ModelFilter};
+use crate::envelope::EnvelopeQuery;
+use crate::error::ApiError;
+use crate::routes::aggregates::StatsQuery;
+use crate::routes::history::validate_range;
+use crate::routes::predictions::{
+    parse_horizon, validate_model_name, validate_model_version, validate_pair,
+};
+use crate::state::AppState;
+use crate::timestamp;
+
+/// Most points a single rolling accuracy request may produce.
+const MAX_POINTS: i64 = 10_000;
+
+/// Most rows a single evaluated predictions request may return.
+const MAX_EVALUATED_ROWS: usize = 50_000;
+
+/// Residual histogram bins when the request does not give a count.
+const DEFAULT_BINS: i64 = 20;
+
+/// Most bins a residual histogram may have.
+const MAX_BINS: i64 = 200;
+
+/// Range a rolling accuracy request covers when it does not give one.
+const DEFAULT_ROLLING_RANGE_MS: i64 = 7 * 86_400_000;
+
+/// Error metrics of predictions against actual prices; all null when no
+/// prediction in the window could be evaluated yet.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AccuracyMetrics {
+    pub pair: String,
+    /// Window of prediction times covered, ending at request time (ms)
+    pub window_ms: i64,
+    /// Predictions evaluated
+    pub count: i64,
+    /// Mean absolute error
+    pub mae: Option<f64>,
+    /// Root mean squared error
+    pub rmse: Option<f64>,
+    /// Mean absolute percentage error, in percent
+    pub mape: Option<f64>,
+}
+
+/// Accuracy of a pair's predictions made over a trailing window.
+///
+/// Each prediction is compared with the actual price at its target time,
+/// read from the `prices` series (one close per minute). Predictions whose
+/// target time has not been reached yet are not counted.
+#[utoipa::path(
+    get,
+    path = "/metrics/accuracy",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Error metrics", body = AccuracyMetrics),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "metrics"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_accuracy(
+    State(state): State<AppState>,
+    Query(params): Query<StatsQuery>,
+) -> Result<Json<AccuracyMetrics>, ApiError> {
+    let window_ms = params.validate(state.config.max_history_range_ms)?;
+    let model = ModelFilter {
+        name: params.model_name.as_deref(),
+        version: params.model_version.as_deref(),
+        horizon_ms: parse_horizon(params.horizon.as_deref())?,
+    };
+
+    tracing::info!(pair = %params.pair, window_ms, "Computing prediction accuracy");
+
+    let metrics = db::get_accuracy(&state.pool, &params.pair, model, window_ms).await?;
+
+    Ok(Json(metrics))
+}
+
+/// Error totals over a set of evaluated predictions, from which the error
+/// metrics follow. Totals of adjacent buckets add up to their union.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ErrorSums {
+    pub count: i64,
+    pub abs_error: f64,
+    pub squared_error: f64,
+    /// Predictions with a non-zero actual price, over which MAPE is taken
+    pub pct_count: i64,
+    pub abs_pct_error: f64,
+}
+
+impl ErrorSums {
+    fn mae(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.abs_error / self.count as f64)
+    }
+
+    fn rmse(&self) -> Option<f64> {
+        (self.count > 0).then(|| (self.squared_error / self.count as f64).sqrt())
+    }
+
+    fn mape(&self) -> Option<f64> {
+        (self.pct_count > 0).then(|| 100.0 * self.abs_pct_error / self.pct_count as f64)
+    }
+}
+
+impl AddAssign for ErrorSums {
+    fn add_assign(&mut self, other: Self) {
+        self.count += other.count;
+        self.abs_error += other.abs_error;
+        self.squared_error += other.squared_error;
+        self.pct_count += other.pct_count;
+        self.abs_pct_error += other.abs_pct_error;
+    }
+}
+
+impl SubAssign for ErrorSums {
+    fn sub_assign(&mut self, other: Self) {
+        self.count -= other.count;
+        self.abs_error -= other.abs_error;
+        self.squared_error -= other.squared_error;
+        self.pct_count -= other.pct_count;
+        self.abs_pct_error -= other.abs_pct_error;
+    }
+}
+
+/// Query parameters for rolling accuracy.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct RollingAccuracyQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Width of each rolling window, e.g. "24h"; a multiple of `step`
+    pub window: String,
+    /// Spacing between points, e.g. "1h"
+    pub step: String,
+    /// First point no earlier than this (ms); defaults to 7 days before `to_ts_ms`
+    pub from_ts_ms: Option<i64>,
+    /// Last point no later than this (ms); defaults to now
+    pub to_ts_ms: Option<i64>,
+    /// Only include predictions from this model
+    pub model_name: Option<String>,
+    /// Only include predictions from this model version
+    pub model_version: Option<String>,
+    /// Only include predictions for this horizon, e.g. "4h"
+    pub horizon: Option<String>,
+}
+
+/// Resolved rolling accuracy request.
+#[derive(Debug, PartialEq, Eq)]
+struct RollingSpec {
+    window_ms: i64,
+    step_ms: i64,
+    /// First and last point, both multiples of `step_ms`
+    first_ms: i64,
+    last_ms: i64,
+}
+
+impl RollingAccuracyQuery {
+    /// Validate the query parameters, resolving the points to compute.
+    fn validate(&self, max_range_ms: i64) -> Result<RollingSpec, ApiError> {
+        validate_pair(&self.pair)?;
+        if let Some(model_name) = &self.model_name {
+            validate_model_name(model_name)?;
+        }
+        if let Some(model_version) = &self.model_version {
+            validate_model_version(model_version)?;
+        }
+        parse_horizon(self.horizon.as_deref())?;
+
+        let window_ms = timestamp::parse_duration_ms(&self.window).ok_or_else(|| {
+            ApiError::BadRequest("window must be a positive duration like 1h or 24h".to_string())
+        })?;
+        let step_ms = timestamp::parse_duration_ms(&self.step).ok_or_else(|| {
+            ApiError::BadRequest("step must be a positive duration like 5m or 1h".to_string())
+        })?;
+        if window_ms % step_ms != 0 {
+            return Err(ApiError::BadRequest(
+                "window must be a multiple of step".to_string(),
+            ));
+        }
+        validate_range(0, window_ms, max_range_ms)?;
+
+        let to_ts_ms = self.to_ts_ms.unwrap_or_else(timestamp::now_ms);
+        let from_ts_ms = self
+            .from_ts_ms
+            .unwrap_or_else(|| to_ts_ms.saturating_sub(DEFAULT_ROLLING_RANGE_MS));
+        validate_range(from_ts_ms, to_ts_ms, max_range_ms)?;
+
+        let first_ms = from_ts_ms.div_euclid(step_ms) * step_ms
+            + if from_ts_ms.rem_euclid(step_ms) == 0 {
+                0
+            } else {
+                step_ms
+            };
+        let last_ms = to_ts_ms.div_euclid(step_ms) * step_ms;
+        if last_ms < first_ms {
+            return Err(ApiError::BadRequest(
+                "range contains no step boundary; widen it".to_string(),
+            ));
+        }
+        if (last_ms - first_ms) / step_ms >= MAX_POINTS {
+            return Err(ApiError::BadRequest(format!(
+                "range spans more than {MAX_POINTS} points; widen the step"
+            )));
+        }
+
+        Ok(RollingSpec {
+            window_ms,
+            step_ms,
+            first_ms,
+            last_ms,
+        })
+    }
+}
+
+/// Error metrics over the window ending at one point of a rolling series.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AccuracyPoint {
+    /// End of the window, exclusive (ms)
+    pub ts_ms: i64,
+    /// Predictions evaluated in the window
+    pub count: i64,
+    pub mae: Option<f64>,
+    pub rmse: Option<f64>,
+    pub mape: Option<f64>,
+}
+
+/// Slide the window over per-step error totals, one point per step.
+///
+/// `buckets` are keyed by bucket start, ascending; the point at `t` covers
+/// the buckets starting in `[t - window_ms, t)`.
+fn rolling(spec: &RollingSpec, buckets: &[(i64, ErrorSums)]) -> Vec<AccuracyPoint> {
+    let mut points = Vec::new();
+    let mut sums = ErrorSums::default();
+    let (mut entering, mut leaving) = (0, 0);
+
+    let mut ts_ms = spec.first_ms;
+    while ts_ms <= spec.last_ms {
+        while entering < buckets.len() && buckets[entering].0 < ts_ms {
+            sums += buckets[entering].1;
+            entering += 1;
+        }
+        while leaving < entering && buckets[leaving].0 < ts_ms - spec.window_ms {
+            sums -= buckets[leaving].1;
+            leaving += 1;
+        }
+        points.push(AccuracyPoint {
+            ts_ms,
+            count: sums.count,
+            mae: sums.mae(),
+            rmse: sums.rmse(),
+            mape: sums.mape(),
+        });
+        ts_ms += spec.step_ms;
+    }
+    points
+}
+
+/// Rolling accuracy of a pair's predictions, to see when a model started
+/// degrading.
+///
+/// Returns one point per `step`, oldest first; each point holds the error
+/// metrics of the predictions made in the `window` before it. Points with no
+/// evaluated predictions have null metrics.
+#[utoipa::path(
+    get,
+    path = "/metrics/accuracy/rolling",
+    params(RollingAccuracyQuery, EnvelopeQuery),
+    responses(
+        (status = 200, description = "Rolling error metrics, oldest first", body = Vec<AccuracyPoint>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "metrics"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_rolling_accuracy(
+    State(state): State<AppState>,
+    Query(params): Query<RollingAccuracyQuery>,
+) -> Result<Json<Vec<AccuracyPoint>>, ApiError> {
+    let spec = params.validate(state.config.max_history_range_ms)?;
+    let model = ModelFilter {
+        name: params.model_name.as_deref(),
+        version: params.model_version.as_deref(),
+        horizon_ms: parse_horizon(params.horizon.as_deref())?,
+    };
+
+    tracing::info!(pair = %params.pair, ?spec, "Computing rolling accuracy");
+
+    let buckets = db::get_error_sums(
+        &state.pool,
+        &params.pair,
+        model,
+        spec.first_ms.saturating_sub(spec.window_ms),
+        spec.last_ms - 1,
+        spec.step_ms,
+    )
+    .await?;
+
+    Ok(Json(rolling(&spec, &buckets)))
+}
+
+/// Query parameters for direction hit rates.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct HitRateQuery {
+    /// Only include this trading pair
+    pub pair: Option<String>,
+    /// Trailing window of prediction times ending now, e.g. "24h", "7d"
+    pub window: String,
+    /// Only include predictions from this model
+    pub model_name: Option<String>,
+    /// Only include predictions from this model version
+    pub model_version: Option<String>,
+    /// Only include predictions for this horizon, e.g. "4h"
+    pub horizon: Option<String>,
+}
+
+impl HitRateQuery {
+    /// Validate the query parameters, returning the window in ms.
+    fn validate(&self, max_range_ms: i64) -> Result<i64, ApiError> {
+        if let Some(pair) = &self.pair {
+            validate_pair(pair)?;
+        }
+        if let Some(model_name) = &self.model_name {
+            validate_model_name(model_name)?;
+        }
+        if let Some(model_version) = &self.model_version {
+            validate_model_version(model_version)?;
+        }
+        parse_horizon(self.horizon.as_deref())?;
+
+        let window_ms = timestamp::parse_duration_ms(&self.window).ok_or_else(|| {
+            ApiError::BadRequest(
+                "window must be a positive duration like 1h, 24h or 7d".to_string(),
+            )
+        })?;
+        validate_range(0, window_ms, max_range_ms)?;
+        Ok(window_ms)
+    }
+}
+
+/// How often one model called a pair's direction right.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HitRate {
+    pub pair: String,
+    pub model_name: String,
+    /// Predictions evaluated
+    pub count: i64,
+    /// Predictions whose direction matched the realized one
+    pub hits: i64,
+    /// `hits / count`, between 0 and 1
+    pub hit_rate: f64,
+}
+
+/// Direction hit rate per pair and model over a trailing window.
+///
+/// A prediction's direction (up, down or flat) is taken relative to the
+/// price when it was made, and compared with how the actual price moved by
+/// its target time. As for the `direction` field, moves within
+/// `DIRECTION_FLAT_THRESHOLD_PCT` count as flat.
+#[utoipa::path(
+    get,
+    path = "/metrics/hit-rate",
+    params(HitRateQuery, EnvelopeQuery),
+    responses(
+        (status = 200, description = "Hit rate per pair and model", body = Vec<HitRate>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "metrics"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_hit_rates(
+    State(state): State<AppState>,
+    Query(params): Query<HitRateQuery>,
+) -> Result<Json<Vec<HitRate>>, ApiError> {
+    let window_ms = params.validate(state.config.max_history_range_ms)?;
+    let model = ModelFilter {
+        name: params.model_name.as_deref(),
+        version: params.model_version.as_deref(),
+        horizon_ms: parse_horizon(params.horizon.as_deref())?,
+    };
+
+    tracing::info!(pair = ?params.pair, window_ms, "Computing direction hit rates");
+
+    let hit_rates = db::get_hit_rates(
+        &state.pool,
+        params.pair.as_deref(),
+        model,
+        window_ms,
+        state.config.direction_flat_threshold_pct,
+    )
+    .await?;
+
+    Ok(Json(hit_rates))
+}
+
+/// Query parameters for the residual histogram.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct HistogramQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Trailing window of prediction times ending now, e.g. "7d"
+    pub window: String,
+    /// Number of equal-width bins (default 20, at most 200)
+    pub bins: Option<i64>,
+    /// Only include predictions from this model
+    pub model_name: Option<String>,
+    /// Only include predictions from this model version
+    pub model_version: Option<String>,
+    /// Only include predictions for this horizon, e.g. "4h"
+    pub horizon: Option<String>,
+}
+
+impl HistogramQuery {
+    /// Validate the query parameters, returning the window in ms and the
+    /// bin count.
+    fn validate(&self, max_range_ms: i64) -> Result<(i64, i64), ApiError> {
+        let stats = StatsQuery {
+            pair: self.pair.clone(),
+            window: self.window.clone(),
+            model_name: self.model_name.clone(),
+            model_version: self.model_version.clone(),
+            horizon: self.horizon.clone(),
+        };
+        let window_ms = stats.validate(max_range_ms)?;
+
+        let bins = self.bins.unwrap_or(DEFAULT_BINS);
+        if !(1..=MAX_BINS).contains(&bins) {
+            return Err(ApiError::BadRequest(format!(
+                "bins must be between 1 and {MAX_BINS}"
+            )));
+        }
+        Ok((window_ms, bins))
+    }
+}
+
+/// One bin of the residual histogram.
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct HistogramBin {
+    /// Lower edge, inclusive
+    pub lower: f64,
+    /// Upper edge, exclusive except for the last bin
+    pub upper: f64,
+    pub count: i64,
+}
+
+/// How often actual prices fell inside the published prediction intervals.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IntervalCoverage {
+    /// Evaluated predictions that published both bounds
+    pub with_interval: i64,
+    /// Actual price within `[lower_bound, upper_bound]`
+    pub covered: i64,
+    /// Actual price below `lower_bound`
+    pub below: i64,
+    /// Actual price above `upper_bound`
+    pub above: i64,
+    /// `covered / with_interval`; compare with the interval's nominal level
+    pub coverage: Option<f64>,
+    /// Mean `upper_bound - lower_bound`
+    pub mean_width: Option<f64>,
+}
+
+/// Distribution of residuals (predicted minus actual price).
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorHistogram {
+    pub pair: String,
+    /// Window of prediction times covered, ending at request time (ms)
+    pub window_ms: i64,
+    /// Predictions evaluated
+    pub count: i64,
+    /// Equal-width bins from the smallest to the largest residual; empty
+    /// when nothing was evaluated
+    pub bins: Vec<HistogramBin>,
+    pub coverage: IntervalCoverage,
+}
+
+/// Lay out `bins` equal-width bins over `[min, max]`, filled from sparse
+/// `(bin index, count)` pairs. The maximum itself lands one past the last
+/// bin and is counted in it; identical residuals share a single bin.
+fn histogram_bins(min: f64, max: f64, bins: i64, counts: &[(i64, i64)]) -> Vec<HistogramBin> {
+    let bins = if max > min { bins } else { 1 };
+    let width = (max - min) / bins as f64;
+
+    (0..bins)
+        .map(|i| HistogramBin {
+            lower: min + width * i as f64,
+            upper: if i == bins - 1 {
+                max
+            } else {
+                min + width * (i + 1) as f64
+            },
+            count: counts
+                .iter()
+                .filter(|(bin, _)| (*bin).min(bins - 1) == i)
+                .map(|(_, count)| count)
+                .sum(),
+        })
+        .collect()
+}
+
+/// Histogram of a pair's residuals over a trailing window, with coverage of
+/// its prediction intervals.
+///
+/// Residuals are predicted minus actual price, so positive bins are
+/// over-predictions. Coverage only counts predictions that published both
+/// `lower_bound` and `upper_bound`; a calibrated 90% interval should cover
+/// about 90% of actual prices.
+#[utoipa::path(
+    get,
+    path = "/metrics/errors/histogram",
+    params(HistogramQuery),
+    responses(
+        (status = 200, description = "Residual histogram and interval coverage", body = ErrorHistogram),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "metrics"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_error_histogram(
+    State(state): State<AppState>,
+    Query(params): Query<HistogramQuery>,
+) -> Result<Json<ErrorHistogram>, ApiError> {
+    let (window_ms, bins) = params.validate(state.config.max_history_range_ms)?;
+    let model = ModelFilter {
+        name: params.model_name.as_deref(),
+        version: params.model_version.as_deref(),
+        horizon_ms: parse_horizon(params.horizon.as_deref())?,
+    };
+    let to_ts_ms = timestamp::now_ms();
+    let from_ts_ms = to_ts_ms.saturating_sub(window_ms);
+
+    tracing::info!(pair = %params.pair, window_ms, bins, "Computing residual histogram");
+
+    let (count, min, max, coverage) =
+        db::get_residual_summary(&state.pool, &params.pair, model, from_ts_ms, to_ts_ms).await?;
+
+    let bins = match (min, max) {
+        (Some(min), Some(max)) if max > min => {
+            let width = (max - min) / bins as f64;
+            let counts = db::get_residual_bins(
+                &state.pool,
+                &params.pair,
+                model,
+                from_ts_ms,
+                to_ts_ms,
+                min,
+                width,
+            )
+            .await?;
+            histogram_bins(min, max, bins, &counts)
+        }
+        (Some(min), Some(max)) => histogram_bins(min, max, bins, &[(0, count)]),
+        _ => Vec::new(),
+    };
+
+    Ok(Json(ErrorHistogram {
+        pair: params.pair,
+        window_ms,
+        count,
+        bins,
+        coverage,
+    }))
+}
+
+/// Query parameters for predictions joined with actual prices.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct EvaluatedQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Start of the range of prediction times, inclusive (ms)
+    pub from_ts_ms: i64,
+    /// End of the range of prediction times, inclusive (ms)
+    pub to_ts_ms: i64,
+    /// Only include predictions from this model
+    pub model_name: Option<String>,
+    /// Only include predictions from this model version
+    pub model_version: Option<String>,
+    /// Only include predictions for this horizon, e.g. "4h"
+    pub horizon: Option<String>,
+}
+
+impl EvaluatedQuery {
+    /// Validate the query parameters.
+    fn validate(&self, max_range_ms: i64) -> Result<(), ApiError> {
+        validate_pair(&self.pair)?;
+        if let Some(model_name) = &self.model_name {
+            validate_model_name(model_name)?;
+        }
+        if let Some(model_version) = &self.model_version {
+            validate_model_version(model_version)?;
+        }
+        parse_horizon(self.horizon.as_deref())?;
+        validate_range(self.from_ts_ms, self.to_ts_ms, max_range_ms)
+    }
+}
+
+/// A prediction next to the price that actually occurred.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EvaluatedPrediction {
+    pub model_name: String,
+    /// Timestamp when prediction was made (ms)
+    pub ts_ms: i64,
+    /// Timestamp the prediction is for (ms); `ts_ms` for a current fair value
+    pub predicted_ts_ms: i64,
+    pub predicted_price: f64,
+    /// Price at `predicted_ts_ms`
+    pub actual_price: f64,
+    /// `predicted_price - actual_price`
+    pub error: f64,
+}
+
+/// Get a pair's predictions joined with the actual prices they predicted.
+///
+/// Returns one row per prediction made in the range whose target price is
+/// known, ordered by `ts_ms`, so prediction quality can be charted in one
+/// call. Predictions with a non-finite price are left out.
+#[utoipa::path(
+    get,
+    path = "/predictions/evaluated",
+    params(EvaluatedQuery, EnvelopeQuery),
+    responses(
+        (status = 200, description = "Evaluated predictions, oldest first", body = Vec<EvaluatedPrediction>),
+        (status = 400, description = "Invalid request or too many rows")
+    ),
+    tag = "metrics"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_evaluated(
+    State(state): State<AppState>,
+    Query(params): Query<EvaluatedQuery>,
+) -> Result<Json<Vec<EvaluatedPrediction>>, ApiError> {
+    params.validate(state.config.max_history_range_ms)?;
+    let model = ModelFilter {
+        name: params.model_name.as_deref(),
+        version: params.model_version.as_deref(),
+        horizon_ms: parse_horizon(params.horizon.as_deref())?,
+    };
+
+    tracing::info!(pair = %params.pair, "Fetching evaluated predictions");
+
+    let rows = db::get_evaluated_predictions(
+        &state.pool,
+        &params.pair,
+        model,
+        params.from_ts_ms,
+        params.to_ts_ms,
+        MAX_EVALUATED_ROWS as i64 + 1,
+    )
+    .await?;
+
+    if rows.len() > MAX_EVALUATED_ROWS {
+        return Err(ApiError::BadRequest(format!(
+            "result exceeds {MAX_EVALUATED_ROWS} rows; narrow the range"
+        )));
+    }
+
+    tracing::debug!(count = rows.len(), "Evaluated predictions fetched");
+
+    Ok(Json(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sums(count: i64, abs_error: f64) -> ErrorSums {
+        ErrorSums {
+            count,
+            abs_error,
+            squared_error: abs_error * abs_error,
+            pct_count: count,
+            abs_pct_error: abs_error / 100.0,
+        }
+    }
+
+    fn query(window: &str, step: &str, from_ts_ms: i64, to_ts_ms: i64) -> RollingAccuracyQuery {
+        RollingAccuracyQuery {
+            pair: "BTCUSDT".to_string(),
+            window: window.to_string(),
+            step: step.to_string(),
+            from_ts_ms: Some(from_ts_ms),
+            to_ts_ms: Some(to_ts_ms),
+            model_name: None,
+            model_version: None,
+            horizon: None,
+        }
+    }
+
+    #[test]
+    fn lays_out_histogram_bins() {
+        let bins = histogram_bins(-2.0, 2.0, 4, &[(0, 3), (3, 1), (4, 1)]);
+        let edges: Vec<_> = bins.iter().map(|b| (b.lower, b.upper)).collect();
+        assert_eq!(edges, [(-2.0, -1.0), (-1.0, 0.0), (0.0, 1.0), (1.0, 2.0)]);
+        let counts: Vec<_> = bins.iter().map(|b| b.count).collect();
+        assert_eq!(counts, [3, 0, 0, 2]);
+
+        let single = histogram_bins(5.0, 5.0, 20, &[(0, 7)]);
+        assert_eq!(
+            single,
+            [HistogramBin {
+                lower: 5.0,
+                upper: 5.0,
+                count: 7
+            }]
+        );
+    }
+
+    #[test]
+    fn aligns_points_to_steps() {
+        let spec = query("2h", "1h", 1_800_000, 10_800_000)
+            .validate(i64::MAX)
+            .unwrap();
+        assert_eq!(
+            spec,
+            RollingSpec {
+                window_ms: 7_200_000,
+                step_ms: 3_600_000,
+                first_ms: 3_600_000,
+                last_ms: 10_800_000,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_window_not_multiple_of_step() {
+        assert!(query("90m", "1h", 0, 36_000_000)
+            .validate(i64::MAX)
+            .is_err());
+        assert!(query("1h", "1m", 0, 60_000 * MAX_POINTS)
+            .validate(i64::MAX)
+            .is_err());
+    }
+
+    #[test]
+    fn slides_window_over_buckets() {
+        let spec = RollingSpec {
+            window_ms: 20,
+            step_ms: 10,
+            first_ms: 10,
+            last_ms: 40,
+        };
+        let buckets = [(0, sums(1, 2.0)), (10, sums(1, 4.0)), (30, sums(2, 2.0))];
+        let points = rolling(&spec, &buckets);
+
+        let counts: Vec<_> = points.iter().map(|p| p.count).collect();
+        assert_eq!(counts, [1, 2, 1, 2]);
+        assert_eq!(points[1].mae, Some(3.0));
+        assert_eq!(points[2].mae, Some(4.0));
+        assert_eq!(points[3].mae, Some(1.0));
+        assert_eq!(points[0].mape, Some(2.0));
+    }
+}
@@ -1,4 +1,22 @@
 //! Route handlers for the prediction API.
 
+pub mod aggregates;
+pub mod candles;
+pub mod derivatives;
+pub mod export;
 pub mod health;
+pub mod history;
+pub mod index;
+pub mod ingest;
+pub mod metrics;
+pub mod models;
+pub mod orderbook;
+pub mod pairs;
 pub mod predictions;
+pub mod prices;
+pub mod ratelimit;
+pub mod registry;
+pub mod rejected;
+pub mod status;
+pub mod stream;
+pub mod webhooks;
@@ -0,0 +1,192 @@
+//! Per-model prediction endpoints.
+//!
+//! Path-based alternatives to the `model_name` query filter, for clients
+//! that always target one model, plus a listing of the models that exist.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::db::{self, ModelFilter};
+use crate::envelope::EnvelopeQuery;
+use crate::error::ApiError;
+use crate::projection::{keeps_any, ProfileQuery, Projected};
+use crate::routes::predictions::{
+    attach_current_prices, etag, not_modified, validate_model_name, validate_pair, Prediction,
+    CURRENT_PRICE_FIELDS,
+};
+use crate::state::AppState;
+use crate::timestamp::TimestampQuery;
+
+/// A model version that has written predictions.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModelSummary {
+    /// Model name
+    pub model_name: String,
+    /// Model version
+    pub model_version: String,
+    /// Pairs the model version has predicted, sorted
+    pub pairs: Vec<String>,
+    /// Timestamp of its earliest prediction (ms)
+    pub first_ts_ms: i64,
+    /// Timestamp of its latest prediction (ms)
+    pub last_ts_ms: i64,
+    /// Predictions stored for it
+    pub count: i64,
+}
+
+/// List every model name and version that has written predictions.
+///
+/// Sorted by model name, then version. `last_ts_ms` tells live models from
+/// retired ones.
+#[utoipa::path(
+    get,
+    path = "/models",
+    params(EnvelopeQuery),
+    responses(
+        (status = 200, description = "Models with predictions", body = Vec<ModelSummary>)
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn list_models(State(pool): State<PgPool>) -> Result<Json<Vec<ModelSummary>>, ApiError> {
+    tracing::info!("Listing models");
+
+    let models = db::get_model_summaries(&pool).await?;
+
+    tracing::debug!(count = models.len(), "Models fetched");
+
+    Ok(Json(models))
+}
+
+/// Get a model's latest prediction for a trading pair.
+///
+/// Supports `ETag`/`If-None-Match` like `GET /predictions`.
+#[utoipa::path(
+    get,
+    path = "/models/{model_name}/predictions/{pair}",
+    params(
+        ("model_name" = String, Path, description = "Model name"),
+        ("pair" = String, Path, description = "Trading pair (e.g., \"BTCUSDT\")"),
+        ProfileQuery,
+        TimestampQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag from a previous response")
+    ),
+    responses(
+        (status = 200, description = "Prediction found", body = Prediction,
+            headers(("ETag" = String, description = "Weak validator for the prediction"))),
+        (status = 304, description = "Prediction unchanged since If-None-Match"),
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "Prediction not found")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state, headers))]
+pub async fn get_model_prediction(
+    State(state): State<AppState>,
+    Path((model_name, pair)): Path<(String, String)>,
+    Query(params): Query<ProfileQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    validate_model_name(&model_name)?;
+    validate_pair(&pair)?;
+    let fields = state
+        .config
+        .prediction_profiles
+        .select(params.profile.as_deref(), params.fields.as_deref())?;
+
+    tracing::info!(%model_name, %pair, "Fetching model prediction");
+
+    match db::get_latest_prediction(
+        &state.pool,
+        &pair,
+        ModelFilter::name(&model_name),
+        None,
+        None,
+    )
+    .await?
+    {
+        Some(mut p) => {
+            let etag = etag(std::slice::from_ref(&p));
+            if not_modified(&headers, &etag, None) {
+                return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+            }
+            if keeps_any(fields.as_deref(), CURRENT_PRICE_FIELDS) {
+                attach_current_prices(&state, std::slice::from_mut(&mut p)).await;
+            }
+            Ok(([(header::ETAG, etag)], Projected { body: p, fields }).into_response())
+        }
+        None => {
+            tracing::warn!(%model_name, %pair, "Prediction not found");
+            Err(ApiError::NotFound(format!(
+                "{} (model {})",
+                pair, model_name
+            )))
+        }
+    }
+}
+
+/// Get a model's latest prediction for every trading pair.
+///
+/// Supports `ETag`/`If-None-Match` like `GET /predictions/latest`.
+#[utoipa::path(
+    get,
+    path = "/models/{model_name}/predictions",
+    params(
+        ("model_name" = String, Path, description = "Model name"),
+        ProfileQuery,
+        EnvelopeQuery,
+        TimestampQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag from a previous response")
+    ),
+    responses(
+        (status = 200, description = "Latest predictions from the model", body = Vec<Prediction>,
+            headers(("ETag" = String, description = "Weak validator for the snapshot"))),
+        (status = 304, description = "Snapshot unchanged since If-None-Match"),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state, headers))]
+pub async fn get_model_predictions(
+    State(state): State<AppState>,
+    Path(model_name): Path<String>,
+    Query(params): Query<ProfileQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    validate_model_name(&model_name)?;
+    let fields = state
+        .config
+        .prediction_profiles
+        .select(params.profile.as_deref(), params.fields.as_deref())?;
+
+    tracing::info!(%model_name, "Fetching latest predictions for model");
+
+    let mut predictions =
+        db::get_all_latest_predictions(&state.pool, Some(&model_name), None, None, None).await?;
+
+    tracing::debug!(count = predictions.len(), "Predictions fetched");
+
+    let etag = etag(&predictions);
+    if not_modified(&headers, &etag, None) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+    if keeps_any(fields.as_deref(), CURRENT_PRICE_FIELDS) {
+        attach_current_prices(&state, &mut predictions).await;
+    }
+
+    Ok((
+        [(header::ETAG, etag)],
+        Projected {
+            body: predictions,
+            fields,
+        },
+    )
+        .into_response())
+}
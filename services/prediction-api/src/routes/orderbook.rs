@@ -0,0 +1,68 @@
+//! L2 order book snapshots.
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db;
+use crate::error::ApiError;
+use crate::routes::predictions::validate_pair;
+use crate::state::AppState;
+
+/// Query parameters for a pair's order book.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct OrderBookQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Return the snapshot that was current at this time (ms); the latest
+    /// when omitted
+    pub ts_ms: Option<i64>,
+}
+
+/// Snapshot of the top levels of a pair's order book.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrderBook {
+    pub pair: String,
+    /// Exchange time of the snapshot (ms)
+    pub ts_ms: i64,
+    /// Exchange's update id of the book at the snapshot
+    pub last_update_id: i64,
+    /// `[price, quantity]` bids, best (highest) first
+    pub bids: Vec<[f64; 2]>,
+    /// `[price, quantity]` asks, best (lowest) first
+    pub asks: Vec<[f64; 2]>,
+}
+
+/// Get a pair's order book snapshot.
+///
+/// Snapshots are taken every `ORDERBOOK_INTERVAL_MS` for the pairs in
+/// `ORDERBOOK_PAIRS`. With `ts_ms`, the last snapshot taken at or before it
+/// is returned, the book a prediction made then could have seen.
+#[utoipa::path(
+    get,
+    path = "/orderbook",
+    params(OrderBookQuery),
+    responses(
+        (status = 200, description = "Order book snapshot", body = OrderBook),
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "No snapshot for the pair at or before ts_ms")
+    ),
+    tag = "prices"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_orderbook(
+    State(state): State<AppState>,
+    Query(params): Query<OrderBookQuery>,
+) -> Result<Json<OrderBook>, ApiError> {
+    validate_pair(&params.pair)?;
+
+    tracing::info!(pair = %params.pair, ts_ms = ?params.ts_ms, "Fetching order book");
+
+    db::get_orderbook(&state.pool, &params.pair, params.ts_ms)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::OrderBookNotFound(params.pair))
+}
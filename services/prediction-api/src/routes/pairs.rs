@@ -0,0 +1,286 @@
+//! Trading pair discovery.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::envelope::EnvelopeQuery;
+use crate::error::ApiError;
+use crate::routes::predictions::validate_pair;
+use crate::state::AppState;
+use crate::timestamp;
+
+/// Characters exchanges put between the base and quote asset.
+const SYMBOL_SEPARATORS: [char; 5] = ['-', '/', '_', ':', ' '];
+
+/// A trading pair with predictions, and the span they cover.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PairSummary {
+    /// Trading pair
+    pub pair: String,
+    /// Timestamp of the pair's earliest prediction (ms)
+    pub first_ts_ms: i64,
+    /// Timestamp of the pair's latest prediction (ms)
+    pub last_ts_ms: i64,
+    /// Predictions stored for the pair
+    pub count: i64,
+}
+
+/// List the trading pairs that have predictions.
+///
+/// Pairs are sorted by name. Any pair listed here can be passed to the
+/// `pair` parameter of the other endpoints.
+#[utoipa::path(
+    get,
+    path = "/pairs",
+    params(EnvelopeQuery),
+    responses(
+        (status = 200, description = "Pairs with predictions", body = Vec<PairSummary>)
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn list_pairs(State(pool): State<PgPool>) -> Result<Json<Vec<PairSummary>>, ApiError> {
+    tracing::info!("Listing pairs");
+
+    let pairs = db::get_pair_summaries(&pool).await?;
+
+    tracing::debug!(count = pairs.len(), "Pairs fetched");
+
+    Ok(Json(pairs))
+}
+
+/// Normalize an exchange-native symbol: uppercase and without separators,
+/// so `btc-usdt`, `BTC/USDT` and `BTCUSDT` are the same symbol.
+pub fn normalize_symbol(symbol: &str) -> String {
+    symbol
+        .trim()
+        .chars()
+        .filter(|c| !SYMBOL_SEPARATORS.contains(c))
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// The canonical pair for an exchange-native symbol: the normalized symbol,
+/// mapped through `aliases` (alias to pair) when it is an alias.
+pub fn canonical_pair(aliases: &HashMap<String, String>, symbol: &str) -> String {
+    let symbol = normalize_symbol(symbol);
+    aliases.get(&symbol).cloned().unwrap_or(symbol)
+}
+
+/// An exchange-native symbol stored under another pair.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PairAlias {
+    /// Normalized symbol, e.g. "XBTUSD"
+    pub alias: String,
+    /// Canonical pair it maps to, e.g. "BTCUSD"
+    pub pair: String,
+    /// When the alias was last set (ms)
+    pub created_ts_ms: i64,
+}
+
+/// A canonical pair and the symbols that map to it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PairAliases {
+    /// Canonical pair
+    pub pair: String,
+    /// Normalized symbols mapped to the pair, sorted
+    pub aliases: Vec<String>,
+}
+
+/// Request body mapping an alias to a pair.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AliasRequest {
+    /// Canonical pair, e.g. "BTCUSD"; normalized like the alias
+    pub pair: String,
+}
+
+/// Resolve a symbol to its canonical pair and list the pair's aliases.
+///
+/// The symbol may be the canonical pair or any alias, in any case and with
+/// separators, e.g. `xbt-usd`.
+#[utoipa::path(
+    get,
+    path = "/pairs/{pair}/aliases",
+    params(("pair" = String, Path, description = "Pair or exchange-native symbol"), EnvelopeQuery),
+    responses(
+        (status = 200, description = "The canonical pair and its aliases", body = PairAliases),
+        (status = 400, description = "Invalid symbol")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_aliases(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+) -> Result<Json<PairAliases>, ApiError> {
+    validate_pair(&normalize_symbol(&symbol))?;
+
+    let aliases = state.aliases.get().await?;
+    let pair = canonical_pair(&aliases, &symbol);
+    let mut matching: Vec<String> = aliases
+        .iter()
+        .filter(|(_, target)| **target == pair)
+        .map(|(alias, _)| alias.clone())
+        .collect();
+    matching.sort();
+
+    Ok(Json(PairAliases {
+        pair,
+        aliases: matching,
+    }))
+}
+
+/// List every pair alias.
+#[utoipa::path(
+    get,
+    path = "/admin/pair-aliases",
+    responses(
+        (status = 200, description = "Aliases, sorted by alias", body = Vec<PairAlias>),
+        (status = 401, description = "Missing or invalid admin API key")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+pub async fn list_aliases(State(pool): State<PgPool>) -> Result<Json<Vec<PairAlias>>, ApiError> {
+    Ok(Json(db::get_pair_aliases(&pool).await?))
+}
+
+/// Map an exchange-native symbol to a canonical pair.
+///
+/// Predictions ingested under the alias from now on are stored under the
+/// pair; rows already stored keep the symbol they were written with. Other
+/// replicas cache the aliases and pick the change up within a minute. Both
+/// sides are normalized, so `PUT /admin/pair-aliases/XBT-USD` with
+/// `{"pair": "btc/usd"}` maps `XBTUSD` to `BTCUSD`. Aliases do not chain:
+/// the pair cannot itself be an alias, nor the alias a pair others map to.
+#[utoipa::path(
+    put,
+    path = "/admin/pair-aliases/{alias}",
+    params(("alias" = String, Path, description = "Exchange-native symbol")),
+    request_body = AliasRequest,
+    responses(
+        (status = 200, description = "The alias", body = PairAlias),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid admin API key")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn put_alias(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+    Json(request): Json<AliasRequest>,
+) -> Result<Json<PairAlias>, ApiError> {
+    let alias = normalize_symbol(&alias);
+    let pair = normalize_symbol(&request.pair);
+    validate_pair(&alias)?;
+    validate_pair(&pair)?;
+
+    let existing = db::get_pair_alias_map(&state.pool).await?;
+    check_alias(&existing, &alias, &pair)?;
+
+    let alias = PairAlias {
+        alias,
+        pair,
+        created_ts_ms: timestamp::now_ms(),
+    };
+    db::upsert_pair_alias(&state.pool, &alias).await?;
+    reload(&state).await;
+
+    tracing::info!(alias = %alias.alias, pair = %alias.pair, "Pair alias set");
+    Ok(Json(alias))
+}
+
+/// Check that mapping `alias` to `pair` keeps `existing` free of chains.
+fn check_alias(
+    existing: &HashMap<String, String>,
+    alias: &str,
+    pair: &str,
+) -> Result<(), ApiError> {
+    if alias == pair {
+        return Err(ApiError::BadRequest(
+            "an alias cannot map to itself".to_string(),
+        ));
+    }
+    if let Some(target) = existing.get(pair) {
+        return Err(ApiError::BadRequest(format!(
+            "{pair} is itself an alias of {target}"
+        )));
+    }
+    if existing.values().any(|target| target == alias) {
+        return Err(ApiError::BadRequest(format!(
+            "{alias} is the pair of other aliases"
+        )));
+    }
+    Ok(())
+}
+
+/// Remove a pair alias.
+#[utoipa::path(
+    delete,
+    path = "/admin/pair-aliases/{alias}",
+    params(("alias" = String, Path, description = "Exchange-native symbol")),
+    responses(
+        (status = 204, description = "Alias removed"),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such alias")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn delete_alias(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let alias = normalize_symbol(&alias);
+    if !db::delete_pair_alias(&state.pool, &alias).await? {
+        return Err(ApiError::AliasNotFound(alias));
+    }
+    reload(&state).await;
+
+    tracing::info!(alias = %alias, "Pair alias removed");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Reload the cached aliases after a change. Failing to only delays the
+/// change here until the next periodic reload, so it is logged.
+async fn reload(state: &AppState) {
+    if let Err(e) = state.aliases.reload().await {
+        tracing::warn!(error = %e, "Failed to reload pair aliases");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_exchange_symbols() {
+        for symbol in ["BTCUSDT", "btcusdt", "BTC-USDT", "BTC/USDT", " btc_usdt "] {
+            assert_eq!(normalize_symbol(symbol), "BTCUSDT");
+        }
+    }
+
+    #[test]
+    fn maps_aliases_without_chains() {
+        let aliases = HashMap::from([("XBTUSD".to_string(), "BTCUSD".to_string())]);
+        assert_eq!(canonical_pair(&aliases, "XBT/USD"), "BTCUSD");
+        assert_eq!(canonical_pair(&aliases, "eth-usd"), "ETHUSD");
+
+        assert!(check_alias(&aliases, "XBTUSDT", "BTCUSDT").is_ok());
+        assert!(check_alias(&aliases, "BTCUSD", "BTCUSD").is_err());
+        assert!(check_alias(&aliases, "XXBTZUSD", "XBTUSD").is_err());
+        assert!(check_alias(&aliases, "BTCUSD", "XBTUSDT").is_err());
+    }
+}
@@ -1,114 +1,1279 @@
 //! Prediction endpoints.
 
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use axum::{
     extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{AppendHeaders, IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use tokio::time::Instant;
 use utoipa::{IntoParams, ToSchema};
 
-use crate::db;
+use crate::db::{self, ModelFilter};
+use crate::envelope::EnvelopeQuery;
 use crate::error::ApiError;
+use crate::experiments::{self, Variant};
+use crate::projection::{keeps_any, Projected};
+use crate::routes::history::MAX_BATCH_PAIRS;
+use crate::state::AppState;
+use crate::timestamp::{self, TimestampQuery};
 
 /// Query parameters for getting a prediction.
 #[derive(Debug, Deserialize, IntoParams, ToSchema)]
 pub struct PredictionQuery {
     /// Trading pair (e.g., "BTCUSDT")
     pub pair: String,
+    /// Only consider predictions from this model
+    pub model_name: Option<String>,
+    /// Only consider predictions from this model version
+    pub model_version: Option<String>,
+    /// Only consider predictions for this horizon, e.g. "300s", "1h", "4h",
+    /// "24h" or plain milliseconds
+    pub horizon: Option<String>,
+    /// What to return when the model filter matches nothing
+    pub fallback: Option<Fallback>,
+    /// Ignore predictions made less than this many ms ago
+    pub min_age_ms: Option<i64>,
+    /// Return the prediction that was current at this time (ms)
+    pub as_of_ts_ms: Option<i64>,
+    /// Response profile, e.g. "minimal"; defaults to "full"
+    pub profile: Option<String>,
+    /// Comma-separated fields to return, e.g. "pair,predicted_price,ts_ms"
+    pub fields: Option<String>,
+    /// Hold the request until a prediction newer than `since_ts_ms` exists
+    pub wait: Option<bool>,
+    /// With `wait`, the `ts_ms` of the newest prediction already seen
+    pub since_ts_ms: Option<i64>,
+    /// Only consider predictions whose interval (`upper_bound -
+    /// lower_bound`) is at most this percentage of `predicted_price`
+    pub max_interval_pct: Option<f64>,
+}
+
+/// Fallback behaviour when a filtered lookup finds no prediction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Fallback {
+    /// Return the latest prediction for the pair from any model
+    Latest,
+}
+
+/// Query parameters for the latest predictions of all pairs.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct LatestQuery {
+    /// Only pairs matching this pattern, where `*` matches any run of
+    /// characters and `?` any single one (e.g., "BTC*")
+    pub pair: Option<String>,
+    /// Only pairs starting with this prefix; shorthand for `pair=<prefix>*`
+    pub pair_prefix: Option<String>,
+    /// Return at most this many predictions
+    pub limit: Option<usize>,
+    /// Field to sort by (default "pair")
+    pub sort_by: Option<SortBy>,
+    /// Sort direction (default "asc")
+    pub order: Option<SortOrder>,
+    /// Response profile, e.g. "minimal"; defaults to "full"
+    pub profile: Option<String>,
+    /// Comma-separated fields to return, e.g. "pair,predicted_price,ts_ms"
+    pub fields: Option<String>,
+    /// Only consider predictions whose interval (`upper_bound -
+    /// lower_bound`) is at most this percentage of `predicted_price`
+    pub max_interval_pct: Option<f64>,
+}
+
+/// Sort key for prediction listings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    Pair,
+    TsMs,
+    PredictedPrice,
+}
+
+/// Sort direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl LatestQuery {
+    /// Validate the query parameters.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.limit == Some(0) {
+            return Err(ApiError::BadRequest("limit must be positive".to_string()));
+        }
+        self.pair_pattern()?;
+        validate_max_interval_pct(self.max_interval_pct)
+    }
+
+    /// `LIKE` pattern selecting pairs, from `pair` or `pair_prefix`.
+    fn pair_pattern(&self) -> Result<Option<String>, ApiError> {
+        match (&self.pair, &self.pair_prefix) {
+            (Some(_), Some(_)) => Err(ApiError::BadRequest(
+                "pair and pair_prefix cannot be combined".to_string(),
+            )),
+            (Some(glob), None) => glob_to_like(glob).map(Some),
+            (None, Some(prefix)) => {
+                validate_pair(prefix)?;
+                Ok(Some(format!("{prefix}%")))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Sort `predictions` as requested, then apply the limit. Ties are broken
+    /// by pair so the order is stable.
+    fn arrange(&self, predictions: &mut Vec<Prediction>) {
+        let sort_by = self.sort_by.unwrap_or_default();
+        predictions.sort_by(|a, b| {
+            let ordering = match sort_by {
+                SortBy::Pair => a.pair.cmp(&b.pair),
+                SortBy::TsMs => a.ts_ms.cmp(&b.ts_ms),
+                SortBy::PredictedPrice => a.predicted_price.total_cmp(&b.predicted_price),
+            };
+            let ordering = match self.order.unwrap_or_default() {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            };
+            ordering.then_with(|| a.pair.cmp(&b.pair))
+        });
+        if let Some(limit) = self.limit {
+            predictions.truncate(limit);
+        }
+    }
 }
 
 impl PredictionQuery {
+    /// Newest `ts_ms` the lookup may return, from `as_of_ts_ms` and
+    /// `min_age_ms`.
+    fn max_ts_ms(&self) -> Option<i64> {
+        let min_age_cutoff = self
+            .min_age_ms
+            .map(|age| timestamp::now_ms().saturating_sub(age));
+        match (self.as_of_ts_ms, min_age_cutoff) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
     /// Validate the query parameters.
     pub fn validate(&self) -> Result<(), ApiError> {
-        if self.pair.is_empty() {
-            return Err(ApiError::BadRequest("pair cannot be empty".to_string()));
+        validate_pair(&self.pair)?;
+        if let Some(model_name) = &self.model_name {
+            validate_model_name(model_name)?;
         }
-        if self.pair.len() > 20 {
-            return Err(ApiError::BadRequest("pair is too long".to_string()));
+        if let Some(model_version) = &self.model_version {
+            validate_model_version(model_version)?;
         }
-        // Basic alphanumeric check
-        if !self.pair.chars().all(|c| c.is_alphanumeric()) {
+        parse_horizon(self.horizon.as_deref())?;
+        validate_max_interval_pct(self.max_interval_pct)?;
+        if self.min_age_ms.is_some_and(|age| age < 0) {
             return Err(ApiError::BadRequest(
-                "pair must be alphanumeric".to_string(),
+                "min_age_ms cannot be negative".to_string(),
+            ));
+        }
+        if self.wait == Some(true) {
+            if self.since_ts_ms.is_none() {
+                return Err(ApiError::BadRequest(
+                    "wait requires since_ts_ms".to_string(),
+                ));
+            }
+            // Neither lookup ever sees a prediction as it is written
+            if self.as_of_ts_ms.is_some() || self.min_age_ms.is_some() {
+                return Err(ApiError::BadRequest(
+                    "wait cannot be combined with as_of_ts_ms or min_age_ms".to_string(),
+                ));
+            }
+        } else if self.since_ts_ms.is_some() {
+            return Err(ApiError::BadRequest(
+                "since_ts_ms requires wait=true".to_string(),
             ));
         }
         Ok(())
     }
+
+    /// With `wait`, the `ts_ms` a prediction must be newer than to be
+    /// returned.
+    fn wait_since(&self) -> Option<i64> {
+        self.since_ts_ms.filter(|_| self.wait == Some(true))
+    }
+}
+
+/// Validate a trading pair symbol.
+pub fn validate_pair(pair: &str) -> Result<(), ApiError> {
+    if pair.is_empty() {
+        return Err(ApiError::BadRequest("pair cannot be empty".to_string()));
+    }
+    if pair.len() > 20 {
+        return Err(ApiError::BadRequest("pair is too long".to_string()));
+    }
+    // Stored symbols are ASCII, so Unicode look-alikes can never match
+    if !pair.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ApiError::BadRequest(
+            "pair must be ASCII alphanumeric".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Translate a pair glob (`*` any run, `?` any one character) into a `LIKE`
+/// pattern.
+///
+/// Apart from the wildcards only ASCII letters and digits are allowed, so
+/// the result can never contain `LIKE` metacharacters of its own.
+fn glob_to_like(glob: &str) -> Result<String, ApiError> {
+    if glob.is_empty() {
+        return Err(ApiError::BadRequest("pair cannot be empty".to_string()));
+    }
+    if glob.len() > 20 {
+        return Err(ApiError::BadRequest("pair is too long".to_string()));
+    }
+    if !glob
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '*' | '?'))
+    {
+        return Err(ApiError::BadRequest(
+            "pair may only contain ASCII letters, digits, '*' and '?'".to_string(),
+        ));
+    }
+
+    Ok(glob
+        .chars()
+        .map(|c| match c {
+            '*' => '%',
+            '?' => '_',
+            c => c,
+        })
+        .collect())
+}
+
+/// Parse a `horizon` parameter into milliseconds.
+pub fn parse_horizon(horizon: Option<&str>) -> Result<Option<i64>, ApiError> {
+    horizon
+        .map(|h| {
+            timestamp::parse_duration_ms(h).ok_or_else(|| {
+                ApiError::BadRequest(
+                    "horizon must be a positive duration like 300s, 4h or 1d".to_string(),
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Validate a `max_interval_pct` filter.
+pub fn validate_max_interval_pct(max_interval_pct: Option<f64>) -> Result<(), ApiError> {
+    if max_interval_pct.is_some_and(|pct| !pct.is_finite() || pct < 0.0) {
+        return Err(ApiError::BadRequest(
+            "max_interval_pct must be a non-negative number".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Validate a model name filter.
+pub fn validate_model_name(model_name: &str) -> Result<(), ApiError> {
+    validate_model_field("model_name", model_name)
+}
+
+/// Validate a model version filter.
+pub fn validate_model_version(model_version: &str) -> Result<(), ApiError> {
+    validate_model_field("model_version", model_version)
+}
+
+fn validate_model_field(field: &str, value: &str) -> Result<(), ApiError> {
+    if value.is_empty() {
+        return Err(ApiError::BadRequest(format!("{field} cannot be empty")));
+    }
+    if value.len() > 64 {
+        return Err(ApiError::BadRequest(format!("{field} is too long")));
+    }
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "{field} may only contain letters, digits, '_', '-' and '.'"
+        )));
+    }
+    Ok(())
 }
 
 /// Prediction response.
-#[derive(Debug, Serialize, ToSchema)]
+///
+/// Which timestamp fields are present depends on `TIMESTAMP_FORMAT`: the
+/// `*_ms` fields for `ms` and `both`, the `*_iso` fields for `iso` and `both`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct Prediction {
     /// Trading pair
     pub pair: String,
-    /// Predicted price
+    /// Predicted price; null when `valid` is false
     pub predicted_price: f64,
     /// Timestamp when prediction was made (ms)
+    #[serde(skip_serializing_if = "timestamp::omit_ms")]
     pub ts_ms: i64,
-    /// Timestamp for which price is predicted (ms)
-    pub predicted_ts_ms: i64,
+    /// Timestamp when prediction was made (ISO-8601, UTC)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ts_iso: Option<String>,
+    /// Timestamp for which price is predicted (ms), or null when the model
+    /// emits a current fair value with no target time
+    #[serde(skip_serializing_if = "timestamp::omit_ms")]
+    pub predicted_ts_ms: Option<i64>,
+    /// Timestamp for which price is predicted (ISO-8601, UTC), omitted when
+    /// there is no target time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub predicted_ts_iso: Option<String>,
+    /// How far ahead the prediction looks (`predicted_ts_ms - ts_ms`), or
+    /// null for a current fair value
+    pub horizon_ms: Option<i64>,
+    /// Lower end of the prediction interval, from probabilistic models only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lower_bound: Option<f64>,
+    /// Upper end of the prediction interval, from probabilistic models only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upper_bound: Option<f64>,
+    /// Quantile level `predicted_price` represents, e.g. 0.5 for a median
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantile: Option<f64>,
+    /// Latest observed market price for the pair (close of its newest
+    /// candle); omitted when no candle is available
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_price: Option<f64>,
+    /// `predicted_price - current_price`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta_abs: Option<f64>,
+    /// `delta_abs` as a percentage of `current_price`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta_pct: Option<f64>,
+    /// Whether the prediction is above, below or within
+    /// `DIRECTION_FLAT_THRESHOLD_PCT` of `current_price`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
     /// Model name used for prediction
     pub model_name: String,
     /// Model version
     pub model_version: String,
+    /// True when the requested model had no prediction and the latest
+    /// prediction from any model was returned instead
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub fallback: bool,
+    /// Side of the pair's A/B split the prediction was served from; omitted
+    /// when the pair has no split or the request named a model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<Variant>,
+    /// False when the stored price is NaN or infinite; only served with
+    /// `NON_FINITE_PRICE=lenient`
+    #[serde(skip_serializing_if = "is_true")]
+    pub valid: bool,
+}
+
+/// Predicted move relative to the current market price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Up,
+    Down,
+    Flat,
+}
+
+impl Direction {
+    /// Classify a percentage change; moves of at most `flat_threshold_pct`
+    /// in either direction are flat.
+    fn from_delta_pct(delta_pct: f64, flat_threshold_pct: f64) -> Self {
+        if delta_pct.abs() <= flat_threshold_pct {
+            Self::Flat
+        } else if delta_pct > 0.0 {
+            Self::Up
+        } else {
+            Self::Down
+        }
+    }
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+impl Prediction {
+    /// Record the current market price and the prediction's distance and
+    /// direction from it.
+    fn set_current_price(&mut self, current_price: f64, flat_threshold_pct: f64) {
+        self.current_price = Some(current_price);
+        if !self.valid || !current_price.is_finite() {
+            return;
+        }
+        let delta_abs = self.predicted_price - current_price;
+        self.delta_abs = Some(delta_abs);
+        self.delta_pct = (current_price != 0.0).then(|| delta_abs / current_price * 100.0);
+        self.direction = self
+            .delta_pct
+            .map(|pct| Direction::from_delta_pct(pct, flat_threshold_pct));
+    }
+
+    /// A valid `lgbm` v1 prediction of 65000 for tests to adjust.
+    #[cfg(test)]
+    pub fn sample(pair: &str, ts_ms: i64) -> Self {
+        Self {
+            pair: pair.to_string(),
+            predicted_price: 65_000.0,
+            ts_ms,
+            ts_iso: None,
+            predicted_ts_ms: None,
+            predicted_ts_iso: None,
+            horizon_ms: None,
+            lower_bound: None,
+            upper_bound: None,
+            quantile: None,
+            current_price: None,
+            delta_abs: None,
+            delta_pct: None,
+            direction: None,
+            model_name: "lgbm".to_string(),
+            model_version: "v1".to_string(),
+            fallback: false,
+            variant: None,
+            valid: true,
+        }
+    }
+}
+
+/// Fields filled in by `attach_current_prices`.
+pub const CURRENT_PRICE_FIELDS: &[&str] = &["current_price", "delta_abs", "delta_pct", "direction"];
+
+/// Fill in `current_price`, the deltas and `direction` from the latest
+/// candles.
+///
+/// The market price is supplementary, so a failed lookup is logged and the
+/// predictions are served without it.
+pub async fn attach_current_prices(state: &AppState, predictions: &mut [Prediction]) {
+    if predictions.is_empty() {
+        return;
+    }
+    let mut pairs: Vec<String> = predictions.iter().map(|p| p.pair.clone()).collect();
+    pairs.sort();
+    pairs.dedup();
+
+    let prices = match db::get_current_prices(&state.pool, &pairs).await {
+        Ok(prices) => prices,
+        Err(e) => {
+            tracing::warn!(error = %e, "Current price lookup failed");
+            return;
+        }
+    };
+    for prediction in predictions {
+        if let Some(&price) = prices.get(&prediction.pair) {
+            prediction.set_current_price(price, state.config.direction_flat_threshold_pct);
+        }
+    }
 }
 
 /// Get the latest prediction for a trading pair.
 ///
 /// Returns the most recent price prediction for the specified trading pair.
+/// When the pair has a production version in the model registry, only its
+/// predictions are served unless `model_name` or `model_version` is given.
+/// If the pair also has an A/B split, the request is served from the
+/// variant it is assigned, sticky by API key or pair, and `variant` says
+/// which.
+///
+/// With `model_name`, `model_version` and/or `horizon`, only matching
+/// predictions are considered and a miss is a 404. Adding `fallback=latest` instead returns the latest
+/// prediction from any model, flagged with `"fallback": true`.
+///
+/// With `min_age_ms`, predictions younger than that are skipped so clients
+/// only see values that have had time to settle.
+///
+/// With `as_of_ts_ms`, the latest prediction made at or before that time is
+/// returned, reconstructing what a client would have seen then. Combined
+/// with `min_age_ms`, the earlier cutoff wins.
+///
+/// With `profile`, only that profile's fields are returned; `fields` lists
+/// them directly instead (e.g. `fields=pair,predicted_price,ts_ms`).
+///
+/// `current_price`, `delta_abs` and `delta_pct` compare the prediction with
+/// the pair's latest candle close.
+///
+/// The response carries a weak `ETag` derived from the prediction; clients
+/// that send it back as `If-None-Match` get an empty `304 Not Modified` until
+/// a newer prediction is written or this one is replaced.
+///
+/// With `wait=true`, the request is held until a prediction newer than
+/// `since_ts_ms` exists and that prediction is returned. If none is written
+/// within `LONG_POLL_TIMEOUT_MS`, the answer is an empty `204 No Content`
+/// and the client polls again with the same `since_ts_ms`.
+///
+/// With `max_interval_pct`, predictions with a wider interval, or none, are
+/// skipped: the latest that is tight enough is returned, and `404` when
+/// none is.
 #[utoipa::path(
     get,
     path = "/predictions",
-    params(PredictionQuery),
+    params(
+        PredictionQuery,
+        TimestampQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag from a previous response")
+    ),
     responses(
-        (status = 200, description = "Prediction found", body = Prediction),
+        (status = 200, description = "Prediction found", body = Prediction,
+            headers(("ETag" = String, description = "Weak validator for the prediction"))),
+        (status = 204, description = "No newer prediction was written before the long poll timed out"),
+        (status = 304, description = "Prediction unchanged since If-None-Match"),
         (status = 400, description = "Invalid request"),
         (status = 404, description = "Prediction not found")
     ),
     tag = "predictions"
 )]
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(state, headers))]
 pub async fn get_prediction(
-    State(pool): State<PgPool>,
+    State(state): State<AppState>,
     Query(params): Query<PredictionQuery>,
-) -> Result<Json<Prediction>, ApiError> {
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
     params.validate()?;
+    let fields = state
+        .config
+        .prediction_profiles
+        .select(params.profile.as_deref(), params.fields.as_deref())?;
+    let pool = &state.pool;
+
+    tracing::info!(
+        pair = %params.pair,
+        model_name = ?params.model_name,
+        model_version = ?params.model_version,
+        "Fetching prediction"
+    );
+
+    let max_ts_ms = params.max_ts_ms();
+    let model = ModelFilter {
+        name: params.model_name.as_deref(),
+        version: params.model_version.as_deref(),
+        horizon_ms: parse_horizon(params.horizon.as_deref())?,
+    };
 
-    tracing::info!(pair = %params.pair, "Fetching prediction");
+    // Subscribe before the first lookup, so nothing written in between is
+    // missed
+    let mut updates = params.wait_since().map(|_| state.feed.subscribe());
+    let deadline = Instant::now() + Duration::from_millis(state.config.long_poll_timeout_ms);
 
-    let prediction = db::get_latest_prediction(&pool, &params.pair).await?;
+    let prediction = loop {
+        let mut prediction = db::get_latest_prediction(
+            pool,
+            &params.pair,
+            model,
+            max_ts_ms,
+            params.max_interval_pct,
+        )
+        .await?;
+
+        if prediction.is_none() && !model.is_empty() && params.fallback == Some(Fallback::Latest) {
+            tracing::info!(
+                pair = %params.pair,
+                model_name = ?params.model_name,
+                model_version = ?params.model_version,
+                "No prediction for model, falling back to latest"
+            );
+            prediction = db::get_latest_prediction(
+                pool,
+                &params.pair,
+                ModelFilter::default(),
+                max_ts_ms,
+                params.max_interval_pct,
+            )
+            .await?
+            .map(|p| Prediction {
+                fallback: true,
+                ..p
+            });
+        }
+
+        // Swap in the candidate before checking for a newer prediction, as
+        // it is the one served
+        if let Some(p) = prediction.as_mut() {
+            if params.model_name.is_none() && params.model_version.is_none() {
+                experiments::apply(
+                    &state,
+                    &headers,
+                    std::slice::from_mut(p),
+                    model,
+                    max_ts_ms,
+                    params.max_interval_pct,
+                )
+                .await?;
+            }
+        }
+
+        let (Some(since), Some(updates)) = (params.wait_since(), updates.as_mut()) else {
+            break prediction;
+        };
+        if prediction.as_ref().is_some_and(|p| p.ts_ms > since) {
+            break prediction;
+        }
+        if !state.feed.changed(updates, &params.pair, deadline).await {
+            tracing::debug!(pair = %params.pair, since_ts_ms = since, "Long poll timed out");
+            return Ok((
+                StatusCode::NO_CONTENT,
+                [(header::CACHE_CONTROL, "no-store")],
+            )
+                .into_response());
+        }
+    };
 
     match prediction {
-        Some(p) => {
+        Some(mut p) => {
             tracing::debug!(pair = %p.pair, price = %p.predicted_price, "Prediction found");
-            Ok(Json(p))
+            let etag = etag(std::slice::from_ref(&p));
+            let mut validators = vec![(header::ETAG, etag.clone())];
+            if let Some(vary) = experiments::vary(&state, std::slice::from_ref(&p)) {
+                validators.push((header::VARY, vary.to_string()));
+            }
+            if not_modified(&headers, &etag, None) {
+                return Ok((StatusCode::NOT_MODIFIED, AppendHeaders(validators)).into_response());
+            }
+            if keeps_any(fields.as_deref(), CURRENT_PRICE_FIELDS) {
+                attach_current_prices(&state, std::slice::from_mut(&mut p)).await;
+            }
+            Ok((AppendHeaders(validators), Projected { body: p, fields }).into_response())
         }
         None => {
             tracing::warn!(pair = %params.pair, "Prediction not found");
-            Err(ApiError::NotFound(params.pair))
+            if model.is_empty() {
+                Err(ApiError::NotFound(params.pair.clone()))
+            } else {
+                Err(ApiError::NotFound(format!("{} ({})", params.pair, model)))
+            }
         }
     }
 }
 
 /// Get the latest predictions for all trading pairs.
 ///
-/// Returns the most recent price prediction for each trading pair.
+/// Returns the most recent price prediction for each trading pair, from
+/// the pair's production version if it has one, or from the variant of
+/// its A/B split the request is assigned.
+///
+/// The response carries a `Last-Modified` header derived from the newest
+/// `ts_ms` in the snapshot. Clients that send it back as `If-Modified-Since`
+/// get an empty `304 Not Modified` until a newer prediction is written.
+/// HTTP dates have one-second resolution, so the comparison is made on
+/// whole seconds. A weak `ETag` covers the same snapshot's stored fields,
+/// so it also changes when a prediction is replaced in place or served from
+/// another version, and is checked against `If-None-Match`, which takes
+/// precedence. A change in `current_price` alone does not invalidate the
+/// snapshot.
+///
+/// `pair=BTC*` (or `pair_prefix=BTC`) restricts the snapshot to matching
+/// pairs, e.g. every BTC quote pair.
+///
+/// `sort_by`, `order` and `limit` select e.g. the ten most recently updated
+/// pairs (`sort_by=ts_ms&order=desc&limit=10`). With `profile` or `fields`,
+/// only the selected fields are returned.
 #[utoipa::path(
     get,
     path = "/predictions/latest",
+    params(
+        LatestQuery,
+        EnvelopeQuery,
+        TimestampQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag from a previous response"),
+        ("If-Modified-Since" = Option<String>, Header, description = "HTTP date from a previous Last-Modified")
+    ),
     responses(
-        (status = 200, description = "List of latest predictions", body = Vec<Prediction>)
+        (status = 200, description = "List of latest predictions", body = Vec<Prediction>,
+            headers(
+                ("ETag" = String, description = "Weak validator for the snapshot"),
+                ("Last-Modified" = String, description = "Time of the newest prediction")
+            )),
+        (status = 304, description = "Snapshot unchanged since If-None-Match or If-Modified-Since"),
+        (status = 400, description = "Invalid request")
     ),
     tag = "predictions"
 )]
-#[tracing::instrument(skip(pool))]
-pub async fn get_all_latest(State(pool): State<PgPool>) -> Result<Json<Vec<Prediction>>, ApiError> {
+#[tracing::instrument(skip(state, headers))]
+pub async fn get_all_latest(
+    State(state): State<AppState>,
+    Query(params): Query<LatestQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    params.validate()?;
+    let fields = state
+        .config
+        .prediction_profiles
+        .select(params.profile.as_deref(), params.fields.as_deref())?;
+
     tracing::info!("Fetching all latest predictions");
 
-    let predictions = db::get_all_latest_predictions(&pool).await?;
+    let pair_pattern = params.pair_pattern()?;
+    let mut predictions = db::get_all_latest_predictions(
+        &state.pool,
+        None,
+        pair_pattern.as_deref(),
+        params.max_interval_pct,
+        None,
+    )
+    .await?;
+    experiments::apply(
+        &state,
+        &headers,
+        &mut predictions,
+        ModelFilter::default(),
+        None,
+        params.max_interval_pct,
+    )
+    .await?;
+    params.arrange(&mut predictions);
 
     tracing::debug!(count = predictions.len(), "Predictions fetched");
 
-    Ok(Json(predictions))
+    let etag = etag(&predictions);
+    let last_modified = predictions
+        .iter()
+        .map(|p| p.ts_ms)
+        .max()
+        .and_then(ms_to_system_time);
+
+    let mut validators = vec![(header::ETAG, etag.clone())];
+    if let Some(last_modified) = last_modified {
+        validators.push((
+            header::LAST_MODIFIED,
+            httpdate::fmt_http_date(last_modified),
+        ));
+    }
+    if let Some(vary) = experiments::vary(&state, &predictions) {
+        validators.push((header::VARY, vary.to_string()));
+    }
+
+    if not_modified(&headers, &etag, last_modified) {
+        tracing::debug!("Snapshot not modified");
+        return Ok((StatusCode::NOT_MODIFIED, AppendHeaders(validators)).into_response());
+    }
+
+    if keeps_any(fields.as_deref(), CURRENT_PRICE_FIELDS) {
+        attach_current_prices(&state, &mut predictions).await;
+    }
+
+    Ok((
+        AppendHeaders(validators),
+        Projected {
+            body: predictions,
+            fields,
+        },
+    )
+        .into_response())
+}
+
+/// Request body for the latest predictions of several pairs.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LatestBatchRequest {
+    /// Trading pairs (e.g., ["BTCUSDT", "ETHUSDT"])
+    pub pairs: Vec<String>,
+}
+
+impl LatestBatchRequest {
+    /// Validate the request body.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.pairs.is_empty() {
+            return Err(ApiError::BadRequest("pairs cannot be empty".to_string()));
+        }
+        if self.pairs.len() > MAX_BATCH_PAIRS {
+            return Err(ApiError::BadRequest(format!(
+                "at most {MAX_BATCH_PAIRS} pairs per request"
+            )));
+        }
+        for pair in &self.pairs {
+            validate_pair(pair)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the latest prediction for several trading pairs at once.
+///
+/// Returns a map of pair to its latest prediction, from the pair's
+/// production version if it has one, or from the variant of its A/B split
+/// the request is assigned; requested pairs without predictions map to
+/// null. One request replaces a round of per-pair calls.
+#[utoipa::path(
+    post,
+    path = "/predictions/batch",
+    request_body = LatestBatchRequest,
+    responses(
+        (status = 200, description = "Latest prediction per pair, null when none", body = BTreeMap<String, Prediction>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state, headers))]
+pub async fn get_latest_batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<LatestBatchRequest>,
+) -> Result<Json<BTreeMap<String, Option<Prediction>>>, ApiError> {
+    request.validate()?;
+
+    tracing::info!(
+        pairs = request.pairs.len(),
+        "Fetching latest predictions for pairs"
+    );
+
+    let mut rows = db::get_latest_for_pairs(&state.pool, &request.pairs).await?;
+    experiments::apply(
+        &state,
+        &headers,
+        &mut rows,
+        ModelFilter::default(),
+        None,
+        None,
+    )
+    .await?;
+    attach_current_prices(&state, &mut rows).await;
+
+    let mut latest: BTreeMap<String, Option<Prediction>> =
+        request.pairs.into_iter().map(|pair| (pair, None)).collect();
+    for prediction in rows {
+        latest.insert(prediction.pair.clone(), Some(prediction));
+    }
+
+    tracing::debug!(pairs = latest.len(), "Latest predictions fetched");
+
+    Ok(Json(latest))
+}
+
+/// Most models a single comparison may name.
+pub const MAX_COMPARE_MODELS: usize = 20;
+
+/// Query parameters for comparing models on one pair.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct CompareQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Comma-separated model names (e.g., "lstm_v2,xgb_v4")
+    pub models: String,
+    /// Only consider predictions for this horizon, e.g. "4h"
+    pub horizon: Option<String>,
+}
+
+impl CompareQuery {
+    /// Validate the query parameters, returning the model names.
+    pub fn validate(&self) -> Result<Vec<String>, ApiError> {
+        validate_pair(&self.pair)?;
+        parse_horizon(self.horizon.as_deref())?;
+
+        let mut models: Vec<String> = self.models.split(',').map(str::to_string).collect();
+        for model_name in &models {
+            validate_model_name(model_name)?;
+        }
+        models.sort();
+        models.dedup();
+        if models.len() > MAX_COMPARE_MODELS {
+            return Err(ApiError::BadRequest(format!(
+                "at most {MAX_COMPARE_MODELS} models per comparison"
+            )));
+        }
+        Ok(models)
+    }
+}
+
+/// Compare the latest predictions of several models for one pair.
+///
+/// Returns a map of model name to that model's latest prediction for the
+/// pair; models without one map to null. Pass `horizon` so every model is
+/// compared on the same forecast horizon.
+#[utoipa::path(
+    get,
+    path = "/predictions/compare",
+    params(CompareQuery, TimestampQuery),
+    responses(
+        (status = 200, description = "Latest prediction per model, null when none", body = BTreeMap<String, Prediction>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn compare_models(
+    State(state): State<AppState>,
+    Query(params): Query<CompareQuery>,
+) -> Result<Json<BTreeMap<String, Option<Prediction>>>, ApiError> {
+    let models = params.validate()?;
+    let horizon_ms = parse_horizon(params.horizon.as_deref())?;
+
+    tracing::info!(pair = %params.pair, models = models.len(), "Comparing models");
+
+    let mut rows =
+        db::get_latest_for_models(&state.pool, &params.pair, &models, horizon_ms).await?;
+    attach_current_prices(&state, &mut rows).await;
+
+    let mut latest: BTreeMap<String, Option<Prediction>> =
+        models.into_iter().map(|model| (model, None)).collect();
+    for prediction in rows {
+        latest.insert(prediction.model_name.clone(), Some(prediction));
+    }
+
+    Ok(Json(latest))
+}
+
+/// Convert a millisecond Unix timestamp to a `SystemTime`.
+fn ms_to_system_time(ts_ms: i64) -> Option<SystemTime> {
+    let ms = u64::try_from(ts_ms).ok()?;
+    UNIX_EPOCH.checked_add(Duration::from_millis(ms))
+}
+
+/// Weak ETag for a response built from `predictions`, derived from each
+/// prediction's stored fields. It changes when a newer prediction is
+/// written, and when one is replaced in place or served from another model
+/// version; live fields such as `current_price` don't count.
+pub fn etag(predictions: &[Prediction]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for p in predictions {
+        p.pair.hash(&mut hasher);
+        p.model_name.hash(&mut hasher);
+        p.model_version.hash(&mut hasher);
+        p.ts_ms.hash(&mut hasher);
+        p.predicted_ts_ms.hash(&mut hasher);
+        p.predicted_price.to_bits().hash(&mut hasher);
+        p.lower_bound.map(f64::to_bits).hash(&mut hasher);
+        p.upper_bound.map(f64::to_bits).hash(&mut hasher);
+        p.quantile.map(f64::to_bits).hash(&mut hasher);
+    }
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Whether a conditional request can be answered with `304 Not Modified`.
+///
+/// `If-None-Match` is compared weakly against `etag` and, when present,
+/// takes precedence over `If-Modified-Since`, as RFC 9110 requires.
+pub fn not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        let etag = etag.trim_start_matches("W/");
+        return if_none_match.to_str().is_ok_and(|tags| {
+            tags.split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        });
+    }
+    last_modified.is_some_and(|t| !modified_since(headers, t))
+}
+
+/// Whether `last_modified` is newer than the request's `If-Modified-Since`.
+///
+/// A missing or unparseable header counts as modified, as RFC 9110 requires.
+fn modified_since(headers: &HeaderMap, last_modified: SystemTime) -> bool {
+    let Some(since) = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+    else {
+        return true;
+    };
+
+    let secs = |t: SystemTime| t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    secs(last_modified) > secs(since)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pair: &str) -> PredictionQuery {
+        PredictionQuery {
+            pair: pair.to_string(),
+            model_name: None,
+            model_version: None,
+            horizon: None,
+            fallback: None,
+            min_age_ms: None,
+            as_of_ts_ms: None,
+            profile: None,
+            fields: None,
+            wait: None,
+            since_ts_ms: None,
+            max_interval_pct: None,
+        }
+    }
+
+    fn rejection(pair: &str) -> String {
+        match query(pair).validate() {
+            Err(ApiError::BadRequest(msg)) => msg,
+            other => panic!("expected BadRequest for {pair:?}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn max_interval_pct_must_be_a_non_negative_number() {
+        let bounded = |max_interval_pct| PredictionQuery {
+            max_interval_pct: Some(max_interval_pct),
+            ..query("BTCUSDT")
+        };
+        assert!(bounded(0.0).validate().is_ok());
+        assert!(bounded(2.5).validate().is_ok());
+        assert!(bounded(-1.0).validate().is_err());
+        assert!(bounded(f64::NAN).validate().is_err());
+        assert!(bounded(f64::INFINITY).validate().is_err());
+    }
+
+    #[test]
+    fn wait_requires_since_and_a_live_lookup() {
+        let waiting = |since_ts_ms| PredictionQuery {
+            wait: Some(true),
+            since_ts_ms,
+            ..query("BTCUSDT")
+        };
+        assert!(waiting(Some(1_700_000_000_000)).validate().is_ok());
+        assert!(waiting(None).validate().is_err());
+        assert!(PredictionQuery {
+            as_of_ts_ms: Some(1_700_000_000_000),
+            ..waiting(Some(1_700_000_000_000))
+        }
+        .validate()
+        .is_err());
+        assert!(PredictionQuery {
+            since_ts_ms: Some(1_700_000_000_000),
+            ..query("BTCUSDT")
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn accepts_ascii_pair() {
+        assert!(query("BTCUSDT").validate().is_ok());
+        assert!(query("1000PEPEUSDT").validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_cyrillic_look_alikes() {
+        // "ВТС" is Cyrillic Ve, Te, Es
+        assert_eq!(rejection("ВТС"), "pair must be ASCII alphanumeric");
+        assert_eq!(rejection("ВТСUSDT"), "pair must be ASCII alphanumeric");
+    }
+
+    #[test]
+    fn rejects_full_width_digits() {
+        assert_eq!(rejection("BTC１２"), "pair must be ASCII alphanumeric");
+    }
+
+    #[test]
+    fn rejects_empty_pair() {
+        assert_eq!(rejection(""), "pair cannot be empty");
+    }
+
+    #[test]
+    fn non_finite_price_is_flagged_invalid() {
+        let prediction = Prediction {
+            predicted_price: f64::INFINITY,
+            valid: false,
+            ..Prediction::sample("BTCUSDT", 1_700_000_000_000)
+        };
+        let json = serde_json::to_value(&prediction).unwrap();
+        assert_eq!(json["predicted_price"], serde_json::Value::Null);
+        assert_eq!(json["valid"], false);
+
+        let json = serde_json::to_value(Prediction {
+            predicted_price: 65_000.0,
+            valid: true,
+            ..prediction
+        })
+        .unwrap();
+        assert!(json.get("valid").is_none());
+    }
+
+    fn latest(pair: &str, ts_ms: i64, predicted_price: f64) -> Prediction {
+        Prediction {
+            predicted_price,
+            ..Prediction::sample(pair, ts_ms)
+        }
+    }
+
+    #[test]
+    fn translates_pair_globs() {
+        assert_eq!(glob_to_like("BTC*").unwrap(), "BTC%");
+        assert_eq!(glob_to_like("*USD?").unwrap(), "%USD_");
+        assert_eq!(glob_to_like("BTCUSDT").unwrap(), "BTCUSDT");
+        assert!(glob_to_like("BTC%").is_err());
+        assert!(glob_to_like("BTC_*").is_err());
+        assert!(glob_to_like("").is_err());
+    }
+
+    #[test]
+    fn compare_dedups_and_validates_models() {
+        let compare = |models: &str| CompareQuery {
+            pair: "BTCUSDT".to_string(),
+            models: models.to_string(),
+            horizon: None,
+        };
+        assert_eq!(
+            compare("xgb_v4,lstm_v2,xgb_v4").validate().unwrap(),
+            ["lstm_v2", "xgb_v4"]
+        );
+        assert!(compare("lstm_v2,").validate().is_err());
+        assert!(compare("lstm v2").validate().is_err());
+    }
+
+    #[test]
+    fn deltas_against_current_price() {
+        let mut prediction = latest("BTCUSDT", 1, 66_000.0);
+        prediction.set_current_price(64_000.0, 0.1);
+        assert_eq!(prediction.delta_abs, Some(2_000.0));
+        assert_eq!(prediction.delta_pct, Some(3.125));
+        assert_eq!(prediction.direction, Some(Direction::Up));
+
+        let mut prediction = latest("BTCUSDT", 1, 1.0);
+        prediction.set_current_price(0.0, 0.1);
+        assert_eq!(prediction.delta_abs, Some(1.0));
+        assert_eq!(prediction.delta_pct, None);
+
+        let mut prediction = Prediction {
+            valid: false,
+            ..latest("BTCUSDT", 1, f64::NAN)
+        };
+        prediction.set_current_price(64_000.0, 0.1);
+        assert_eq!(prediction.current_price, Some(64_000.0));
+        assert_eq!(prediction.delta_abs, None);
+    }
+
+    #[test]
+    fn direction_respects_flat_threshold() {
+        assert_eq!(Direction::from_delta_pct(0.05, 0.1), Direction::Flat);
+        assert_eq!(Direction::from_delta_pct(-0.1, 0.1), Direction::Flat);
+        assert_eq!(Direction::from_delta_pct(-0.2, 0.1), Direction::Down);
+        assert_eq!(Direction::from_delta_pct(0.01, 0.0), Direction::Up);
+        assert_eq!(Direction::from_delta_pct(0.0, 0.0), Direction::Flat);
+    }
+
+    #[test]
+    fn arranges_most_recent_first_with_limit() {
+        let query = LatestQuery {
+            pair: None,
+            pair_prefix: None,
+            limit: Some(2),
+            sort_by: Some(SortBy::TsMs),
+            order: Some(SortOrder::Desc),
+            profile: None,
+            fields: None,
+            max_interval_pct: None,
+        };
+        let mut predictions = vec![
+            latest("BTCUSDT", 1, 65_000.0),
+            latest("ETHUSDT", 3, 3_500.0),
+            latest("SOLUSDT", 2, 150.0),
+        ];
+        query.arrange(&mut predictions);
+
+        let pairs: Vec<_> = predictions.iter().map(|p| p.pair.as_str()).collect();
+        assert_eq!(pairs, ["ETHUSDT", "SOLUSDT"]);
+    }
+
+    #[test]
+    fn arranges_by_price_with_pair_tie_break() {
+        let query = LatestQuery {
+            pair: None,
+            pair_prefix: None,
+            limit: None,
+            sort_by: Some(SortBy::PredictedPrice),
+            order: None,
+            profile: None,
+            fields: None,
+            max_interval_pct: None,
+        };
+        let mut predictions = vec![
+            latest("SOLUSDT", 1, 150.0),
+            latest("ETHUSDT", 1, 150.0),
+            latest("BTCUSDT", 1, 65_000.0),
+        ];
+        query.arrange(&mut predictions);
+
+        let pairs: Vec<_> = predictions.iter().map(|p| p.pair.as_str()).collect();
+        assert_eq!(pairs, ["ETHUSDT", "SOLUSDT", "BTCUSDT"]);
+    }
+
+    #[test]
+    fn as_of_and_min_age_take_the_earlier_cutoff() {
+        let mut q = query("BTCUSDT");
+        assert_eq!(q.max_ts_ms(), None);
+
+        q.as_of_ts_ms = Some(1_700_000_000_000);
+        assert_eq!(q.max_ts_ms(), Some(1_700_000_000_000));
+
+        // A day's minimum age from now is still later than 2023
+        q.min_age_ms = Some(86_400_000);
+        assert_eq!(q.max_ts_ms(), Some(1_700_000_000_000));
+
+        q.as_of_ts_ms = Some(i64::MAX);
+        assert!(q.max_ts_ms().unwrap() < timestamp::now_ms());
+    }
+
+    #[test]
+    fn etag_changes_only_with_stored_fields() {
+        let snapshot = vec![
+            latest("BTCUSDT", 1, 65_000.0),
+            latest("ETHUSDT", 1, 3_500.0),
+        ];
+        let etag = etag(&snapshot);
+        assert!(etag.starts_with("W/\""));
+
+        let mut repriced = vec![
+            latest("BTCUSDT", 1, 65_000.0),
+            latest("ETHUSDT", 1, 3_500.0),
+        ];
+        repriced[0].current_price = Some(64_000.0);
+        assert_eq!(super::etag(&repriced), etag);
+
+        // Replaced in place under the same key
+        repriced[0].predicted_price = 64_500.0;
+        assert_ne!(super::etag(&repriced), etag);
+        repriced[0].predicted_price = 65_000.0;
+        repriced[0].model_version = "v2".to_string();
+        assert_ne!(super::etag(&repriced), etag);
+        repriced[0].model_version = snapshot[0].model_version.clone();
+        assert_eq!(super::etag(&repriced), etag);
+
+        repriced[1].ts_ms = 2;
+        assert_ne!(super::etag(&repriced), etag);
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence() {
+        let etag = etag(&[latest("BTCUSDT", 1, 65_000.0)]);
+        let last_modified = ms_to_system_time(1_700_000_000_000);
+        let headers = |pairs: &[(header::HeaderName, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(name, value.parse().unwrap());
+            }
+            headers
+        };
+        let since = httpdate::fmt_http_date(last_modified.unwrap());
+
+        let strong = etag.trim_start_matches("W/").to_string();
+        assert!(not_modified(
+            &headers(&[(header::IF_NONE_MATCH, &strong)]),
+            &etag,
+            None
+        ));
+        assert!(not_modified(
+            &headers(&[(header::IF_NONE_MATCH, &format!("\"x\", {etag}"))]),
+            &etag,
+            None
+        ));
+        assert!(not_modified(
+            &headers(&[(header::IF_MODIFIED_SINCE, &since)]),
+            &etag,
+            last_modified
+        ));
+        assert!(!not_modified(
+            &headers(&[
+                (header::IF_NONE_MATCH, "\"x\""),
+                (header::IF_MODIFIED_SINCE, &since)
+            ]),
+            &etag,
+            last_modified
+        ));
+        assert!(!not_modified(&HeaderMap::new(), &etag, last_modified));
+    }
 }
@@ -0,0 +1,220 @@
+//! Realized market prices.
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db;
+use crate::envelope::EnvelopeQuery;
+use crate::error::ApiError;
+use crate::routes::history::validate_range;
+use crate::routes::predictions::validate_pair;
+use crate::state::AppState;
+
+/// Most prices a single request may return.
+const MAX_PRICE_ROWS: usize = 50_000;
+
+/// Query parameters for a pair's realized prices.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct PricesQuery {
+    /// Trading pair (e.g., "BTCUSDT")
+    pub pair: String,
+    /// Start of the range, inclusive (ms)
+    pub from_ts_ms: i64,
+    /// End of the range, inclusive (ms)
+    pub to_ts_ms: i64,
+}
+
+/// Market price of a pair at one point in time.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PricePoint {
+    /// Close of the minute ending at this time (ms)
+    pub ts_ms: i64,
+    pub price: f64,
+}
+
+/// Span of time without prices for a pair.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PriceGap {
+    /// Start of the first minute without a price, inclusive (ms)
+    pub start_ts_ms: i64,
+    /// End of the last minute without a price, exclusive (ms)
+    pub end_ts_ms: i64,
+}
+
+/// Get a pair's realized prices within a time range.
+///
+/// These are the prices predictions are evaluated against by the
+/// `/metrics` endpoints: one close per minute, oldest first.
+#[utoipa::path(
+    get,
+    path = "/prices",
+    params(PricesQuery, EnvelopeQuery),
+    responses(
+        (status = 200, description = "Prices in the range, oldest first", body = Vec<PricePoint>),
+        (status = 400, description = "Invalid request or too many rows")
+    ),
+    tag = "prices"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_prices(
+    State(state): State<AppState>,
+    Query(params): Query<PricesQuery>,
+) -> Result<Json<Vec<PricePoint>>, ApiError> {
+    validate_pair(&params.pair)?;
+    validate_range(
+        params.from_ts_ms,
+        params.to_ts_ms,
+        state.config.max_history_range_ms,
+    )?;
+
+    tracing::info!(pair = %params.pair, "Fetching prices");
+
+    let prices = db::get_prices(
+        &state.pool,
+        &params.pair,
+        params.from_ts_ms,
+        params.to_ts_ms,
+        MAX_PRICE_ROWS as i64 + 1,
+    )
+    .await?;
+
+    if prices.len() > MAX_PRICE_ROWS {
+        return Err(ApiError::BadRequest(format!(
+            "result exceeds {MAX_PRICE_ROWS} rows; narrow the range"
+        )));
+    }
+
+    tracing::debug!(count = prices.len(), "Prices fetched");
+
+    Ok(Json(prices))
+}
+
+/// Find the minutes within a time range that have no price for a pair.
+///
+/// Prices are only recorded while the trades service is connected, so
+/// minutes it was down for are missing. Each gap is the span of trades to
+/// fetch again, e.g. with the trades service's historical mode and
+/// `HISTORICAL_START_MS`/`HISTORICAL_END_MS`. Minutes that closed moments
+/// ago may not have made it through the pipeline yet and show up as gaps
+/// too.
+#[utoipa::path(
+    get,
+    path = "/prices/gaps",
+    params(PricesQuery, EnvelopeQuery),
+    responses(
+        (status = 200, description = "Gaps in the range, oldest first", body = Vec<PriceGap>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "prices"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn get_price_gaps(
+    State(state): State<AppState>,
+    Query(params): Query<PricesQuery>,
+) -> Result<Json<Vec<PriceGap>>, ApiError> {
+    validate_pair(&params.pair)?;
+    validate_range(
+        params.from_ts_ms,
+        params.to_ts_ms,
+        state.config.max_history_range_ms,
+    )?;
+
+    tracing::info!(pair = %params.pair, "Finding price gaps");
+
+    let present = db::get_price_times(
+        &state.pool,
+        &params.pair,
+        params.from_ts_ms,
+        params.to_ts_ms,
+    )
+    .await?;
+    let gaps = find_gaps(params.from_ts_ms, params.to_ts_ms, &present);
+
+    tracing::debug!(count = gaps.len(), "Price gaps found");
+
+    Ok(Json(gaps))
+}
+
+/// The gaps among the minutes closing within `[from_ts_ms, to_ts_ms]`,
+/// given the closes `present` there, sorted. A missing close at `ts_ms`
+/// leaves the trades of `[ts_ms - PRICE_STEP_MS, ts_ms)` uncounted;
+/// consecutive ones merge into one gap.
+fn find_gaps(from_ts_ms: i64, to_ts_ms: i64, present: &[i64]) -> Vec<PriceGap> {
+    let step = db::PRICE_STEP_MS;
+    // First minute close at or after the start
+    let mut ts_ms = from_ts_ms.saturating_add((step - from_ts_ms.rem_euclid(step)) % step);
+    let mut present = present.iter().copied().peekable();
+    let mut gaps: Vec<PriceGap> = Vec::new();
+    while ts_ms <= to_ts_ms {
+        while present.next_if(|&p| p < ts_ms).is_some() {}
+        if present.next_if_eq(&ts_ms).is_none() {
+            match gaps.last_mut() {
+                Some(gap) if gap.end_ts_ms == ts_ms - step => gap.end_ts_ms = ts_ms,
+                _ => gaps.push(PriceGap {
+                    start_ts_ms: ts_ms - step,
+                    end_ts_ms: ts_ms,
+                }),
+            }
+        }
+        let Some(next) = ts_ms.checked_add(step) else {
+            break;
+        };
+        ts_ms = next;
+    }
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = db::PRICE_STEP_MS;
+
+    fn spans(gaps: Vec<PriceGap>) -> Vec<(i64, i64)> {
+        gaps.into_iter()
+            .map(|gap| (gap.start_ts_ms, gap.end_ts_ms))
+            .collect()
+    }
+
+    #[test]
+    fn aligns_the_range_to_minute_closes() {
+        // 30s in is not a minute close, so the first one counted is at 1m
+        let present = [MINUTE, 2 * MINUTE, 3 * MINUTE];
+        assert_eq!(spans(find_gaps(30_000, 3 * MINUTE + 59_999, &present)), []);
+        assert_eq!(
+            spans(find_gaps(30_000, 4 * MINUTE, &present)),
+            [(3 * MINUTE, 4 * MINUTE)]
+        );
+        assert_eq!(spans(find_gaps(-30_000, MINUTE, &present)), [(-MINUTE, 0)]);
+    }
+
+    #[test]
+    fn finds_gaps_at_the_ends_and_merges_runs() {
+        let present = [3 * MINUTE, 4 * MINUTE, 7 * MINUTE];
+        assert_eq!(
+            spans(find_gaps(MINUTE, 9 * MINUTE, &present)),
+            [
+                (0, 2 * MINUTE),
+                (4 * MINUTE, 6 * MINUTE),
+                (7 * MINUTE, 9 * MINUTE)
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_an_empty_range_as_one_gap() {
+        assert_eq!(spans(find_gaps(MINUTE, 5 * MINUTE, &[])), [(0, 5 * MINUTE)]);
+        // No minute closes within it
+        assert_eq!(spans(find_gaps(MINUTE + 1, 2 * MINUTE - 1, &[])), []);
+    }
+
+    #[test]
+    fn finds_no_gaps_in_a_full_range() {
+        let present: Vec<i64> = (1..=5).map(|m| m * MINUTE).collect();
+        assert_eq!(spans(find_gaps(MINUTE, 5 * MINUTE, &present)), []);
+    }
+}
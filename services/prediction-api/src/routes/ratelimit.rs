@@ -0,0 +1,72 @@
+//! Rate limiter inspection for operators.
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::RateLimit;
+use crate::middleware::RateLimitStats;
+use crate::state::AppState;
+
+/// Configuration and activity of one route group's rate limiter.
+#[derive(Serialize, ToSchema)]
+pub struct RateLimitGroupStatus {
+    /// Sustained requests per second per client IP
+    pub per_second: u64,
+    /// Burst size per client IP
+    pub burst: u32,
+    /// Requests rejected with 429 since startup
+    pub rejected_total: u64,
+    /// Client IPs the limiter currently tracks; omitted when disabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracked_clients: Option<usize>,
+}
+
+impl RateLimitGroupStatus {
+    fn new(limit: &RateLimit, stats: &RateLimitStats) -> Self {
+        Self {
+            per_second: limit.per_second,
+            burst: limit.burst,
+            rejected_total: stats.rejected(),
+            tracked_clients: stats.tracked_keys(),
+        }
+    }
+}
+
+/// Effective rate limits by route group.
+#[derive(Serialize, ToSchema)]
+pub struct RateLimitResponse {
+    /// Whether rate limiting is applied at all (`RATE_LIMIT_ENABLED`)
+    pub enabled: bool,
+    pub read: RateLimitGroupStatus,
+    pub heavy: RateLimitGroupStatus,
+    pub write: RateLimitGroupStatus,
+    pub admin: RateLimitGroupStatus,
+}
+
+/// Effective rate-limit configuration and rejection counters.
+///
+/// Per-client bucket levels are not exposed by the limiter, so only the
+/// number of tracked clients is reported.
+#[utoipa::path(
+    get,
+    path = "/admin/ratelimit",
+    responses(
+        (status = 200, description = "Rate limiter state", body = RateLimitResponse),
+        (status = 401, description = "Missing or invalid admin API key")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+pub async fn get_rate_limits(State(state): State<AppState>) -> Json<RateLimitResponse> {
+    let config = &state.config;
+    let stats = &state.rate_limits;
+
+    Json(RateLimitResponse {
+        enabled: config.rate_limit_enabled,
+        read: RateLimitGroupStatus::new(&config.rate_limit_read, &stats.read),
+        heavy: RateLimitGroupStatus::new(&config.rate_limit_heavy, &stats.heavy),
+        write: RateLimitGroupStatus::new(&config.rate_limit_write, &stats.write),
+        admin: RateLimitGroupStatus::new(&config.rate_limit_admin, &stats.admin),
+    })
+}
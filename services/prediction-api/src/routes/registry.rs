@@ -0,0 +1,680 @@
+//! Model registry: the model versions known to the service, with the
+//! metadata needed to reproduce them.
+//!
+//! Unlike `GET /models`, which lists whatever has written predictions,
+//! registry entries are created by operators and exist before, during and
+//! after a version's time in service.
+//!
+//! A pair has at most one production version. Versions only enter
+//! production through promotion or rollback. While a pair has one, the
+//! latest-prediction lookups that name no model serve only that version's
+//! predictions: `GET /predictions` (long polls and `fallback=latest`
+//! included), `GET /predictions/latest`, `POST /predictions/batch` and,
+//! with `DEFAULT_PAIR` set, `GET /`. A staging version with a
+//! `traffic_pct` takes that share of the first three instead; see
+//! `experiments`.
+//!
+//! Every other read covers all versions, as a record of what was
+//! predicted: history, recent, exports, the `/predictions/stream` feed,
+//! webhooks, stats, accuracy metrics and `/models`. Those that take a
+//! `model_name` or `model_version` narrow to a version that way.
+
+use std::str::FromStr;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db;
+use crate::error::ApiError;
+use crate::routes::predictions::{validate_model_name, validate_model_version, validate_pair};
+use crate::routes::webhooks::new_id;
+use crate::state::AppState;
+use crate::timestamp;
+use crate::API_PREFIX;
+
+/// Longest artifact URI accepted.
+const MAX_URI_LEN: usize = 2048;
+
+/// Most input features a model may list.
+const MAX_FEATURES: usize = 500;
+
+/// Longest feature name accepted.
+const MAX_FEATURE_LEN: usize = 128;
+
+/// Where a model version is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelStatus {
+    /// Registered and possibly writing predictions, but not served by
+    /// default
+    Staging,
+    /// The version served for its pair
+    Production,
+    /// Out of service; kept for its history
+    Retired,
+}
+
+impl ModelStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ModelStatus::Staging => "staging",
+            ModelStatus::Production => "production",
+            ModelStatus::Retired => "retired",
+        }
+    }
+}
+
+impl FromStr for ModelStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "staging" => Ok(ModelStatus::Staging),
+            "production" => Ok(ModelStatus::Production),
+            "retired" => Ok(ModelStatus::Retired),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A registered model version.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RegisteredModel {
+    /// Registry id
+    pub id: String,
+    /// Trading pair the model predicts
+    pub pair: String,
+    /// Model name, as in its predictions
+    pub model_name: String,
+    /// Model version, as in its predictions
+    pub model_version: String,
+    /// Where the trained artifact is stored, e.g. `s3://models/lgbm/v3.onnx`
+    pub artifact_uri: Option<String>,
+    /// Input features, in the order the model expects them
+    pub features: Vec<String>,
+    /// Start of the training data window (ms)
+    pub training_from_ts_ms: Option<i64>,
+    /// End of the training data window (ms)
+    pub training_to_ts_ms: Option<i64>,
+    /// Lifecycle status
+    pub status: ModelStatus,
+    /// Share of the pair's default traffic (0-100) served from this staging
+    /// version instead of the production one
+    pub traffic_pct: i32,
+    /// When the version was last promoted to production (ms); null if never
+    pub promoted_ts_ms: Option<i64>,
+    /// When the version was registered (ms)
+    pub created_ts_ms: i64,
+    /// When the entry last changed (ms)
+    pub updated_ts_ms: i64,
+}
+
+/// Request body registering or changing a model version.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ModelRequest {
+    /// Trading pair the model predicts
+    pub pair: String,
+    /// Model name, as in its predictions
+    pub model_name: String,
+    /// Model version, as in its predictions
+    pub model_version: String,
+    /// Where the trained artifact is stored
+    pub artifact_uri: Option<String>,
+    /// Input features, in the order the model expects them
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Start of the training data window (ms)
+    pub training_from_ts_ms: Option<i64>,
+    /// End of the training data window (ms)
+    pub training_to_ts_ms: Option<i64>,
+    /// `staging` or `retired`; defaults to `staging` for new versions and
+    /// to the current status otherwise. Production is entered by promotion.
+    pub status: Option<ModelStatus>,
+    /// Share of the pair's default traffic (0-100) to serve from this
+    /// version while it is in staging and the pair has a production version
+    #[serde(default)]
+    pub traffic_pct: i32,
+}
+
+impl ModelRequest {
+    /// Validate the request body.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        validate_pair(&self.pair)?;
+        validate_model_name(&self.model_name)?;
+        validate_model_version(&self.model_version)?;
+        if let Some(uri) = &self.artifact_uri {
+            validate_artifact_uri(uri)?;
+        }
+        validate_features(&self.features)?;
+        if self.status == Some(ModelStatus::Production) {
+            return Err(ApiError::BadRequest(
+                "status cannot be set to production; promote the version instead".to_string(),
+            ));
+        }
+        if !(0..=100).contains(&self.traffic_pct) {
+            return Err(ApiError::BadRequest(
+                "traffic_pct must be between 0 and 100".to_string(),
+            ));
+        }
+
+        for ts_ms in [self.training_from_ts_ms, self.training_to_ts_ms]
+            .into_iter()
+            .flatten()
+        {
+            if ts_ms <= 0 {
+                return Err(ApiError::BadRequest(
+                    "training window timestamps must be positive".to_string(),
+                ));
+            }
+        }
+        if let (Some(from), Some(to)) = (self.training_from_ts_ms, self.training_to_ts_ms) {
+            if from >= to {
+                return Err(ApiError::BadRequest(
+                    "training_from_ts_ms must be before training_to_ts_ms".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validate an artifact URI: any `scheme:rest` form, since artifacts live
+/// in object stores as often as behind HTTP.
+fn validate_artifact_uri(uri: &str) -> Result<(), ApiError> {
+    if uri.len() > MAX_URI_LEN {
+        return Err(ApiError::BadRequest("artifact_uri is too long".to_string()));
+    }
+    let valid = uri.split_once(':').is_some_and(|(scheme, rest)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            && !rest.is_empty()
+            && !rest.chars().any(char::is_whitespace)
+    });
+    if !valid {
+        return Err(ApiError::BadRequest(
+            "artifact_uri must be an absolute URI such as s3://bucket/model.onnx".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_features(features: &[String]) -> Result<(), ApiError> {
+    if features.len() > MAX_FEATURES {
+        return Err(ApiError::BadRequest(format!(
+            "at most {MAX_FEATURES} features per model"
+        )));
+    }
+    for (i, feature) in features.iter().enumerate() {
+        if feature.is_empty() || feature.len() > MAX_FEATURE_LEN {
+            return Err(ApiError::BadRequest(format!(
+                "feature names must be 1 to {MAX_FEATURE_LEN} characters"
+            )));
+        }
+        if features[..i].contains(feature) {
+            return Err(ApiError::BadRequest(format!(
+                "feature {feature} is listed twice"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Query parameters for listing registered models.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct RegistryQuery {
+    /// Only list models for this pair
+    pub pair: Option<String>,
+    /// Only list versions of this model
+    pub model_name: Option<String>,
+    /// Only list models with this status
+    pub status: Option<ModelStatus>,
+}
+
+/// List registered models, by pair, name and registration time.
+#[utoipa::path(
+    get,
+    path = "/admin/models",
+    params(RegistryQuery),
+    responses(
+        (status = 200, description = "Registered models", body = Vec<RegisteredModel>),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid admin API key")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+pub async fn list_registered_models(
+    State(state): State<AppState>,
+    Query(params): Query<RegistryQuery>,
+) -> Result<Json<Vec<RegisteredModel>>, ApiError> {
+    if let Some(pair) = &params.pair {
+        validate_pair(pair)?;
+    }
+    if let Some(model_name) = &params.model_name {
+        validate_model_name(model_name)?;
+    }
+
+    Ok(Json(db::get_registered_models(&state.pool, &params).await?))
+}
+
+/// Register a model version.
+///
+/// A pair, model name and version can be registered once; registering it
+/// again is a 409.
+#[utoipa::path(
+    post,
+    path = "/admin/models",
+    request_body = ModelRequest,
+    responses(
+        (status = 201, description = "Model registered", body = RegisteredModel,
+            headers(("Location" = String, description = "URL of the new registry entry"))),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 409, description = "The version is already registered for the pair")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn register_model(
+    State(state): State<AppState>,
+    Json(request): Json<ModelRequest>,
+) -> Result<Response, ApiError> {
+    request.validate()?;
+
+    let now = timestamp::now_ms();
+    let model = RegisteredModel {
+        id: new_id(),
+        pair: request.pair,
+        model_name: request.model_name,
+        model_version: request.model_version,
+        artifact_uri: request.artifact_uri,
+        features: request.features,
+        training_from_ts_ms: request.training_from_ts_ms,
+        training_to_ts_ms: request.training_to_ts_ms,
+        status: request.status.unwrap_or(ModelStatus::Staging),
+        traffic_pct: request.traffic_pct,
+        promoted_ts_ms: None,
+        created_ts_ms: now,
+        updated_ts_ms: now,
+    };
+    check_split(&state, &model).await?;
+    if !db::insert_registered_model(&state.pool, &model).await? {
+        return Err(already_registered(
+            &model.pair,
+            &model.model_name,
+            &model.model_version,
+        ));
+    }
+
+    tracing::info!(
+        model = %model.id,
+        pair = %model.pair,
+        model_name = %model.model_name,
+        model_version = %model.model_version,
+        "Model registered"
+    );
+    Ok((
+        StatusCode::CREATED,
+        [(
+            header::LOCATION,
+            format!("{}/admin/models/{}", API_PREFIX, model.id),
+        )],
+        Json(model),
+    )
+        .into_response())
+}
+
+/// Get a registered model.
+#[utoipa::path(
+    get,
+    path = "/admin/models/{id}",
+    params(("id" = String, Path, description = "Registry id")),
+    responses(
+        (status = 200, description = "The registered model", body = RegisteredModel),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such model")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+pub async fn get_registered_model(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<RegisteredModel>, ApiError> {
+    db::get_registered_model(&state.pool, &id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::ModelNotFound(id))
+}
+
+/// Change a registered model's metadata and status.
+///
+/// A production version may be retired or sent back to staging, after
+/// which its pair serves every model again, but it cannot be moved to
+/// another pair, name or version.
+#[utoipa::path(
+    put,
+    path = "/admin/models/{id}",
+    params(("id" = String, Path, description = "Registry id")),
+    request_body = ModelRequest,
+    responses(
+        (status = 200, description = "The updated model", body = RegisteredModel),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such model"),
+        (status = 409, description = "Another entry has the same pair, name and version, or the version is in production")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn update_registered_model(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<ModelRequest>,
+) -> Result<Json<RegisteredModel>, ApiError> {
+    request.validate()?;
+
+    let Some(existing) = db::get_registered_model(&state.pool, &id).await? else {
+        return Err(ApiError::ModelNotFound(id));
+    };
+    if existing.status == ModelStatus::Production
+        && (existing.pair != request.pair
+            || existing.model_name != request.model_name
+            || existing.model_version != request.model_version)
+    {
+        return Err(ApiError::Conflict(
+            "a production version cannot change its pair, name or version".to_string(),
+        ));
+    }
+    let model = RegisteredModel {
+        pair: request.pair,
+        model_name: request.model_name,
+        model_version: request.model_version,
+        artifact_uri: request.artifact_uri,
+        features: request.features,
+        training_from_ts_ms: request.training_from_ts_ms,
+        training_to_ts_ms: request.training_to_ts_ms,
+        status: request.status.unwrap_or(existing.status),
+        traffic_pct: request.traffic_pct,
+        updated_ts_ms: timestamp::now_ms(),
+        ..existing
+    };
+    check_split(&state, &model).await?;
+    if !db::update_registered_model(&state.pool, &model).await? {
+        // Either removed meanwhile or clashing with another entry
+        return match db::get_registered_model(&state.pool, &id).await? {
+            Some(_) => Err(already_registered(
+                &model.pair,
+                &model.model_name,
+                &model.model_version,
+            )),
+            None => Err(ApiError::ModelNotFound(id)),
+        };
+    }
+
+    tracing::info!(model = %id, status = model.status.as_str(), "Model updated");
+    Ok(Json(model))
+}
+
+/// Promote a registered model to production for its pair.
+///
+/// The pair's current production version, if any, is retired in the same
+/// statement, so default endpoints switch from one version to the other
+/// with no moment in between. A traffic split the version took part in
+/// ends. Only versions that have written predictions
+/// for the pair can be promoted. Promoting the production version again
+/// changes nothing.
+#[utoipa::path(
+    post,
+    path = "/admin/models/{id}/promote",
+    params(("id" = String, Path, description = "Registry id")),
+    responses(
+        (status = 200, description = "The promoted model", body = RegisteredModel),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such model"),
+        (status = 409, description = "The version has no predictions for its pair")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn promote_model(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<RegisteredModel>, ApiError> {
+    let Some(model) = db::get_registered_model(&state.pool, &id).await? else {
+        return Err(ApiError::ModelNotFound(id));
+    };
+    if model.status == ModelStatus::Production {
+        return Ok(Json(model));
+    }
+    if !db::has_predictions(
+        &state.pool,
+        &model.pair,
+        &model.model_name,
+        &model.model_version,
+    )
+    .await?
+    {
+        return Err(ApiError::Conflict(format!(
+            "{} {} has no predictions for {} yet",
+            model.model_name, model.model_version, model.pair
+        )));
+    }
+
+    if !db::promote_registered_model(&state.pool, &id, timestamp::now_ms()).await? {
+        return Err(ApiError::ModelNotFound(id));
+    }
+
+    tracing::info!(
+        model = %id,
+        pair = %model.pair,
+        model_name = %model.model_name,
+        model_version = %model.model_version,
+        "Model promoted"
+    );
+    get_registered_model(State(state), Path(id)).await
+}
+
+/// Roll a pair back from its production version to the one promoted
+/// before it.
+///
+/// `id` must be the production version; it is retired and the previous
+/// version restored in the same statement. Rolling back again steps
+/// further back through earlier promotions.
+#[utoipa::path(
+    post,
+    path = "/admin/models/{id}/rollback",
+    params(("id" = String, Path, description = "Registry id of the production version")),
+    responses(
+        (status = 200, description = "The restored model", body = RegisteredModel),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such model"),
+        (status = 409, description = "The version is not in production, or none was promoted before it")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn rollback_model(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<RegisteredModel>, ApiError> {
+    let Some(model) = db::get_registered_model(&state.pool, &id).await? else {
+        return Err(ApiError::ModelNotFound(id));
+    };
+    if model.status != ModelStatus::Production {
+        return Err(ApiError::Conflict(format!(
+            "{} {} is not in production for {}",
+            model.model_name, model.model_version, model.pair
+        )));
+    }
+    if !db::rollback_registered_model(&state.pool, &id, timestamp::now_ms()).await? {
+        return Err(ApiError::Conflict(format!(
+            "no version of {} was promoted before {} {}",
+            model.pair, model.model_name, model.model_version
+        )));
+    }
+
+    let restored = db::get_registered_models(
+        &state.pool,
+        &RegistryQuery {
+            pair: Some(model.pair.clone()),
+            model_name: None,
+            status: Some(ModelStatus::Production),
+        },
+    )
+    .await?
+    .into_iter()
+    .next()
+    .ok_or_else(|| ApiError::Conflict(format!("{} was changed meanwhile", model.pair)))?;
+
+    tracing::info!(
+        model = %id,
+        pair = %model.pair,
+        restored = %restored.id,
+        model_name = %restored.model_name,
+        model_version = %restored.model_version,
+        "Model rolled back"
+    );
+    Ok(Json(restored))
+}
+
+/// Remove a model from the registry. Its predictions are kept.
+#[utoipa::path(
+    delete,
+    path = "/admin/models/{id}",
+    params(("id" = String, Path, description = "Registry id")),
+    responses(
+        (status = 204, description = "Model removed"),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such model")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn delete_registered_model(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !db::delete_registered_model(&state.pool, &id).await? {
+        return Err(ApiError::ModelNotFound(id));
+    }
+
+    tracing::info!(model = %id, "Model removed");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Check that `model` only takes traffic while in staging, and that no
+/// other version of its pair does.
+async fn check_split(state: &AppState, model: &RegisteredModel) -> Result<(), ApiError> {
+    if model.traffic_pct == 0 {
+        return Ok(());
+    }
+    if model.status != ModelStatus::Staging {
+        return Err(ApiError::BadRequest(
+            "only staging versions can take a traffic_pct".to_string(),
+        ));
+    }
+
+    let query = RegistryQuery {
+        pair: Some(model.pair.clone()),
+        model_name: None,
+        status: Some(ModelStatus::Staging),
+    };
+    let other = db::get_registered_models(&state.pool, &query)
+        .await?
+        .into_iter()
+        .find(|m| m.id != model.id && m.traffic_pct > 0);
+    match other {
+        Some(other) => Err(ApiError::Conflict(format!(
+            "{} {} already takes traffic for {}; set its traffic_pct to 0 first",
+            other.model_name, other.model_version, model.pair
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn already_registered(pair: &str, model_name: &str, model_version: &str) -> ApiError {
+    ApiError::Conflict(format!(
+        "{model_name} {model_version} is already registered for {pair}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ModelRequest {
+        ModelRequest {
+            pair: "BTCUSDT".to_string(),
+            model_name: "lgbm".to_string(),
+            model_version: "v3".to_string(),
+            artifact_uri: Some("s3://models/lgbm/v3.onnx".to_string()),
+            features: vec!["rsi_14".to_string(), "ema_20".to_string()],
+            training_from_ts_ms: Some(1_700_000_000_000),
+            training_to_ts_ms: Some(1_710_000_000_000),
+            status: None,
+            traffic_pct: 10,
+        }
+    }
+
+    #[test]
+    fn validates_registrations() {
+        assert!(request().validate().is_ok());
+
+        let mut r = request();
+        r.training_to_ts_ms = r.training_from_ts_ms;
+        assert!(r.validate().is_err());
+
+        let mut r = request();
+        r.features.push("rsi_14".to_string());
+        assert!(r.validate().is_err());
+
+        let mut r = request();
+        r.pair = "BTC/USDT".to_string();
+        assert!(r.validate().is_err());
+
+        let mut r = request();
+        r.status = Some(ModelStatus::Production);
+        assert!(r.validate().is_err());
+
+        let mut r = request();
+        r.traffic_pct = 101;
+        assert!(r.validate().is_err());
+    }
+
+    #[test]
+    fn accepts_absolute_artifact_uris() {
+        assert!(validate_artifact_uri("s3://models/lgbm/v3.onnx").is_ok());
+        assert!(validate_artifact_uri("http://registry.internal/lgbm/v3").is_ok());
+        assert!(validate_artifact_uri("file:///models/v3.onnx").is_ok());
+        assert!(validate_artifact_uri("models/v3.onnx").is_err());
+        assert!(validate_artifact_uri("s3:").is_err());
+        assert!(validate_artifact_uri("3s://models").is_err());
+        assert!(validate_artifact_uri("s3://models/my model.onnx").is_err());
+    }
+
+    #[test]
+    fn round_trips_statuses() {
+        for status in [
+            ModelStatus::Staging,
+            ModelStatus::Production,
+            ModelStatus::Retired,
+        ] {
+            assert_eq!(status.as_str().parse(), Ok(status));
+        }
+        assert!("live".parse::<ModelStatus>().is_err());
+    }
+}
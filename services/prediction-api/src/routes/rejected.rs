@@ -0,0 +1,155 @@
+//! Ingested predictions that failed validation, kept for debugging for
+//! `REJECTED_PREDICTION_RETENTION_MS`.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Response,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db;
+use crate::error::ApiError;
+use crate::routes::ingest::{self, NewPrediction, WriteQuery};
+use crate::routes::predictions::{validate_model_name, Prediction};
+use crate::state::AppState;
+
+/// Rejected predictions returned when no `limit` is given.
+const DEFAULT_REJECTED: u32 = 100;
+
+/// Most rejected predictions returned per request.
+const MAX_REJECTED: u32 = 1_000;
+
+/// An ingested prediction that failed validation.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RejectedPrediction {
+    /// Rejected prediction id
+    pub id: String,
+    /// The prediction as submitted, with its pair mapped through the pair
+    /// aliases
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    /// Why validation failed
+    pub reason: String,
+    /// When the prediction was rejected (ms)
+    pub rejected_ts_ms: i64,
+}
+
+/// Query parameters for listing rejected predictions.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct RejectedQuery {
+    /// Only list predictions from this model
+    pub model_name: Option<String>,
+    /// Maximum number of rejected predictions to return (default 100, max
+    /// 1000)
+    pub limit: Option<u32>,
+}
+
+/// List rejected predictions, newest first.
+///
+/// Predictions sent to `POST /predictions` or `POST /predictions/bulk` that
+/// fail validation are kept here with the reason, until replayed or
+/// removed. Bodies that are not valid JSON predictions at all are not.
+#[utoipa::path(
+    get,
+    path = "/admin/rejected-predictions",
+    params(RejectedQuery),
+    responses(
+        (status = 200, description = "Rejected predictions, newest first", body = Vec<RejectedPrediction>),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid admin API key")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+pub async fn list_rejected(
+    State(state): State<AppState>,
+    Query(params): Query<RejectedQuery>,
+) -> Result<Json<Vec<RejectedPrediction>>, ApiError> {
+    if let Some(model_name) = &params.model_name {
+        validate_model_name(model_name)?;
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_REJECTED);
+    if limit == 0 || limit > MAX_REJECTED {
+        return Err(ApiError::BadRequest(format!(
+            "limit must be between 1 and {MAX_REJECTED}"
+        )));
+    }
+
+    Ok(Json(
+        db::get_rejected_predictions(&state.pool, params.model_name.as_deref(), limit.into())
+            .await?,
+    ))
+}
+
+/// Submit a rejected prediction again.
+///
+/// The stored payload, or a corrected prediction sent as the body, goes
+/// through `POST /predictions` as if the model had sent it, answering the
+/// same way. Once stored, the rejected prediction is removed; if it is
+/// rejected again, it stays.
+#[utoipa::path(
+    post,
+    path = "/admin/rejected-predictions/{id}/replay",
+    params(("id" = String, Path, description = "Rejected prediction id"), WriteQuery),
+    request_body(content = Option<NewPrediction>, description = "Corrected prediction; the stored payload when omitted"),
+    responses(
+        (status = 201, description = "Prediction stored", body = Prediction),
+        (status = 200, description = "Prediction stored in place of earlier ones", body = Prediction),
+        (status = 400, description = "Still invalid"),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such rejected prediction"),
+        (status = 409, description = "A prediction with the same pair, ts_ms and model_name exists")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state, corrected))]
+pub async fn replay_rejected(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<WriteQuery>,
+    corrected: Option<Json<NewPrediction>>,
+) -> Result<Response, ApiError> {
+    let Some(rejected) = db::get_rejected_prediction(&state.pool, &id).await? else {
+        return Err(ApiError::RejectedNotFound(id));
+    };
+    let prediction = match corrected {
+        Some(Json(prediction)) => prediction,
+        None => serde_json::from_value(rejected.payload).map_err(|e| {
+            ApiError::BadRequest(format!("stored payload is not a prediction: {e}"))
+        })?,
+    };
+
+    let response = ingest::ingest(&state, prediction, params.replace(), false).await?;
+    db::delete_rejected_prediction(&state.pool, &id).await?;
+
+    tracing::info!(rejected = %id, "Rejected prediction replayed");
+    Ok(response)
+}
+
+/// Remove a rejected prediction.
+#[utoipa::path(
+    delete,
+    path = "/admin/rejected-predictions/{id}",
+    params(("id" = String, Path, description = "Rejected prediction id")),
+    responses(
+        (status = 204, description = "Rejected prediction removed"),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such rejected prediction")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn delete_rejected(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !db::delete_rejected_prediction(&state.pool, &id).await? {
+        return Err(ApiError::RejectedNotFound(id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,117 @@
+//! Aggregated status endpoint for operators.
+
+use std::time::Instant;
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::middleware::pool_saturation;
+use crate::state::AppState;
+
+/// Overall or per-subsystem health.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SubsystemStatus {
+    Ok,
+    Degraded,
+    Down,
+}
+
+/// Connection pool statistics.
+#[derive(Serialize, ToSchema)]
+pub struct PoolStats {
+    /// Open connections
+    pub size: u32,
+    /// Idle connections
+    pub idle: usize,
+    /// Maximum connections
+    pub max: u32,
+    /// Fraction of the maximum currently in use
+    pub saturation: f64,
+}
+
+/// Database subsystem report.
+#[derive(Serialize, ToSchema)]
+pub struct DatabaseStatus {
+    pub status: SubsystemStatus,
+    /// Whether a trivial query succeeded
+    pub reachable: bool,
+    /// Round-trip time of the probe query (ms)
+    pub latency_ms: u64,
+    pub pool: PoolStats,
+    /// Why the subsystem is not ok
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Aggregated service status report.
+#[derive(Serialize, ToSchema)]
+pub struct StatusResponse {
+    /// Worst status across all subsystems
+    pub status: SubsystemStatus,
+    /// Package version
+    pub version: String,
+    /// Seconds since startup
+    pub uptime_secs: u64,
+    pub database: DatabaseStatus,
+}
+
+/// Aggregated service status.
+///
+/// Reports every subsystem in one call for operators. Requires the admin
+/// API key. Probes should keep using `/health` and `/ready`.
+#[utoipa::path(
+    get,
+    path = "/status",
+    responses(
+        (status = 200, description = "Status report", body = StatusResponse),
+        (status = 401, description = "Missing or invalid admin API key")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+pub async fn status(State(state): State<AppState>) -> Json<StatusResponse> {
+    let database = database_status(&state).await;
+
+    Json(StatusResponse {
+        status: database.status,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        database,
+    })
+}
+
+/// Probe the database and summarize pool health.
+async fn database_status(state: &AppState) -> DatabaseStatus {
+    // Sample the pool before the probe so it doesn't count its own connection
+    let saturation = pool_saturation(&state.pool);
+    let pool = PoolStats {
+        size: state.pool.size(),
+        idle: state.pool.num_idle(),
+        max: state.pool.options().get_max_connections(),
+        saturation,
+    };
+
+    let started = Instant::now();
+    let probe = sqlx::query("SELECT 1").execute(&state.pool).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let (status, reason) = match (&probe, &state.degraded) {
+        (Err(e), _) => (SubsystemStatus::Down, Some(format!("unreachable: {}", e))),
+        (Ok(_), Some(reason)) => (SubsystemStatus::Degraded, Some(reason.to_string())),
+        (Ok(_), None) if saturation >= state.config.load_shed_threshold => (
+            SubsystemStatus::Degraded,
+            Some("connection pool saturated".to_string()),
+        ),
+        (Ok(_), None) => (SubsystemStatus::Ok, None),
+    };
+
+    DatabaseStatus {
+        status,
+        reachable: probe.is_ok(),
+        latency_ms,
+        pool,
+        reason,
+    }
+}
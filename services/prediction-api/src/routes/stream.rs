@@ -0,0 +1,294 @@
+//! Server-Sent Events stream of new predictions.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db::{self, ModelFilter};
+use crate::error::ApiError;
+use crate::pagination::Cursor;
+use crate::routes::ingest::PredictionKey;
+use crate::routes::predictions::{validate_pair, Prediction};
+use crate::state::AppState;
+use crate::timestamp::{self, TimestampFormat, TimestampQuery};
+
+/// Predictions replayed after `Last-Event-ID` before the stream ends, so a
+/// client far behind catches up over several reconnects.
+const MAX_REPLAY: usize = 1_000;
+
+/// How often a heartbeat comment is sent on an idle stream.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Fields every `delta` event carries, changed or not.
+const DELTA_FIELDS: [&str; 3] = ["pair", "predicted_price", "ts_ms"];
+
+/// Query parameters for the prediction stream.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct StreamQuery {
+    /// Trading pair (e.g., "BTCUSDT"); all pairs when omitted
+    pub pair: Option<String>,
+    /// Send each pair's first prediction in full and then only the fields
+    /// that changed
+    pub delta: Option<bool>,
+}
+
+/// Stream new predictions as Server-Sent Events.
+///
+/// Each `prediction` event carries one prediction, serialized as the other
+/// prediction endpoints serialize it (`ts_format` applies), with its
+/// `<ts_ms>:<pair>:<model_name>` as the event id. A `heartbeat` comment is
+/// sent every 15 seconds while no predictions arrive.
+///
+/// On reconnect, predictions written after `Last-Event-ID` are replayed
+/// before live ones. If more than 1000 are missing the stream ends after the
+/// first 1000, and the client catches up by reconnecting again. The stream
+/// also ends when a client falls too far behind to be kept up to date.
+///
+/// Live predictions arrive in the order they are written, so a prediction
+/// written late may come after ones with a later `ts_ms`.
+///
+/// With `delta=true`, only each pair's first prediction on the connection
+/// is sent as a `prediction` event. Later ones are `delta` events with the
+/// fields that differ from the pair's previous event, always including
+/// `pair`, `predicted_price` and `ts_ms`, and fields no longer present as
+/// null. Clients apply them on top of what they have; after reconnecting
+/// they start again from full predictions.
+#[utoipa::path(
+    get,
+    path = "/predictions/stream",
+    params(
+        StreamQuery,
+        TimestampQuery,
+        ("Last-Event-ID" = Option<String>, Header, description = "Id of the last event received, to resume after it")
+    ),
+    responses(
+        (status = 200, description = "Stream of `prediction` events, and `delta` events with `delta=true`", content_type = "text/event-stream", body = Prediction),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "predictions"
+)]
+#[tracing::instrument(skip(state, headers))]
+pub async fn stream_predictions(
+    State(state): State<AppState>,
+    Query(params): Query<StreamQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    if let Some(pair) = &params.pair {
+        validate_pair(pair)?;
+    }
+    let resume = headers
+        .get("last-event-id")
+        .map(|id| {
+            id.to_str()
+                .ok()
+                .and_then(|id| id.parse::<Cursor>().ok())
+                .ok_or_else(|| ApiError::BadRequest("invalid Last-Event-ID".to_string()))
+        })
+        .transpose()?;
+
+    tracing::info!(pair = ?params.pair, resume = resume.is_some(), "Opening prediction stream");
+
+    // Subscribe before replaying, so nothing written meanwhile is missed
+    let receiver = state.feed.subscribe();
+
+    let (replay, complete) = match resume.clone() {
+        Some(after) => {
+            let page = db::get_history_page(
+                &state.pool,
+                params.pair.as_deref(),
+                ModelFilter::default(),
+                None,
+                after.ts_ms..=i64::MAX,
+                Some(after),
+                MAX_REPLAY,
+            )
+            .await?;
+            let complete = page.next_cursor.is_none();
+            (page.items, complete)
+        }
+        None => (Vec::new(), true),
+    };
+    // Predictions written while replaying arrive live as well
+    let mut replayed: HashSet<PredictionKey> = replay.iter().map(key).collect();
+
+    let pair = params.pair;
+    let live = stream::unfold(receiver, |mut receiver| async move {
+        match receiver.recv().await {
+            Ok(prediction) => Some((prediction, receiver)),
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "Prediction stream fell behind; closing it");
+                None
+            }
+            Err(RecvError::Closed) => None,
+        }
+    })
+    .fuse()
+    // After a partial replay the stream ends, and the client resumes from
+    // its last event
+    .take(if complete { usize::MAX } else { 0 })
+    .filter(move |prediction| {
+        std::future::ready(
+            pair.as_ref().is_none_or(|pair| *pair == prediction.pair)
+                && !replayed.remove(&key(prediction)),
+        )
+    });
+
+    // Events are serialized after the handler returns, outside the request's
+    // timestamp format scope
+    let format = timestamp::current();
+    let feed = state.feed.clone();
+    let mut last_sent = (params.delta == Some(true)).then(LastSent::default);
+    let events = stream::iter(replay.into_iter().map(Arc::new))
+        .chain(live)
+        .filter_map(move |prediction| {
+            std::future::ready(event(&prediction, format, last_sent.as_mut()).map(Ok))
+        })
+        .take_until(async move { feed.closed().await });
+
+    Ok(Sse::new(events).keep_alive(
+        KeepAlive::new()
+            .interval(HEARTBEAT_INTERVAL)
+            .text("heartbeat"),
+    ))
+}
+
+fn key(prediction: &Prediction) -> PredictionKey {
+    (
+        prediction.pair.clone(),
+        prediction.ts_ms,
+        prediction.model_name.clone(),
+    )
+}
+
+/// Last prediction sent of each pair on a delta stream.
+#[derive(Default)]
+struct LastSent(HashMap<String, Map<String, Value>>);
+
+impl LastSent {
+    /// What to send of `prediction`, serialized: `None` to send all of it,
+    /// as the first of its pair, or else its changes since the last.
+    fn diff(&mut self, prediction: &Map<String, Value>) -> Option<Map<String, Value>> {
+        let pair = prediction.get("pair")?.as_str()?.to_string();
+        let last = self.0.insert(pair, prediction.clone())?;
+
+        let mut delta: Map<String, Value> = prediction
+            .iter()
+            .filter(|(field, value)| {
+                DELTA_FIELDS.contains(&field.as_str()) || last.get(*field) != Some(value)
+            })
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect();
+        for field in last.keys() {
+            if !prediction.contains_key(field) {
+                delta.insert(field.clone(), Value::Null);
+            }
+        }
+        Some(delta)
+    }
+}
+
+/// The event for `prediction`, rendered in `format`: a `prediction`, or a
+/// `delta` against `last_sent` when the stream sends deltas.
+fn event(
+    prediction: &Prediction,
+    format: TimestampFormat,
+    last_sent: Option<&mut LastSent>,
+) -> Option<Event> {
+    timestamp::in_format(format, || {
+        let mut prediction = prediction.clone();
+        prediction.ts_iso = timestamp::iso_if_enabled(prediction.ts_ms);
+        prediction.predicted_ts_iso = prediction
+            .predicted_ts_ms
+            .and_then(timestamp::iso_if_enabled);
+
+        let event = match last_sent {
+            None => Event::default().event("prediction").json_data(&prediction),
+            Some(last_sent) => {
+                let Ok(Value::Object(full)) = serde_json::to_value(&prediction) else {
+                    tracing::error!("Failed to serialize prediction");
+                    return None;
+                };
+                match last_sent.diff(&full) {
+                    None => Event::default().event("prediction").json_data(&full),
+                    Some(delta) => Event::default().event("delta").json_data(&delta),
+                }
+            }
+        }
+        .inspect_err(|e| tracing::error!(error = %e, "Failed to serialize prediction"))
+        .ok()?;
+        // Ids cannot contain line breaks; such a prediction cannot be
+        // resumed after, but is still delivered
+        let id = Cursor::after(&prediction).to_string();
+        Some(if id.contains(['\n', '\r']) {
+            event
+        } else {
+            event.id(id)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            other => panic!("expected an object, got {other}"),
+        }
+    }
+
+    #[test]
+    fn sends_each_pair_in_full_first() {
+        let mut last_sent = LastSent::default();
+        let btc = object(json!({"pair": "BTCUSDT", "predicted_price": 65000.0, "ts_ms": 1}));
+        let eth = object(json!({"pair": "ETHUSDT", "predicted_price": 3000.0, "ts_ms": 1}));
+        assert_eq!(last_sent.diff(&btc), None);
+        assert_eq!(last_sent.diff(&eth), None);
+        assert!(last_sent.diff(&btc).is_some());
+    }
+
+    #[test]
+    fn sends_changed_and_removed_fields() {
+        let mut last_sent = LastSent::default();
+        last_sent.diff(&object(json!({
+            "pair": "BTCUSDT",
+            "predicted_price": 65000.0,
+            "ts_ms": 1,
+            "model_name": "lgbm",
+            "model_version": "v1",
+            "lower_bound": 64000.0,
+        })));
+
+        let delta = last_sent.diff(&object(json!({
+            "pair": "BTCUSDT",
+            "predicted_price": 65000.0,
+            "ts_ms": 2,
+            "model_name": "lgbm",
+            "model_version": "v2",
+        })));
+        assert_eq!(
+            delta.map(Value::Object),
+            Some(json!({
+                "pair": "BTCUSDT",
+                "predicted_price": 65000.0,
+                "ts_ms": 2,
+                "model_version": "v2",
+                "lower_bound": null,
+            }))
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! Webhook registration for server-to-server integrations.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db;
+use crate::error::ApiError;
+use crate::routes::history::MAX_BATCH_PAIRS;
+use crate::routes::predictions::validate_pair;
+use crate::state::AppState;
+use crate::timestamp;
+use crate::API_PREFIX;
+
+/// Longest webhook URL accepted.
+const MAX_URL_LEN: usize = 2048;
+
+/// Dead letters returned when no `limit` is given.
+const DEFAULT_DEAD_LETTERS: u32 = 100;
+
+/// Most dead letters returned per request.
+const MAX_DEAD_LETTERS: u32 = 1_000;
+
+/// A registered webhook.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Webhook {
+    /// Webhook id
+    pub id: String,
+    /// URL new predictions are POSTed to
+    pub url: String,
+    /// Pairs whose predictions are delivered; null for all pairs
+    pub pairs: Option<Vec<String>>,
+    /// When the webhook was registered (ms)
+    pub created_ts_ms: i64,
+    /// Key deliveries are signed with; only returned at registration
+    #[serde(skip)]
+    pub secret: String,
+}
+
+/// A newly registered webhook, with the key its deliveries are signed with.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    /// HMAC-SHA256 key for `X-Signature`; store it now, it is not shown
+    /// again
+    pub secret: String,
+}
+
+/// A delivery that failed every attempt.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeadLetter {
+    /// Dead letter id
+    pub id: String,
+    /// Webhook the delivery was for
+    pub webhook_id: String,
+    /// URL the delivery was POSTed to
+    pub url: String,
+    /// Body that was POSTed
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    /// Delivery attempts made
+    pub attempts: i32,
+    /// HTTP status of the last attempt; null when no response was received
+    pub last_status: Option<i32>,
+    /// Why the last attempt failed
+    pub last_error: String,
+    /// When the delivery was given up (ms)
+    pub failed_ts_ms: i64,
+}
+
+/// Query parameters for listing dead letters.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct DeadLetterQuery {
+    /// Maximum number of dead letters to return (default 100, max 1000)
+    pub limit: Option<u32>,
+}
+
+impl Webhook {
+    /// Whether predictions for `pair` are delivered to this webhook.
+    pub fn wants(&self, pair: &str) -> bool {
+        self.pairs
+            .as_ref()
+            .is_none_or(|pairs| pairs.iter().any(|p| p == pair))
+    }
+}
+
+/// Request body registering or changing a webhook.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WebhookRequest {
+    /// `https://` or `http://` URL to POST new predictions to
+    pub url: String,
+    /// Only deliver predictions for these pairs; all pairs when omitted
+    pub pairs: Option<Vec<String>>,
+}
+
+impl WebhookRequest {
+    /// Validate the request body.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        validate_url(&self.url)?;
+        if let Some(pairs) = &self.pairs {
+            if pairs.is_empty() {
+                return Err(ApiError::BadRequest(
+                    "pairs cannot be empty; omit it for all pairs".to_string(),
+                ));
+            }
+            if pairs.len() > MAX_BATCH_PAIRS {
+                return Err(ApiError::BadRequest(format!(
+                    "at most {MAX_BATCH_PAIRS} pairs per webhook"
+                )));
+            }
+            for pair in pairs {
+                validate_pair(pair)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validate a webhook URL: absolute, `https://` or `http://`.
+pub fn validate_url(url: &str) -> Result<(), ApiError> {
+    if url.len() > MAX_URL_LEN {
+        return Err(ApiError::BadRequest("url is too long".to_string()));
+    }
+    let uri: Uri = url
+        .parse()
+        .map_err(|_| ApiError::BadRequest("url is not a valid URL".to_string()))?;
+    if !matches!(uri.scheme_str(), Some("https" | "http")) || uri.host().is_none_or(str::is_empty) {
+        return Err(ApiError::BadRequest(
+            "url must be an absolute https:// or http:// URL".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// A new random id, for webhooks, dead letters and rejected predictions.
+pub fn new_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// A new random signing key.
+fn new_secret() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// List registered webhooks, oldest first.
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks",
+    responses(
+        (status = 200, description = "Registered webhooks", body = Vec<Webhook>),
+        (status = 401, description = "Missing or invalid admin API key")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+pub async fn list_webhooks(State(state): State<AppState>) -> Result<Json<Vec<Webhook>>, ApiError> {
+    Ok(Json(db::get_webhooks(&state.pool).await?))
+}
+
+/// Register a webhook.
+///
+/// Every prediction written from now on for a matching pair is POSTed to
+/// `url` as JSON, serialized as `GET /predictions` serializes it. Delivery
+/// is left to the one instance with `WEBHOOK_DISPATCHER_ENABLED`, which
+/// picks new webhooks up within a minute when they are registered through
+/// another; predictions written before then may not be delivered.
+///
+/// `https://` receivers must present a certificate chaining to a public
+/// root; private CAs are not trusted.
+///
+/// Each delivery carries `X-Webhook-Timestamp` (ms) and `X-Signature`:
+/// `sha256=` followed by the hex HMAC-SHA256 of `<timestamp>.<body>`, keyed
+/// with the `secret` returned here. Receivers should recompute it and
+/// reject stale timestamps.
+///
+/// Delivery is at least once: after the dispatcher restarts, predictions
+/// it delivered shortly before may be delivered again. Each delivery
+/// carries `X-Event-Id`, the same for every delivery of one write, for
+/// receivers to drop repeats by.
+///
+/// Failed deliveries (no response, 5xx, 408 or 429) are retried with
+/// exponential backoff up to `WEBHOOK_MAX_ATTEMPTS` times. Deliveries that
+/// still fail, or are rejected with another status, are kept as dead
+/// letters. So are deliveries beyond `WEBHOOK_QUEUE_SIZE` waiting for a
+/// receiver that takes `WEBHOOK_CONCURRENCY` at a time, without being
+/// attempted.
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks",
+    request_body = WebhookRequest,
+    responses(
+        (status = 201, description = "Webhook registered", body = CreatedWebhook,
+            headers(("Location" = String, description = "URL of the new webhook"))),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid admin API key")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Json(request): Json<WebhookRequest>,
+) -> Result<Response, ApiError> {
+    request.validate()?;
+
+    let webhook = Webhook {
+        id: new_id(),
+        url: request.url,
+        pairs: request.pairs,
+        created_ts_ms: timestamp::now_ms(),
+        secret: new_secret(),
+    };
+    db::insert_webhook(&state.pool, &webhook).await?;
+    state.webhooks.reload();
+
+    tracing::info!(webhook = %webhook.id, url = %webhook.url, "Webhook registered");
+    Ok((
+        StatusCode::CREATED,
+        [(
+            header::LOCATION,
+            format!("{}/admin/webhooks/{}", API_PREFIX, webhook.id),
+        )],
+        Json(CreatedWebhook {
+            secret: webhook.secret.clone(),
+            webhook,
+        }),
+    )
+        .into_response())
+}
+
+/// Get a webhook.
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/{id}",
+    params(("id" = String, Path, description = "Webhook id")),
+    responses(
+        (status = 200, description = "The webhook", body = Webhook),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such webhook")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Webhook>, ApiError> {
+    db::get_webhook(&state.pool, &id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::WebhookNotFound(id))
+}
+
+/// Change a webhook's URL and pair filter.
+#[utoipa::path(
+    put,
+    path = "/admin/webhooks/{id}",
+    params(("id" = String, Path, description = "Webhook id")),
+    request_body = WebhookRequest,
+    responses(
+        (status = 200, description = "The updated webhook", body = Webhook),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such webhook")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn update_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<WebhookRequest>,
+) -> Result<Json<Webhook>, ApiError> {
+    request.validate()?;
+
+    if !db::update_webhook(&state.pool, &id, &request.url, request.pairs.as_deref()).await? {
+        return Err(ApiError::WebhookNotFound(id));
+    }
+    state.webhooks.reload();
+
+    tracing::info!(webhook = %id, url = %request.url, "Webhook updated");
+    get_webhook(State(state), Path(id)).await
+}
+
+/// List a webhook's dead letters, newest first. Dead letters are kept for
+/// `DEAD_LETTER_RETENTION_MS`.
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/{id}/dead-letters",
+    params(("id" = String, Path, description = "Webhook id"), DeadLetterQuery),
+    responses(
+        (status = 200, description = "Deliveries that failed every attempt", body = Vec<DeadLetter>),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such webhook")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+pub async fn get_dead_letters(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<DeadLetterQuery>,
+) -> Result<Json<Vec<DeadLetter>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_DEAD_LETTERS);
+    if limit == 0 || limit > MAX_DEAD_LETTERS {
+        return Err(ApiError::BadRequest(format!(
+            "limit must be between 1 and {MAX_DEAD_LETTERS}"
+        )));
+    }
+    if db::get_webhook(&state.pool, &id).await?.is_none() {
+        return Err(ApiError::WebhookNotFound(id));
+    }
+
+    Ok(Json(
+        db::get_dead_letters(&state.pool, &id, limit.into()).await?,
+    ))
+}
+
+/// Remove a webhook and its dead letters.
+#[utoipa::path(
+    delete,
+    path = "/admin/webhooks/{id}",
+    params(("id" = String, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Webhook removed"),
+        (status = 401, description = "Missing or invalid admin API key"),
+        (status = 404, description = "No such webhook")
+    ),
+    security(("admin_api_key" = [])),
+    tag = "admin"
+)]
+#[tracing::instrument(skip(state))]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !db::delete_webhook(&state.pool, &id).await? {
+        return Err(ApiError::WebhookNotFound(id));
+    }
+    state.webhooks.reload();
+
+    tracing::info!(webhook = %id, "Webhook removed");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_only_absolute_http_and_https_urls() {
+        assert!(validate_url("http://hooks.internal:8080/predictions").is_ok());
+        assert!(validate_url("https://hooks.example.com/predictions").is_ok());
+        assert!(validate_url("ftp://hooks.example.com/predictions").is_err());
+        assert!(validate_url("/predictions").is_err());
+        assert!(validate_url("http://").is_err());
+        assert!(validate_url("not a url").is_err());
+    }
+
+    #[test]
+    fn filters_by_pair() {
+        let mut webhook = Webhook {
+            id: new_id(),
+            url: "http://hooks.internal/predictions".to_string(),
+            pairs: None,
+            created_ts_ms: 0,
+            secret: new_secret(),
+        };
+        assert!(webhook.wants("BTCUSDT"));
+
+        webhook.pairs = Some(vec!["ETHUSDT".to_string()]);
+        assert!(webhook.wants("ETHUSDT"));
+        assert!(!webhook.wants("BTCUSDT"));
+    }
+}
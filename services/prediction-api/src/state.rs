@@ -0,0 +1,52 @@
+//! Shared application state.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::FromRef;
+use sqlx::PgPool;
+use tokio::sync::Semaphore;
+
+use crate::aliases::Aliases;
+use crate::config::Config;
+use crate::feed::Feed;
+use crate::middleware::RateLimitStats;
+use crate::webhooks::Dispatcher;
+
+/// State shared by all handlers.
+///
+/// Handlers that only need the pool can keep extracting `State<PgPool>`.
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    pub config: Arc<Config>,
+    /// When the service started, for uptime reporting
+    pub started_at: Instant,
+    /// Why the service is degraded, if the startup schema probe failed.
+    /// Prediction endpoints answer 503 while this is set.
+    pub degraded: Option<Arc<str>>,
+    pub rate_limits: RateLimitGroups,
+    /// Newly written predictions, for streaming endpoints
+    pub feed: Feed,
+    /// Delivers new predictions to registered webhooks
+    pub webhooks: Dispatcher,
+    /// Pair aliases ingested symbols are mapped through
+    pub aliases: Aliases,
+    /// One permit per export that may be streamed at once
+    pub exports: Arc<Semaphore>,
+}
+
+/// Rate limiter statistics for each route group.
+#[derive(Clone, Default)]
+pub struct RateLimitGroups {
+    pub read: Arc<RateLimitStats>,
+    pub heavy: Arc<RateLimitStats>,
+    pub write: Arc<RateLimitStats>,
+    pub admin: Arc<RateLimitStats>,
+}
+
+impl FromRef<AppState> for PgPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
@@ -0,0 +1,211 @@
+//! Timestamp formatting for API responses.
+
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+/// How timestamps are rendered in prediction responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// Epoch milliseconds only (`ts_ms`)
+    #[default]
+    Ms,
+    /// ISO-8601 UTC strings only (`ts_iso`)
+    Iso,
+    /// Both representations
+    Both,
+}
+
+impl TimestampFormat {
+    pub fn includes_ms(self) -> bool {
+        matches!(self, Self::Ms | Self::Both)
+    }
+
+    pub fn includes_iso(self) -> bool {
+        matches!(self, Self::Iso | Self::Both)
+    }
+}
+
+impl FromStr for TimestampFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ms" => Ok(Self::Ms),
+            "iso" | "iso8601" => Ok(Self::Iso),
+            "both" => Ok(Self::Both),
+            other => Err(format!("unknown timestamp format: {}", other)),
+        }
+    }
+}
+
+/// Query parameter overriding the timestamp format for one request.
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct TimestampQuery {
+    /// Timestamp fields to return: "ms", "iso8601" (or "iso") or "both";
+    /// defaults to `TIMESTAMP_FORMAT`
+    pub ts_format: Option<String>,
+}
+
+/// Format chosen at startup; read by the `Prediction` serializer.
+static FORMAT: OnceLock<TimestampFormat> = OnceLock::new();
+
+tokio::task_local! {
+    /// Format requested with `ts_format` for the request being handled.
+    static REQUEST_FORMAT: TimestampFormat;
+}
+
+/// Set the process-wide timestamp format. Only the first call has an effect.
+pub fn init(format: TimestampFormat) {
+    let _ = FORMAT.set(format);
+}
+
+/// Run `f` with `format` in place of the process-wide format.
+pub async fn with_format<F: Future>(format: TimestampFormat, f: F) -> F::Output {
+    REQUEST_FORMAT.scope(format, f).await
+}
+
+/// Call `f` with `format` in place of the process-wide format; for work done
+/// outside the request's task, such as serializing a streamed body.
+pub fn in_format<R>(format: TimestampFormat, f: impl FnOnce() -> R) -> R {
+    REQUEST_FORMAT.sync_scope(format, f)
+}
+
+/// The format for the current request: its `ts_format`, or else the
+/// process-wide format.
+pub fn current() -> TimestampFormat {
+    REQUEST_FORMAT
+        .try_with(|format| *format)
+        .unwrap_or_else(|_| FORMAT.get().copied().unwrap_or_default())
+}
+
+/// `skip_serializing_if` predicate for millisecond timestamp fields.
+pub fn omit_ms<T>(_: &T) -> bool {
+    !current().includes_ms()
+}
+
+/// ISO-8601 form of `ts_ms` if the current format includes it.
+pub fn iso_if_enabled(ts_ms: i64) -> Option<String> {
+    current().includes_iso().then(|| to_iso8601(ts_ms))
+}
+
+/// Current time as a millisecond Unix timestamp.
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+/// Format epoch milliseconds as an ISO-8601 UTC string,
+/// e.g. `2023-11-14T22:13:20.000Z`.
+pub fn to_iso8601(ts_ms: i64) -> String {
+    let days = ts_ms.div_euclid(86_400_000);
+    let ms_of_day = ts_ms.rem_euclid(86_400_000);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        ms_of_day / 3_600_000,
+        ms_of_day / 60_000 % 60,
+        ms_of_day / 1_000 % 60,
+        ms_of_day % 1_000
+    )
+}
+
+/// Parse a duration such as `300s`, `5m`, `4h` or `1d` into milliseconds.
+///
+/// A bare number is taken as milliseconds. Returns `None` for malformed,
+/// non-positive or overflowing input.
+pub fn parse_duration_ms(s: &str) -> Option<i64> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let unit_ms = match unit {
+        "" | "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => return None,
+    };
+    value
+        .parse::<i64>()
+        .ok()
+        .and_then(|v| v.checked_mul(unit_ms))
+        .filter(|ms| *ms > 0)
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) civil date.
+///
+/// Howard Hinnant's algorithm for the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_epoch() {
+        assert_eq!(to_iso8601(0), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn formats_millisecond_timestamps() {
+        assert_eq!(to_iso8601(1_700_000_000_123), "2023-11-14T22:13:20.123Z");
+        assert_eq!(to_iso8601(951_782_400_000), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn formats_pre_epoch_timestamps() {
+        assert_eq!(to_iso8601(-1), "1969-12-31T23:59:59.999Z");
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration_ms("300000"), Some(300_000));
+        assert_eq!(parse_duration_ms("250ms"), Some(250));
+        assert_eq!(parse_duration_ms("300s"), Some(300_000));
+        assert_eq!(parse_duration_ms("5m"), Some(300_000));
+        assert_eq!(parse_duration_ms("4h"), Some(14_400_000));
+        assert_eq!(parse_duration_ms("1d"), Some(86_400_000));
+    }
+
+    #[test]
+    fn rejects_bad_durations() {
+        assert_eq!(parse_duration_ms(""), None);
+        assert_eq!(parse_duration_ms("h"), None);
+        assert_eq!(parse_duration_ms("0s"), None);
+        assert_eq!(parse_duration_ms("-5m"), None);
+        assert_eq!(parse_duration_ms("4 h"), None);
+        assert_eq!(parse_duration_ms("1w"), None);
+        assert_eq!(parse_duration_ms("99999999999999999d"), None);
+    }
+
+    #[tokio::test]
+    async fn request_format_overrides_process_format() {
+        assert_eq!(
+            "iso8601".parse::<TimestampFormat>(),
+            Ok(TimestampFormat::Iso)
+        );
+        let format = with_format(TimestampFormat::Both, async { current() }).await;
+        assert_eq!(format, TimestampFormat::Both);
+        assert_eq!(current(), TimestampFormat::default());
+    }
+}
@@ -0,0 +1,608 @@
+//! Delivery of new predictions to registered webhooks.
+//!
+//! Deliveries go out over HTTP or HTTPS, trusting the Mozilla root
+//! certificates of `webpki-roots`.
+//!
+//! The dispatcher reads the `prediction_events` outbox and POSTs each
+//! prediction, as JSON, to every webhook registered before it was written
+//! whose pair filter matches. Deliveries are signed, transient failures are
+//! retried with exponential backoff, and deliveries that cannot be made are
+//! kept as dead letters. Each webhook gets a bounded number of deliveries
+//! at a time and in waiting, so a slow receiver cannot pile up deliveries
+//! without limit; those beyond are dead-lettered too. The webhook list is
+//! cached and reloaded when it changes through this replica's admin API, and
+//! periodically to pick up changes made through other replicas.
+//!
+//! The dispatcher saves how far it got in the outbox, moving past an event
+//! only once each of its deliveries was made or dead-lettered, and resumes
+//! from there after a restart. Deliveries are therefore at least once: the
+//! ones under way on a restart, and those of the events relayed in the
+//! minute or so before it, are made again, with the same `X-Event-Id`.
+//! Events are kept for `PREDICTION_EVENT_RETENTION_MS`; a dispatcher down
+//! for longer misses the older ones.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use axum::http::{header, Method, Request, StatusCode};
+use hmac::{Hmac, Mac};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::rt::TokioExecutor;
+use sha2::Sha256;
+use sqlx::PgPool;
+use tokio::sync::{watch, Notify, Semaphore};
+
+use crate::db::{self, Event};
+use crate::error::ApiError;
+use crate::feed::Relay;
+use crate::routes::webhooks::{new_id, DeadLetter, Webhook};
+use crate::timestamp;
+
+/// How often the webhook list is reloaded from the database.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name the dispatcher saves its outbox offset under.
+const CONSUMER: &str = "webhooks";
+
+/// `User-Agent` of webhook deliveries.
+const USER_AGENT: &str = concat!("prediction-api/", env!("CARGO_PKG_VERSION"));
+
+type HttpClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+/// How deliveries are attempted.
+#[derive(Debug, Clone, Copy)]
+pub struct Delivery {
+    /// How long one attempt may take
+    pub timeout: Duration,
+    /// Attempts before a delivery is given up
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubles with each further one
+    pub retry_base: Duration,
+    /// Deliveries made to one webhook at a time
+    pub concurrency: usize,
+    /// Deliveries to one webhook that may wait for a turn
+    pub queue_size: usize,
+}
+
+/// Handle to the dispatcher; cheap to clone.
+#[derive(Clone)]
+pub struct Dispatcher {
+    reload: Arc<Notify>,
+}
+
+impl Dispatcher {
+    /// Start delivering predictions, polling the outbox every `interval`
+    /// until `shutdown` turns true. A disabled dispatcher delivers nothing,
+    /// for replicas that leave delivery to another one.
+    pub fn start(
+        pool: PgPool,
+        interval: Duration,
+        delivery: Delivery,
+        enabled: bool,
+        shutdown: watch::Receiver<bool>,
+    ) -> Self {
+        let reload = Arc::new(Notify::new());
+        if enabled {
+            let connector = HttpsConnectorBuilder::new()
+                .with_webpki_roots()
+                .https_or_http()
+                .enable_http1()
+                .build();
+            let client = Client::builder(TokioExecutor::new()).build(connector);
+            tokio::spawn(run(
+                pool,
+                client,
+                interval,
+                delivery,
+                reload.clone(),
+                shutdown,
+            ));
+        }
+        Self { reload }
+    }
+
+    /// Reload the webhook list, after it was changed.
+    pub fn reload(&self) {
+        self.reload.notify_one();
+    }
+}
+
+/// What woke the dispatcher up.
+enum Wake {
+    Reload,
+    Poll,
+    Shutdown,
+}
+
+async fn run(
+    pool: PgPool,
+    client: HttpClient,
+    interval: Duration,
+    delivery: Delivery,
+    reload: Arc<Notify>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut webhooks: Vec<Arc<Webhook>> = Vec::new();
+    let mut lanes: HashMap<String, Lane> = HashMap::new();
+    // Only reading the outbox while there are webhooks
+    let mut relay: Option<Relay> = None;
+    let mut saved_ms: Option<i64> = None;
+    let in_flight = InFlight::default();
+    let mut reload_timer = tokio::time::interval(RELOAD_INTERVAL);
+    let mut poll_timer = tokio::time::interval(interval);
+    poll_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        let wake = tokio::select! {
+            _ = reload_timer.tick() => Wake::Reload,
+            _ = reload.notified() => Wake::Reload,
+            _ = poll_timer.tick() => Wake::Poll,
+            _ = shutdown.wait_for(|&closed| closed) => Wake::Shutdown,
+        };
+
+        match wake {
+            Wake::Reload => match db::get_webhooks(&pool).await {
+                Ok(loaded) => {
+                    webhooks = loaded.into_iter().map(Arc::new).collect();
+                    lanes.retain(|id, _| webhooks.iter().any(|webhook| &webhook.id == id));
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to load webhooks"),
+            },
+            Wake::Poll if webhooks.is_empty() => relay = None,
+            Wake::Poll => {
+                let relay = match relay {
+                    Some(ref mut relay) => relay,
+                    None => match resume(&pool, &webhooks).await {
+                        Ok((resumed, settled_ms)) => {
+                            saved_ms = settled_ms;
+                            relay.insert(resumed)
+                        }
+                        Err(e) => {
+                            tracing::warn!(error = %e, "Failed to load webhook outbox offset");
+                            continue;
+                        }
+                    },
+                };
+                match relay.poll(&pool).await {
+                    Ok(events) => {
+                        for event in events {
+                            dispatch(
+                                &client, &pool, delivery, &webhooks, &mut lanes, &in_flight, event,
+                            );
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Webhook outbox poll failed");
+                        continue;
+                    }
+                }
+
+                // Stay behind the events still being delivered
+                let settled_ms = in_flight.oldest().map_or(relay.settled_ms(), |oldest| {
+                    relay.settled_ms().min(oldest - 1)
+                });
+                if saved_ms.is_none_or(|saved_ms| settled_ms > saved_ms) {
+                    match db::save_event_offset(&pool, CONSUMER, settled_ms).await {
+                        Ok(()) => saved_ms = Some(settled_ms),
+                        Err(e) => {
+                            tracing::warn!(error = %e, "Failed to save webhook outbox offset")
+                        }
+                    }
+                }
+            }
+            Wake::Shutdown => break,
+        }
+    }
+
+    tracing::debug!("Webhook dispatcher stopped");
+}
+
+/// Where to resume reading the outbox for `webhooks`, and the offset saved
+/// before. Without one, delivery starts with the events written from now
+/// on.
+async fn resume(
+    pool: &PgPool,
+    webhooks: &[Arc<Webhook>],
+) -> Result<(Relay, Option<i64>), ApiError> {
+    let saved_ms = db::get_event_offset(pool, CONSUMER).await?;
+    let after_ms = match saved_ms {
+        // Events from before every webhook was registered are wanted by none
+        Some(saved_ms) => webhooks
+            .iter()
+            .map(|webhook| webhook.created_ts_ms - 1)
+            .min()
+            .map_or(saved_ms, |registered_ms| saved_ms.max(registered_ms)),
+        None => timestamp::now_ms(),
+    };
+    Ok((Relay::after(after_ms), saved_ms))
+}
+
+/// Start delivering `event`'s prediction to every webhook that wants it,
+/// through the webhook's lane.
+fn dispatch(
+    client: &HttpClient,
+    pool: &PgPool,
+    delivery: Delivery,
+    webhooks: &[Arc<Webhook>],
+    lanes: &mut HashMap<String, Lane>,
+    in_flight: &InFlight,
+    event: Event,
+) {
+    let Some(prediction) = event.prediction else {
+        return;
+    };
+    let targets: Vec<&Arc<Webhook>> = webhooks
+        .iter()
+        .filter(|webhook| {
+            webhook.created_ts_ms <= event.created_ts_ms && webhook.wants(&prediction.pair)
+        })
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+
+    let body = match serde_json::to_vec(&prediction) {
+        Ok(body) => Bytes::from(body),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to serialize prediction");
+            return;
+        }
+    };
+
+    for webhook in targets {
+        let lane = lanes
+            .entry(webhook.id.clone())
+            .or_insert_with(|| Lane::new(delivery.concurrency));
+        let tracked = in_flight.track(event.created_ts_ms);
+        let Some(queued) = lane.enter(delivery.concurrency + delivery.queue_size) else {
+            tracing::warn!(webhook = %webhook.id, "Webhook delivery queue full");
+            let (pool, webhook, body) = (pool.clone(), webhook.clone(), body.clone());
+            tokio::spawn(async move {
+                let failure = Failure::Error("delivery queue full".to_string());
+                dead_letter(&pool, &webhook, &body, 0, &failure).await;
+                drop(tracked);
+            });
+            continue;
+        };
+        tokio::spawn(deliver(
+            client.clone(),
+            pool.clone(),
+            delivery,
+            lane.permits.clone(),
+            Job {
+                webhook: webhook.clone(),
+                event_id: event.id.clone(),
+                body: body.clone(),
+                _tracked: tracked,
+                _queued: queued,
+            },
+        ));
+    }
+}
+
+/// One prediction to deliver to one webhook.
+struct Job {
+    webhook: Arc<Webhook>,
+    event_id: String,
+    body: Bytes,
+    _tracked: Tracked,
+    _queued: Queued,
+}
+
+/// The deliveries pending for one webhook.
+#[derive(Debug)]
+struct Lane {
+    /// One per delivery that may be made at a time
+    permits: Arc<Semaphore>,
+    /// Deliveries under way or waiting for a permit
+    pending: Arc<AtomicUsize>,
+}
+
+impl Lane {
+    fn new(concurrency: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(concurrency)),
+            pending: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Take a place in the lane, unless `capacity` deliveries are pending.
+    fn enter(&self, capacity: usize) -> Option<Queued> {
+        self.pending
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |pending| {
+                (pending < capacity).then_some(pending + 1)
+            })
+            .ok()
+            .map(|_| Queued(self.pending.clone()))
+    }
+}
+
+/// A place in a webhook's lane, given up when dropped.
+#[derive(Debug)]
+struct Queued(Arc<AtomicUsize>);
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Write times of the events with deliveries under way.
+#[derive(Debug, Clone, Default)]
+struct InFlight(Arc<Mutex<BTreeMap<i64, usize>>>);
+
+impl InFlight {
+    /// Count a delivery of an event written at `created_ts_ms` until the
+    /// returned guard is dropped.
+    fn track(&self, created_ts_ms: i64) -> Tracked {
+        *self
+            .0
+            .lock()
+            .expect("in-flight lock poisoned")
+            .entry(created_ts_ms)
+            .or_default() += 1;
+        Tracked {
+            in_flight: self.clone(),
+            created_ts_ms,
+        }
+    }
+
+    /// Write time of the oldest event with a delivery under way.
+    fn oldest(&self) -> Option<i64> {
+        let counts = self.0.lock().expect("in-flight lock poisoned");
+        counts.keys().next().copied()
+    }
+}
+
+/// A delivery counted in `InFlight` until it ends.
+#[derive(Debug)]
+struct Tracked {
+    in_flight: InFlight,
+    created_ts_ms: i64,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let mut counts = self.in_flight.0.lock().expect("in-flight lock poisoned");
+        if let Some(count) = counts.get_mut(&self.created_ts_ms) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.created_ts_ms);
+            }
+        }
+    }
+}
+
+/// Why a delivery attempt failed.
+#[derive(Debug)]
+enum Failure {
+    /// The webhook answered with a non-2xx status
+    Status(StatusCode),
+    /// No response: connection error or timeout
+    Error(String),
+}
+
+impl Failure {
+    /// Whether another attempt may succeed. Other 4xx answers mean the
+    /// receiver rejects the delivery itself.
+    fn retryable(&self) -> bool {
+        match self {
+            Failure::Status(status) => {
+                status.is_server_error()
+                    || *status == StatusCode::REQUEST_TIMEOUT
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Failure::Error(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Status(status) => write!(f, "webhook answered {}", status),
+            Failure::Error(error) => f.write_str(error),
+        }
+    }
+}
+
+/// Delay before retrying after `attempt` failed attempts: `base`, doubling
+/// with each attempt.
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(1 << attempt.saturating_sub(1).min(16))
+}
+
+/// `X-Signature` of a delivery: `sha256=` and the hex HMAC-SHA256 of
+/// `<timestamp_ms>.<body>`, keyed with the webhook's secret.
+pub fn signature(secret: &str, timestamp_ms: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp_ms.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// POST one prediction to one webhook once one of `permits` is free,
+/// retrying transient failures and recording a dead letter when it cannot
+/// be delivered.
+async fn deliver(
+    client: HttpClient,
+    pool: PgPool,
+    delivery: Delivery,
+    permits: Arc<Semaphore>,
+    job: Job,
+) {
+    let Ok(_permit) = permits.acquire_owned().await else {
+        return;
+    };
+    let Job {
+        webhook,
+        event_id,
+        body,
+        ..
+    } = &job;
+
+    let mut attempt = 0;
+    let failure = loop {
+        attempt += 1;
+        let attempted = attempt_delivery(&client, delivery.timeout, webhook, event_id, body).await;
+        let failure = match attempted {
+            Ok(()) => {
+                tracing::debug!(webhook = %webhook.id, attempt, "Webhook delivered");
+                return;
+            }
+            Err(failure) => failure,
+        };
+        if !failure.retryable() || attempt >= delivery.max_attempts {
+            break failure;
+        }
+
+        let delay = backoff(delivery.retry_base, attempt);
+        tracing::info!(
+            webhook = %webhook.id,
+            attempt,
+            retry_in_ms = delay.as_millis() as u64,
+            error = %failure,
+            "Webhook delivery failed, retrying"
+        );
+        tokio::time::sleep(delay).await;
+    };
+
+    tracing::warn!(webhook = %webhook.id, attempt, error = %failure, "Webhook delivery failed");
+    dead_letter(&pool, webhook, body, attempt, &failure).await;
+}
+
+/// Keep a delivery of `body` to `webhook` that failed after `attempts`
+/// attempts.
+async fn dead_letter(
+    pool: &PgPool,
+    webhook: &Webhook,
+    body: &Bytes,
+    attempts: u32,
+    failure: &Failure,
+) {
+    let dead_letter = DeadLetter {
+        id: new_id(),
+        webhook_id: webhook.id.clone(),
+        url: webhook.url.clone(),
+        payload: serde_json::from_slice(body).unwrap_or_default(),
+        attempts: attempts as i32,
+        last_status: match failure {
+            Failure::Status(status) => Some(status.as_u16().into()),
+            Failure::Error(_) => None,
+        },
+        last_error: failure.to_string(),
+        failed_ts_ms: timestamp::now_ms(),
+    };
+    if let Err(e) = db::insert_dead_letter(pool, &dead_letter).await {
+        tracing::error!(webhook = %webhook.id, error = %e, "Failed to record dead letter");
+    }
+}
+
+/// Make one signed delivery attempt.
+async fn attempt_delivery(
+    client: &HttpClient,
+    timeout: Duration,
+    webhook: &Webhook,
+    event_id: &str,
+    body: &Bytes,
+) -> Result<(), Failure> {
+    let timestamp_ms = timestamp::now_ms();
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(&webhook.url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::USER_AGENT, USER_AGENT)
+        .header("x-webhook-id", &webhook.id)
+        .header("x-event-id", event_id)
+        .header("x-webhook-timestamp", timestamp_ms)
+        .header(
+            "x-signature",
+            signature(&webhook.secret, timestamp_ms, body),
+        )
+        .body(Body::from(body.clone()))
+        .map_err(|e| Failure::Error(format!("invalid request: {}", e)))?;
+
+    match tokio::time::timeout(timeout, client.request(request)).await {
+        Ok(Ok(response)) if response.status().is_success() => Ok(()),
+        Ok(Ok(response)) => Err(Failure::Status(response.status())),
+        Ok(Err(e)) => Err(Failure::Error(error_chain(&e))),
+        Err(_) => Err(Failure::Error("timed out".to_string())),
+    }
+}
+
+/// An error with its causes, e.g. the TLS error behind a failed connect.
+pub fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        // echo -n '1700000000000.{"pair":"BTCUSDT"}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            signature("secret", 1_700_000_000_000, br#"{"pair":"BTCUSDT"}"#),
+            "sha256=35797c02c23da7e11c29e5600b9563e77c5e878736a87f8b0e6baa1194e8d10e"
+        );
+    }
+
+    #[test]
+    fn retries_only_transient_failures() {
+        assert!(Failure::Error("connection refused".to_string()).retryable());
+        assert!(Failure::Status(StatusCode::BAD_GATEWAY).retryable());
+        assert!(Failure::Status(StatusCode::TOO_MANY_REQUESTS).retryable());
+        assert!(!Failure::Status(StatusCode::BAD_REQUEST).retryable());
+        assert!(!Failure::Status(StatusCode::GONE).retryable());
+    }
+
+    #[test]
+    fn tracks_oldest_delivery_under_way() {
+        let in_flight = InFlight::default();
+        let first = in_flight.track(100);
+        let second = in_flight.track(100);
+        let later = in_flight.track(200);
+        assert_eq!(in_flight.oldest(), Some(100));
+
+        drop(first);
+        assert_eq!(in_flight.oldest(), Some(100));
+        drop(second);
+        assert_eq!(in_flight.oldest(), Some(200));
+        drop(later);
+        assert_eq!(in_flight.oldest(), None);
+    }
+
+    #[test]
+    fn bounds_pending_deliveries() {
+        let lane = Lane::new(1);
+        let first = lane.enter(2).unwrap();
+        let _second = lane.enter(2).unwrap();
+        assert!(lane.enter(2).is_none());
+
+        drop(first);
+        assert!(lane.enter(2).is_some());
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let base = Duration::from_secs(1);
+        assert_eq!(backoff(base, 1), Duration::from_secs(1));
+        assert_eq!(backoff(base, 2), Duration::from_secs(2));
+        assert_eq!(backoff(base, 4), Duration::from_secs(8));
+    }
+}
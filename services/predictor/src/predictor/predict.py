@@ -2,6 +2,7 @@
 
 import os
 import time
+import uuid
 from datetime import UTC, datetime
 
 import pandas as pd
@@ -10,6 +11,9 @@ from loguru import logger
 
 from predictor.model_registry import get_model_name, load_model
 
+# Outbox the prediction API relays new predictions from
+EVENTS_TABLE = "prediction_events"
+
 
 def get_latest_indicators(
     conn,
@@ -58,6 +62,12 @@ def write_prediction(
 ) -> None:
     """Write prediction to RisingWave.
 
+    The prediction is first appended to the prediction_events outbox. The
+    prediction API relays an event only once its prediction is stored, so
+    without transactions, which RisingWave lacks for writes, a prediction is
+    still published exactly when it is stored. Each statement commits on its
+    own (the connection is in autocommit mode).
+
     Args:
         conn: Database connection
         table: Output table name
@@ -69,24 +79,55 @@ def write_prediction(
         predicted_ts_ms: Predicted timestamp (ms)
     """
     cursor = conn.cursor()
+    cursor.execute(  # nosemgrep
+        f"""
+        INSERT INTO {EVENTS_TABLE}
+            (id, predicted_price, pair, ts_ms, model_name, model_version, predicted_ts_ms,
+             created_ts_ms)
+        VALUES (%s, %s, %s, %s, %s, %s, %s, %s)
+        """,
+        (
+            uuid.uuid4().hex,
+            predicted_price,
+            pair,
+            ts_ms,
+            model_name,
+            model_version,
+            predicted_ts_ms,
+            int(time.time() * 1000),
+        ),
+    )
     # nosemgrep: python.sqlalchemy.security.sqlalchemy-execute-raw-query
-    # Table name is from internal config, not user input
+    # Table name is from internal config, not user input. ts_ms is the
+    # current time, so the key (pair, ts_ms, model_name) is new
     query = f"""
     INSERT INTO {table} (predicted_price, pair, ts_ms, model_name, model_version, predicted_ts_ms)
     VALUES (%s, %s, %s, %s, %s, %s)
-    ON CONFLICT (pair, ts_ms, model_name) DO UPDATE
-    SET predicted_price = EXCLUDED.predicted_price,
-        model_version = EXCLUDED.model_version,
-        predicted_ts_ms = EXCLUDED.predicted_ts_ms
     """
     cursor.execute(  # nosemgrep
         query,
         (predicted_price, pair, ts_ms, model_name, model_version, predicted_ts_ms),
     )
-    conn.commit()
     cursor.close()
 
 
+def connect(**kwargs):
+    """Connect to RisingWave in autocommit mode.
+
+    RisingWave has no read-write transactions, so every statement commits
+    on its own.
+
+    Args:
+        **kwargs: psycopg2 connection parameters
+
+    Returns:
+        Database connection
+    """
+    conn = psycopg2.connect(**kwargs)
+    conn.autocommit = True
+    return conn
+
+
 def predict(
     mlflow_tracking_uri: str,
     risingwave_host: str,
@@ -134,7 +175,7 @@ def predict(
 
     # Connect to RisingWave
     logger.info(f"Connecting to RisingWave at {risingwave_host}:{risingwave_port}")
-    conn = psycopg2.connect(
+    conn = connect(
         host=risingwave_host,
         port=risingwave_port,
         user=risingwave_user,
@@ -199,7 +240,7 @@ def predict(
                 conn.close()
             except Exception:
                 pass
-            conn = psycopg2.connect(
+            conn = connect(
                 host=risingwave_host,
                 port=risingwave_port,
                 user=risingwave_user,
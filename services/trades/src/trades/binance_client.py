@@ -37,6 +37,7 @@ class BinanceHistoricalClient:
     - Multi-symbol support with round-robin fetching
     - Rate limit handling via SDK
     - Time-based pagination (1-hour chunks)
+    - Last N days, or an explicit [start, end) range
     - Exponential backoff on errors
     """
 
@@ -46,9 +47,16 @@ class BinanceHistoricalClient:
         self.last_n_days = config.last_n_days
         self._is_done = False
 
-        # Calculate time range
-        self.end_time_ms = int(time.time() * 1000)
-        base_start = int((time.time() - self.last_n_days * 24 * 60 * 60) * 1000)
+        # Calculate time range; end_time_ms is exclusive
+        now = time.time()
+        if config.historical_end_ms is not None:
+            self.end_time_ms = config.historical_end_ms
+        else:
+            self.end_time_ms = int(now * 1000)
+        if config.historical_start_ms is not None:
+            base_start = config.historical_start_ms
+        else:
+            base_start = int((now - self.last_n_days * 24 * 60 * 60) * 1000)
 
         # State for each symbol: {symbol: start_time_ms}
         self._symbol_state = dict.fromkeys(self.product_ids, base_start)
@@ -99,10 +107,11 @@ class BinanceHistoricalClient:
         end_time = min(start_time + 3600000, self.end_time_ms)  # 1 hour chunk
 
         try:
+            # Binance's endTime is inclusive
             response = self.client.rest_api.compressed_aggregate_trades_list(
                 symbol=symbol,
                 start_time=start_time,
-                end_time=end_time,
+                end_time=end_time - 1,
                 limit=1000,
             )
 
@@ -1,5 +1,7 @@
+import time
 from typing import Literal
 
+from pydantic import model_validator
 from pydantic_settings import BaseSettings, SettingsConfigDict
 
 
@@ -29,6 +31,11 @@ class Settings(BaseSettings):
     # Data source mode
     live_or_historical: Literal["live", "historical"] = "live"
     last_n_days: int = 30
+    # Explicit [start, end) range for historical mode, in ms, e.g. a gap
+    # reported by the prediction API's /v1/prices/gaps. The start replaces
+    # last_n_days; the end defaults to now.
+    historical_start_ms: int | None = None
+    historical_end_ms: int | None = None
 
     # Binance API settings (optional, for authenticated endpoints)
     binance_api_key: str | None = None
@@ -39,5 +46,21 @@ class Settings(BaseSettings):
     rest_api_retries: int = 3
     websocket_reconnect_delay: int = 5000  # milliseconds
 
+    @model_validator(mode="after")
+    def check_historical_range(self) -> "Settings":
+        if self.historical_start_ms is None and self.historical_end_ms is None:
+            return self
+        now_ms = int(time.time() * 1000)
+        start_ms = self.historical_start_ms
+        if start_ms is None:
+            start_ms = now_ms - self.last_n_days * 24 * 60 * 60 * 1000
+        end_ms = self.historical_end_ms if self.historical_end_ms is not None else now_ms
+        if start_ms >= end_ms:
+            raise ValueError(
+                "historical_start_ms (or now - last_n_days) must be before "
+                "historical_end_ms (or now)"
+            )
+        return self
+
 
 config = Settings()
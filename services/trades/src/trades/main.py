@@ -101,9 +101,14 @@ def main():
         )
 
     elif config.live_or_historical == "historical":
+        if config.historical_start_ms is not None:
+            period = (
+                f"from {config.historical_start_ms} to {config.historical_end_ms or 'now'} (ms)"
+            )
+        else:
+            period = f"last {config.last_n_days} days"
         logger.info(
-            f"Starting historical data ingestion for {len(config.product_ids)} symbols, "
-            f"last {config.last_n_days} days"
+            f"Starting historical data ingestion for {len(config.product_ids)} symbols, {period}"
         )
         client = BinanceHistoricalClient(config)
         run_historical(
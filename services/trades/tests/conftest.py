@@ -79,6 +79,8 @@ def mock_settings():
     settings.kafka_topic_name = "test-trades"
     settings.live_or_historical = "live"
     settings.last_n_days = 30
+    settings.historical_start_ms = None
+    settings.historical_end_ms = None
     settings.binance_api_key = None
     settings.binance_api_secret = None
     settings.rest_api_timeout = 30000
@@ -116,6 +116,30 @@ class TestBinanceHistoricalClientInit:
             for start in client._symbol_state.values():
                 assert abs(start - expected_start) < 1000
 
+    def test_init_explicit_range(self, mock_settings):
+        """Test historical_start_ms/historical_end_ms replace last_n_days."""
+        mock_settings.historical_start_ms = 1732636800000
+        mock_settings.historical_end_ms = 1732640400000
+        with patch("trades.binance_client.DerivativesTradingUsdsFutures"):
+            from trades.binance_client import BinanceHistoricalClient
+
+            client = BinanceHistoricalClient(mock_settings)
+            assert client.end_time_ms == 1732640400000
+            for start in client._symbol_state.values():
+                assert start == 1732636800000
+
+    def test_init_explicit_zero_range(self, mock_settings):
+        """Test an explicit 0 is used, not treated as unset."""
+        mock_settings.historical_start_ms = 0
+        mock_settings.historical_end_ms = 60000
+        with patch("trades.binance_client.DerivativesTradingUsdsFutures"):
+            from trades.binance_client import BinanceHistoricalClient
+
+            client = BinanceHistoricalClient(mock_settings)
+            assert client.end_time_ms == 60000
+            for start in client._symbol_state.values():
+                assert start == 0
+
 
 class TestBinanceHistoricalClientGetNextSymbol:
     """Test BinanceHistoricalClient._get_next_symbol method."""
@@ -385,6 +409,29 @@ class TestBinanceHistoricalClientGetTrades:
             # Cursor should be updated to last trade time + 1
             assert client._symbol_state["BTCUSDT"] == 1732636801001
 
+    def test_get_trades_excludes_range_end(self, mock_settings, mock_sdk_rest_api):
+        """Test the end of the range is exclusive."""
+        mock_settings.historical_start_ms = 1732636800000
+        mock_settings.historical_end_ms = 1732636860000
+        with patch("trades.binance_client.DerivativesTradingUsdsFutures") as mock_client_class:
+            mock_client = MagicMock()
+            mock_client.rest_api = mock_sdk_rest_api
+            mock_client_class.return_value = mock_client
+
+            from trades.binance_client import BinanceHistoricalClient
+
+            client = BinanceHistoricalClient(mock_settings)
+            client.client = mock_client
+
+            client.get_trades()
+
+            mock_sdk_rest_api.compressed_aggregate_trades_list.assert_called_once_with(
+                symbol="BTCUSDT",
+                start_time=1732636800000,
+                end_time=1732636859999,
+                limit=1000,
+            )
+
     def test_get_trades_sets_done_when_all_complete(self, mock_settings):
         """Test is_done is set when all symbols complete."""
         with patch("trades.binance_client.DerivativesTradingUsdsFutures") as mock_client_class:
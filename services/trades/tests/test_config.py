@@ -175,6 +175,49 @@ class TestSettingsLoading:
 class TestSettingsValidation:
     """Test settings validation."""
 
+    def test_historical_range_end_after_start(self, env_vars):
+        """Test historical_start_ms must be before historical_end_ms."""
+        with env_vars(
+            kafka_broker_address="localhost:9092",
+            kafka_topic_name="trades",
+        ):
+            from trades.config import Settings
+
+            settings = Settings(
+                kafka_broker_address="localhost:9092",
+                kafka_topic_name="trades",
+                historical_start_ms=1732636800000,
+                historical_end_ms=1732640400000,
+            )
+            assert settings.historical_start_ms == 1732636800000
+            assert settings.historical_end_ms == 1732640400000
+
+            with pytest.raises(ValidationError) as exc_info:
+                Settings(
+                    kafka_broker_address="localhost:9092",
+                    kafka_topic_name="trades",
+                    historical_start_ms=1732640400000,
+                    historical_end_ms=1732636800000,
+                )
+            assert "historical_start_ms" in str(exc_info.value)
+
+    def test_historical_end_before_last_n_days_raises(self, env_vars):
+        """Test an end on its own must fall after now - last_n_days."""
+        with env_vars(
+            kafka_broker_address="localhost:9092",
+            kafka_topic_name="trades",
+        ):
+            from trades.config import Settings
+
+            with pytest.raises(ValidationError) as exc_info:
+                Settings(
+                    kafka_broker_address="localhost:9092",
+                    kafka_topic_name="trades",
+                    last_n_days=1,
+                    historical_end_ms=1732636800000,
+                )
+            assert "historical_end_ms" in str(exc_info.value)
+
     def test_missing_kafka_broker_address_raises(self, env_vars):
         """Test missing kafka_broker_address raises error."""
         with env_vars(